@@ -5,6 +5,7 @@ use crate::types::*;
 use crate::movement::*;
 use crate::messaging::*;
 use crate::utils::*;
+use crate::map::TileType;
 use crate::config::Config;
 
 
@@ -46,6 +47,21 @@ pub fn ai_take_turn(monster_id: EntityId,
                     _msg_log: &mut MsgLog) -> Action {
     let mut turn: Action = Action::NoAction;
 
+    if ai_is_caged(data, monster_id) {
+        return Action::Pass;
+    }
+
+    // stunned monsters lose their turns until they recover
+    if let Some(turns) = data.entities.stunned.get(&monster_id).map(|turns| *turns) {
+        if turns <= 1 {
+            data.entities.stunned.remove(&monster_id);
+        } else {
+            data.entities.stunned[&monster_id] = turns - 1;
+        }
+
+        return Action::Pass;
+    }
+
     if data.entities.alive[&monster_id] {
         match data.entities.ai.get(&monster_id) {
             Some(Ai::Basic) => {
@@ -93,6 +109,9 @@ pub fn ai_attack(monster_id: EntityId,
                           config) {
         let attack = Attack::Attack(target_id);
         turn = Action::Move(Movement::attack(hit_pos, MoveType::Move, attack));
+    } else if let Some(item_id) = ai_can_throw_at_target(data, monster_id, target_pos, config) {
+        // if AI can't hit their target, but has something to throw at them
+        turn = Action::ThrowItem(target_pos, item_id);
     } else if data.map.is_blocked_by_wall(monster_pos, target_pos.x - monster_pos.x, target_pos.y - monster_pos.y).is_some() {
         // path to target is blocked by a wall- investigate the last known position
         turn = Action::StateChange(Behavior::Investigating(target_pos));
@@ -134,7 +153,7 @@ pub fn ai_attack(monster_id: EntityId,
         if let Some(first_target) = targets.next() {
             let mut best_target = first_target;
 
-            let path = data.path_between(monster_pos, *best_target, movement);
+            let path = data.path_between(monster_pos, *best_target, movement, data.entities.can_swim(monster_id));
             let mut best_dist = path.len();
 
             let large_dist = (MAP_WIDTH + MAP_HEIGHT) as usize;
@@ -143,7 +162,7 @@ pub fn ai_attack(monster_id: EntityId,
             }
 
             for move_target in targets {
-                let path = data.path_between(monster_pos, *move_target, movement);
+                let path = data.path_between(monster_pos, *move_target, movement, data.entities.can_swim(monster_id));
                 let path_length = path.len();
                     
                 if path_length > 0 && (path_length < best_dist || best_dist == large_dist) {
@@ -255,13 +274,78 @@ fn ai_can_hit_target(data: &mut GameData,
     return hit_pos;
 }
 
+// a monster sharing its tile with a closed cage can't do anything until it is opened
+fn ai_is_caged(data: &GameData, monster_id: EntityId) -> bool {
+    let monster_pos = data.entities.pos[&monster_id];
+
+    return data.entities.trap.iter().any(|(key, trap)| {
+        *trap == Trap::Cage &&
+        data.entities.armed.get(key) == Some(&true) &&
+        data.entities.pos[key] == monster_pos
+    });
+}
+
+// monsters only throw stones, keeping their weapons in hand
+fn ai_throwable_item(data: &GameData, monster_id: EntityId) -> Option<EntityId> {
+    return data.entities.inventory.get(&monster_id).and_then(|inventory| {
+        inventory.iter()
+                 .find(|item_id| data.entities.item[*item_id] == Item::Stone)
+                 .map(|item_id| *item_id)
+    });
+}
+
+fn ai_can_throw_at_target(data: &mut GameData,
+                          monster_id: EntityId,
+                          target_pos: Pos,
+                          config: &Config) -> Option<EntityId> {
+    let monster_pos = data.entities.pos[&monster_id];
+
+    // nothing can be thrown while swimming
+    if data.map[monster_pos].tile_type == TileType::Water {
+        return None;
+    }
+
+    if let Some(item_id) = ai_throwable_item(data, monster_id) {
+        let range = data.entities.item[&item_id].throw_range(config);
+        let within_range = distance(monster_pos, target_pos) as usize <= range;
+
+        let within_fov =
+            data.entities.is_in_fov(monster_id, &mut data.map, target_pos, config);
+
+        let next_to_tile = move_next_to(monster_pos, target_pos);
+        let clear_path = data.clear_path(monster_pos, next_to_tile);
+
+        if within_range && within_fov && clear_path {
+            return Some(item_id);
+        }
+    }
+
+    return None;
+}
+
+// monsters with an inventory pick up stones they come across
+fn ai_item_to_pick_up(data: &GameData, monster_id: EntityId) -> Option<EntityId> {
+    if data.entities.inventory.get(&monster_id).is_none() {
+        return None;
+    }
+
+    let monster_pos = data.entities.pos[&monster_id];
+    for (item_id, item) in data.entities.item.iter() {
+        if *item == Item::Stone && data.entities.pos[item_id] == monster_pos {
+            return Some(*item_id);
+        }
+    }
+
+    return None;
+}
+
 fn ai_take_astar_step(monster_id: EntityId,
                       target_pos: Pos,
                       data: &GameData) -> Pos {
     let reach = data.entities.movement[&monster_id];
     let monster_pos = data.entities.pos[&monster_id];
 
-    let path = data.path_between(monster_pos, target_pos, reach);
+    let path = data.path_between(monster_pos, target_pos, reach, data.entities.can_swim(monster_id));
 
     if path.len() > 1 {
         return step_towards(monster_pos, path[1]);
@@ -293,6 +377,8 @@ pub fn basic_ai_take_turn(monster_id: EntityId,
                 } else if let Some(Message::Attack(entity_id)) = game_data.entities.was_attacked(monster_id) {
                     game_data.entities.face(monster_id, player_pos);
                     turn = Action::StateChange(Behavior::Attacking(entity_id));
+                } else if let Some(item_id) = ai_item_to_pick_up(game_data, monster_id) {
+                    turn = Action::Pickup(item_id);
                 }
 
                 return turn;
@@ -40,8 +40,8 @@ pub struct Config {
     pub grid_alpha: u8,
     pub grid_alpha_visible: u8,
     pub grid_alpha_overlay: u8,
-    pub map_load: MapLoadConfig,
-    pub map_file: String,
+    pub map_load: MapConfig,
+    pub map_next_level: MapConfig,
     pub idle_speed: f32,
     pub draw_mouse_line: bool,
     pub draw_star_path: bool,
@@ -51,6 +51,7 @@ pub struct Config {
     pub player_attack_speed: f32,
     pub player_vault_sprite_speed: f32,
     pub player_vault_move_speed: f32,
+    pub player_wall_kick_speed: f32,
     pub sound_timeout: f32,
     pub yell_radius: usize,
     pub fog_of_war: bool,
@@ -58,9 +59,63 @@ pub struct Config {
     pub explored_alpha: u8,
     pub sound_rubble_radius: usize,
     pub sound_grass_radius: usize,
+    pub sound_radius_shallow_water: usize,
     pub sound_radius_crushed: usize,
     pub sound_radius_attack: usize,
     pub sound_radius_trap: usize,
+    pub sound_radius_wall_kick: usize,
+    pub sound_radius_stone: usize,
+    pub sound_radius_thrown_item: usize,
+    pub throw_strength: usize,
+    pub throw_damage_dagger: i32,
+    pub stun_turns_stone: usize,
+    pub dagger_durability: i32,
+    pub sword_durability: i32,
+    pub hammer_durability: i32,
+    pub wear_hit_entity: i32,
+    pub wear_hammer_wall: i32,
+    pub hammer_terrain_damage: i32,
+    pub crush_terrain_damage: i32,
+    pub column_fall_length: i32,
+    pub push_collision_damage: i32,
+    pub safe_fall_height: u8,
+    pub fall_damage: i32,
+    pub sound_radius_fall: usize,
+    pub bsp_min_leaf_size: i32,
+    pub bsp_min_room_size: i32,
+    pub bsp_thin_wall_chance: f32,
+    pub cave_fill_chance: f32,
+    pub cave_smooth_iterations: usize,
+    pub cave_noise_scaler: f64,
+    pub cave_grass_threshold: f64,
+    pub cave_rubble_threshold: f64,
+    pub vault_directory: String,
+    pub vault_count: usize,
+    pub wfc_sample_maps: Vec<String>,
+    pub wfc_pattern_size: usize,
+    pub wfc_max_backtracks: usize,
+    pub threat_budget: i32,
+    pub threat_budget_per_depth: i32,
+    pub threat_cost_gol: i32,
+    pub threat_cost_elf: i32,
+    pub threat_cost_spire: i32,
+    pub monster_min_start_distance: i32,
+    pub key_guard_radius: i32,
+    pub key_guard_fraction: f32,
+    pub stone_density: f32,
+    pub stone_density_per_depth: f32,
+    pub item_density: f32,
+    pub item_density_per_depth: f32,
+    pub healing_draught_amount: i32,
+    pub smoke_bomb_radius: usize,
+    pub smoke_turns: usize,
+    pub noisemaker_radius: usize,
+    pub trap_timer_turns: usize,
+    pub fire_spread_chance: f32,
+    pub fire_turns: usize,
+    pub fire_damage: i32,
+    pub fire_light_radius: i32,
+    pub sound_radius_fire: usize,
     pub overlay_directions: bool,
     pub overlay_player_fov: bool,
     pub fov_radius_monster: i32,
@@ -70,6 +125,10 @@ pub struct Config {
     pub sound_radius_sneak: usize,
     pub sound_radius_walk: usize,
     pub sound_radius_run: usize,
+    pub sound_radius_encumbered: usize,
+    pub player_max_weight: usize,
+    pub player_max_items: usize,
+    pub player_encumbered_weight: usize,
 }
 
 impl Config {
@@ -79,8 +138,62 @@ impl Config {
         let mut config_string = String::new();
         file.read_to_string(&mut config_string)
             .expect(&format!("Could not read contents of {}", file_name));
-        let config = serde_yaml::from_str(&config_string).expect(&format!("Could not parse {} file!", file_name));
+        let config: Config = serde_yaml::from_str(&config_string).expect(&format!("Could not parse {} file!", file_name));
+
+        if let Err(err) = config.validate() {
+            panic!("Invalid config file {}: {}", file_name, err);
+        }
 
         return config
     }
+
+    /// Check the settings that parsing alone can't.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.wfc_pattern_size < 1 {
+            return Err("wfc_pattern_size must be at least 1".to_string());
+        }
+
+        self.map_load.validate()?;
+        return self.map_next_level.validate();
+    }
+
+    /// A copy of the config with the named fields replaced. Values are
+    /// given as YAML, the same as they would be written in the config file.
+    pub fn with_overrides(&self, overrides: &[(String, String)]) -> Result<Config, String> {
+        let mut value = serde_yaml::to_value(self).map_err(|err| err.to_string())?;
+
+        if let serde_yaml::Value::Mapping(mapping) = &mut value {
+            for (name, field_value) in overrides.iter() {
+                let key = serde_yaml::Value::String(name.clone());
+                if !mapping.contains_key(&key) {
+                    return Err(format!("Unknown config parameter {}", name));
+                }
+
+                let field_value: serde_yaml::Value =
+                    serde_yaml::from_str(field_value).map_err(|err| err.to_string())?;
+                mapping.insert(key, field_value);
+            }
+        }
+
+        let config: Config = serde_yaml::from_value(value).map_err(|err| err.to_string())?;
+        config.validate()?;
+
+        return Ok(config);
+    }
+}
+
+#[test]
+pub fn test_config_overrides() {
+    let overrides = vec!(("vault_count".to_string(), "3".to_string()),
+                         ("bsp_thin_wall_chance".to_string(), "0.25".to_string()));
+    let config = Config::from_file("../config.yaml").with_overrides(&overrides).unwrap();
+    assert_eq!(3, config.vault_count);
+    assert_eq!(0.25, config.bsp_thin_wall_chance);
+
+    let overrides = vec!(("not_a_parameter".to_string(), "1".to_string()));
+    assert!(config.with_overrides(&overrides).is_err());
+
+    // settings that would break map generation are rejected
+    let overrides = vec!(("wfc_pattern_size".to_string(), "0".to_string()));
+    assert!(config.with_overrides(&overrides).is_err());
 }
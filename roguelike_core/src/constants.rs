@@ -12,7 +12,6 @@ pub const INVENTORY_HEIGHT: usize = 500;
 
 /* Sound */
 pub const SOUND_RADIUS: usize = 3;
-pub const SOUND_RADIUS_STONE: usize = 3;
 pub const SOUND_RADIUS_MONSTER_MOVE: usize = 4;
 
 /* General Settings */
@@ -38,8 +37,13 @@ pub const MAX_MOMENTUM: i32 = 2;
 pub const SPIKE_DAMAGE: i32 = 20;
 pub const HAMMER_DAMAGE: i32 = 40;
 
+/* Elevation */
+pub const ELEVATION_ATTACK_BONUS: i32 = 1;
+pub const ELEVATION_FOV_BONUS: i32 = 2;
+
 /* Player */
 pub const PLAYER_THROW_DIST: usize = 5;
+pub const BELT_CAPACITY: usize = 3;
 
 /* UI */
 pub const BAR_WIDTH: u32 = 20;
@@ -53,6 +57,8 @@ pub const MSG_HEIGHT: u32 = PANEL_HEIGHT - 1;
 /* Map and View */
 pub const MAP_WIDTH: i32 = 30;
 pub const MAP_HEIGHT: i32 = 30;
+// the smallest generated map with room for an island
+pub const MAP_MIN_SIZE: i32 = 7;
 
 pub const FOV_LIGHT_WALLS: bool = true;
 
@@ -60,13 +66,21 @@ pub const ISLAND_RADIUS: i32 = 15;
 pub const ISLAND_NUM_OBSTACLES: i32 = 15;
 pub const ISLAND_NUM_SUBTRACTIONS_ATTEMPTS: i32 = 50;
 pub const ISLAND_NUM_ADDITION_ATTEMPTS: i32 = 50;
+pub const ISLAND_SHORE_WIDTH: i32 = 2;
+pub const ISLAND_ATTEMPTS: usize = 10;
+// obstacles can reach this far past the edge of the island
+pub const ISLAND_OBSTACLE_MARGIN: i32 = 2;
 
 pub const MAP_LAYER_GROUND: usize = 0;
 pub const MAP_LAYER_ENVIRONMENT: usize = 1;
 pub const MAP_LAYER_ENTITIES: usize = 2;
+pub const MAP_LAYER_LOOT: usize = 3;
+pub const MAP_LAYER_TRIGGERS: usize = 4;
+pub const MAP_LAYER_ELEVATION: usize = 5;
 
 pub const MAP_GROUND: u8 = 154;
 pub const MAP_WATER: u8 = 224;
+pub const MAP_SHALLOW_WATER: u8 = 247;
 pub const MAP_EMPTY: u8 = 32;
 pub const MAP_EMPTY_CHAR: u8 = 219; // all white square
 
@@ -133,6 +147,18 @@ pub const ENTITY_SHIELD: u8 = 255;
 pub const ENTITY_HAMMER: u8 = 251;
 pub const ENTITY_TRAP_SOUND: u8 = 83; // char S
 pub const ENTITY_SPIKE_TRAP: u8 = 136;
+pub const ENTITY_HEALING_DRAUGHT: u8 = 173;
+pub const ENTITY_SMOKE_BOMB: u8 = 7;
+pub const ENTITY_NOISEMAKER: u8 = 14;
+pub const ENTITY_SMOKE: u8 = 177;
+pub const ENTITY_PRESSURE_PLATE: u8 = 80; // char P
+pub const ENTITY_TRIPWIRE: u8 = 87; // char W
+pub const ENTITY_TIMER: u8 = 84; // char T
+pub const ENTITY_DOOR: u8 = 68; // char D
+pub const ENTITY_CAGE: u8 = 67; // char C
+pub const ENTITY_TORCH: u8 = 47; // char /
+pub const ENTITY_LANTERN: u8 = 111; // char o
+pub const ENTITY_FIRE: u8 = 94; // char ^
 
 pub const ARROW_RIGHT: u8 = 16;
 pub const ARROW_LEFT: u8 = 17;
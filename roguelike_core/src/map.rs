@@ -21,6 +21,7 @@ use serde_derive::*;
 use crate::types::*;
 use crate::utils::*;
 use crate::movement::Direction;
+use crate::constants::{ELEVATION_FOV_BONUS, MAP_WIDTH, MAP_HEIGHT};
 
 
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
@@ -64,6 +65,8 @@ pub struct Blocked {
     pub direction: Direction,
     pub blocked_tile: bool,
     pub wall_type: Wall,
+    /// The move climbs onto raised ground, which can be climbed but not walked onto.
+    pub ledge: bool,
 }
 
 impl Blocked {
@@ -77,24 +80,89 @@ impl Blocked {
         direction,
         blocked_tile,
         wall_type,
+        ledge: false,
         };
     }
+
+    /// The terrain that caused the block- either the blocked tile,
+    /// or the edge wall between the two positions. A ledge alone is not terrain.
+    pub fn terrain(&self) -> Option<(Pos, Edge)> {
+        if self.blocked_tile {
+            return Some((self.end_pos, Edge::Full));
+        }
+
+        if self.ledge && self.wall_type == Wall::Empty {
+            return None;
+        }
+
+        match self.direction {
+            Direction::Left => return Some((self.start_pos, Edge::Left)),
+            Direction::Right => return Some((self.end_pos, Edge::Left)),
+            Direction::Down => return Some((self.start_pos, Edge::Bottom)),
+            Direction::Up => return Some((self.end_pos, Edge::Bottom)),
+            _ => return None,
+        }
+    }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
-pub enum MapLoadConfig {
-    Random,
+/// Where a level's map comes from- one of the generators, a map file, or a test map.
+/// Map files are read as text maps if they end in .txt, and as REXPaint files otherwise.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MapSource {
+    Island,
+    Bsp,
+    Caves,
+    Wfc,
+    FromFile(String),
     TestMap,
     TestWall,
-    Empty,
     TestCorner,
     TestPlayer,
-    FromFile,
+    Empty,
 }
 
-impl Default for MapLoadConfig {
-    fn default() -> MapLoadConfig {
-        return MapLoadConfig::Random;
+impl MapSource {
+    pub fn is_generated(&self) -> bool {
+        match self {
+            MapSource::Island | MapSource::Bsp | MapSource::Caves | MapSource::Wfc => true,
+            _ => false,
+        }
+    }
+}
+
+/// A map source with the settings used to make the map. The size is only used
+/// by generators, and a seed given here replaces the game's seed while the map is made.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MapConfig {
+    pub source: MapSource,
+    pub width: i32,
+    pub height: i32,
+    pub seed: Option<u64>,
+}
+
+impl MapConfig {
+    pub fn new(source: MapSource) -> MapConfig {
+        return MapConfig { source, width: MAP_WIDTH, height: MAP_HEIGHT, seed: None };
+    }
+
+    pub fn from_file(file_name: &str) -> MapConfig {
+        return MapConfig::new(MapSource::FromFile(file_name.to_string()));
+    }
+
+    /// Generated maps need room for at least a small island.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.source.is_generated() && (self.width < MAP_MIN_SIZE || self.height < MAP_MIN_SIZE) {
+            return Err(format!("Generated maps must be at least {}x{}, not {}x{}",
+                               MAP_MIN_SIZE, MAP_MIN_SIZE, self.width, self.height));
+        }
+
+        return Ok(());
+    }
+}
+
+impl Default for MapConfig {
+    fn default() -> MapConfig {
+        return MapConfig::new(MapSource::Island);
     }
 }
 
@@ -103,6 +171,33 @@ pub enum Surface {
     Floor,
     Rubble,
     Grass,
+    Burnt,
+}
+
+/// What terrain is made of, which decides how much damage it takes to destroy.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Material {
+    Stone,
+    Wood,
+    Rubble,
+}
+
+impl Material {
+    pub fn hp(&self) -> u8 {
+        match self {
+            Material::Stone => 4,
+            Material::Wood => 2,
+            Material::Rubble => 1,
+        }
+    }
+}
+
+/// The part of a tile that is damaged: the tile itself, or one of its edge walls.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Edge {
+    Full,
+    Left,
+    Bottom,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
@@ -116,6 +211,11 @@ pub struct Tile {
     pub left_wall: Wall,
     pub chr: u8,
     pub surface: Surface,
+    pub material: Material,
+    pub hp: u8,
+    pub left_wall_hp: u8,
+    pub bottom_wall_hp: u8,
+    pub elevation: u8,
 }
 
 impl Tile {
@@ -128,9 +228,15 @@ impl Tile {
         left_wall: Wall::Empty,
         chr: ' ' as u8,
         surface: Surface::Floor,
+        material: Material::Stone,
+        hp: Material::Stone.hp(),
+        left_wall_hp: Material::Stone.hp(),
+        bottom_wall_hp: Material::Stone.hp(),
+        elevation: 0,
         }
     }
 
+    /// Deep water, which only swimmers can enter.
     pub fn water() -> Self {
         Tile { blocked: true,
         block_sight: false,
@@ -140,6 +246,28 @@ impl Tile {
         left_wall: Wall::Empty,
         chr: ' ' as u8,
         surface: Surface::Floor,
+        material: Material::Stone,
+        hp: Material::Stone.hp(),
+        left_wall_hp: Material::Stone.hp(),
+        bottom_wall_hp: Material::Stone.hp(),
+        elevation: 0,
+        }
+    }
+
+    pub fn shallow_water() -> Self {
+        Tile { blocked: false,
+        block_sight: false,
+        explored: false,
+        tile_type: TileType::ShallowWater,
+        bottom_wall: Wall::Empty,
+        left_wall: Wall::Empty,
+        chr: ' ' as u8,
+        surface: Surface::Floor,
+        material: Material::Stone,
+        hp: Material::Stone.hp(),
+        left_wall_hp: Material::Stone.hp(),
+        bottom_wall_hp: Material::Stone.hp(),
+        elevation: 0,
         }
     }
 
@@ -156,6 +284,11 @@ impl Tile {
         left_wall: Wall::Empty,
         chr: chr as u8,
         surface: Surface::Floor,
+        material: Material::Stone,
+        hp: Material::Stone.hp(),
+        left_wall_hp: Material::Stone.hp(),
+        bottom_wall_hp: Material::Stone.hp(),
+        elevation: 0,
         }
     }
 
@@ -172,9 +305,28 @@ impl Tile {
         left_wall: Wall::Empty,
         chr: chr as u8,
         surface: Surface::Floor,
+        material: Material::Stone,
+        hp: Material::Stone.hp(),
+        left_wall_hp: Material::Stone.hp(),
+        bottom_wall_hp: Material::Stone.hp(),
+        elevation: 0,
         }
     }
 
+    /// Change what a tile and its edge walls are made of, restoring their hit points.
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self.hp = material.hp();
+        self.left_wall_hp = material.hp();
+        self.bottom_wall_hp = material.hp();
+        return self;
+    }
+
+    pub fn with_elevation(mut self, elevation: u8) -> Self {
+        self.elevation = elevation;
+        return self;
+    }
+
     pub fn exit() -> Self {
         Tile { blocked: false,
         block_sight: false,
@@ -184,6 +336,11 @@ impl Tile {
         left_wall: Wall::Empty,
         chr: ' ' as u8,
         surface: Surface::Floor,
+        material: Material::Stone,
+        hp: Material::Stone.hp(),
+        left_wall_hp: Material::Stone.hp(),
+        bottom_wall_hp: Material::Stone.hp(),
+        elevation: 0,
         }
     }
 }
@@ -195,9 +352,16 @@ pub enum TileType {
     ShortWall,
     Wall,
     Water,
+    ShallowWater,
     Exit,
 }
 
+impl TileType {
+    pub fn is_water(&self) -> bool {
+        return *self == TileType::Water || *self == TileType::ShallowWater;
+    }
+}
+
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Obstacle {
@@ -236,6 +400,8 @@ impl Wall {
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Map {
     pub tiles: Vec<Vec<Tile>>,
+    /// Positions filled with smoke, which blocks sight without blocking movement.
+    pub smoke: HashSet<Pos>,
     fov: MapData,
     fov_pos: Pos,
     fov_radius: i32,
@@ -248,6 +414,7 @@ impl Map {
         let mut map =
             Map {
                 tiles,
+                smoke: HashSet::new(),
                 fov: MapData::new(width, height),
                 fov_pos: Pos::new(0, 0),
                 fov_radius: 1,
@@ -263,6 +430,7 @@ impl Map {
         let mut map =
             Map {
                 tiles,
+                smoke: HashSet::new(),
                 fov: MapData::new(width, height),
                 fov_pos: Pos::new(0, 0),
                 fov_radius: 1,
@@ -277,6 +445,7 @@ impl Map {
         let map =
             Map {
                 tiles: Vec::new(),
+                smoke: HashSet::new(),
                 fov: MapData::new(1, 1),
                 fov_pos: Pos::new(0, 0),
                 fov_radius: 1,
@@ -298,7 +467,7 @@ impl Map {
 
     pub fn blocked_right(&self, pos: Pos) -> bool {
         let offset = Pos::new(pos.x + 1, pos.y);
-        if !self.is_within_bounds(pos) || !self.is_within_bounds(pos) { 
+        if !self.is_within_bounds(offset) || !self.is_within_bounds(pos) { 
             return true;
         }
 
@@ -309,7 +478,7 @@ impl Map {
 
     pub fn blocked_down(&self, pos: Pos) -> bool {
         let offset = Pos::new(pos.x, pos.y + 1);
-        if !self.is_within_bounds(pos) || !self.is_within_bounds(pos) {
+        if !self.is_within_bounds(offset) || !self.is_within_bounds(pos) {
             return true;
         }
 
@@ -320,7 +489,7 @@ impl Map {
 
     pub fn blocked_up(&self, pos: Pos) -> bool {
         let offset = Pos::new(pos.x, pos.y - 1);
-        if !self.is_within_bounds(pos) || !self.is_within_bounds(pos) {
+        if !self.is_within_bounds(offset) || !self.is_within_bounds(pos) {
             return true;
         }
 
@@ -364,6 +533,12 @@ impl Map {
                 found_blocker = true;
             }
 
+            // ledges can be dropped off, but raised ground can only be climbed
+            if self[target_pos].elevation > self[pos].elevation {
+                blocked.ledge = true;
+                found_blocker = true;
+            }
+
             let move_dir = target_pos - Vector2D::new(x, y);
 
             // used for diagonal movement checks
@@ -603,7 +778,7 @@ impl Map {
             return false;
         }
 
-        let within_radius = distance(start_pos, end_pos) < radius;
+        let within_radius = distance(start_pos, end_pos) < self.elevated_radius(start_pos, radius);
         if !within_radius {
             return false;
         }
@@ -621,7 +796,7 @@ impl Map {
         if let Some(blocked) = blocked {
             let at_end = blocked.end_pos == end_pos;
             let no_wall = blocked.wall_type.no_wall();
-            let visible_wall = blocked.blocked_tile && no_wall;
+            let visible_wall = (blocked.blocked_tile || blocked.ledge) && no_wall;
             wall_in_path = !(at_end && visible_wall);
         }
 
@@ -691,16 +866,115 @@ impl Map {
         FovRestrictive::new().compute_fov(&mut self.fov,
                                           pos.x as usize,
                                           pos.y as usize,
-                                          view_radius as usize,
+                                          self.elevated_radius(pos, view_radius) as usize,
                                           true);
     }
 
+    /// High ground extends how far can be seen from it.
+    pub fn elevated_radius(&self, pos: Pos, radius: i32) -> i32 {
+        if !self.is_within_bounds(pos) {
+            return radius;
+        }
+
+        return radius + self[pos].elevation as i32 * ELEVATION_FOV_BONUS;
+    }
+
+    /// How far a move from one position to another drops, or 0 if it does not drop.
+    pub fn drop_height(&self, start_pos: Pos, end_pos: Pos) -> u8 {
+        let start = self[start_pos].elevation;
+        let end = self[end_pos].elevation;
+        if start > end {
+            return start - end;
+        } else {
+            return 0;
+        }
+    }
+
+    /// Whether the end position is one level above the start, so it can be climbed onto.
+    pub fn can_climb(&self, start_pos: Pos, end_pos: Pos) -> bool {
+        return self.is_within_bounds(start_pos) &&
+               self.is_within_bounds(end_pos) &&
+               distance(start_pos, end_pos) == 1 &&
+               !self[end_pos].blocked &&
+               self[end_pos].elevation as i32 == self[start_pos].elevation as i32 + 1 &&
+               self.is_blocked_by_wall(start_pos, end_pos.x - start_pos.x, end_pos.y - start_pos.y)
+                   .map_or(true, |blocked| blocked.wall_type == Wall::Empty);
+    }
+
+    /// Damage a tile, or one of its edge walls, destroying it if it runs out of hit points.
+    /// Destroyed walls are reduced to rubble, and FOV is updated to see through them.
+    /// Returns whether the terrain was destroyed.
+    pub fn damage_terrain(&mut self, pos: Pos, edge: Edge, amount: Hp) -> bool {
+        if !self.is_within_bounds(pos) {
+            return false;
+        }
+
+        let destroyed;
+        match edge {
+            Edge::Full => {
+                let tile_type = self[pos].tile_type;
+                if tile_type != TileType::Wall && tile_type != TileType::ShortWall {
+                    return false;
+                }
+
+                self[pos].hp = reduce_hp(self[pos].hp, amount);
+                destroyed = self[pos].hp == 0;
+
+                if destroyed {
+                    self[pos].blocked = false;
+                    self[pos].block_sight = false;
+                    self[pos].tile_type = TileType::Empty;
+                    self[pos].chr = ' ' as u8;
+                    self[pos].material = Material::Rubble;
+                    self[pos].hp = Material::Rubble.hp();
+
+                    if self[pos].surface == Surface::Floor {
+                        self[pos].surface = Surface::Rubble;
+                    }
+                }
+            }
+
+            Edge::Left => {
+                if self[pos].left_wall == Wall::Empty {
+                    return false;
+                }
+
+                self[pos].left_wall_hp = reduce_hp(self[pos].left_wall_hp, amount);
+                destroyed = self[pos].left_wall_hp == 0;
+
+                if destroyed {
+                    self[pos].left_wall = Wall::Empty;
+                }
+            }
+
+            Edge::Bottom => {
+                if self[pos].bottom_wall == Wall::Empty {
+                    return false;
+                }
+
+                self[pos].bottom_wall_hp = reduce_hp(self[pos].bottom_wall_hp, amount);
+                destroyed = self[pos].bottom_wall_hp == 0;
+
+                if destroyed {
+                    self[pos].bottom_wall = Wall::Empty;
+                }
+            }
+        }
+
+        if destroyed {
+            self.update_map();
+        }
+
+        return destroyed;
+    }
+
     pub fn update_map(&mut self) {
         let dims = (self.width(), self.height());
 
         for y in 0..dims.1 {
             for x in 0..dims.0 {
-                let transparent = !self.tiles[x as usize][y as usize].block_sight;
+                let transparent = !self.tiles[x as usize][y as usize].block_sight &&
+                                  !self.smoke.contains(&Pos::new(x, y));
                 self.fov.set_transparent(x as usize, y as usize, transparent);
             }
         }
@@ -794,6 +1068,28 @@ impl IndexMut<Pos> for Map {
 }
 
 
+// terrain hit points are stored as bytes to keep tiles packed
+fn reduce_hp(hp: u8, amount: Hp) -> u8 {
+    return std::cmp::max(0, hp as Hp - amount) as u8;
+}
+
+/// Where debris lands when terrain is destroyed from the given position- the
+/// tile beyond a destroyed wall, or the far side of a destroyed edge wall.
+pub fn debris_pos(source_pos: Pos, pos: Pos, edge: Edge) -> Pos {
+    let other_side;
+    match edge {
+        Edge::Full => return next_from_to(source_pos, pos),
+        Edge::Left => other_side = move_x(pos, -1),
+        Edge::Bottom => other_side = move_y(pos, 1),
+    }
+
+    if distance(source_pos, other_side) > distance(source_pos, pos) {
+        return other_side;
+    } else {
+        return pos;
+    }
+}
+
 pub fn near_tile_type(map: &Map, position: Pos, tile_type: TileType) -> bool {
     let neighbor_offsets: Vec<(i32, i32)>
         = vec!((1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1));
@@ -1113,6 +1409,51 @@ fn test_blocked_by_wall() {
     assert!(map.is_blocked_by_wall(Pos::new(5, 4), 0, 1).is_some());
 }
 
+#[test]
+fn test_damage_terrain() {
+    let radius = 10;
+    let mut map = Map::from_dims(10, 10);
+
+    let wall_pos = Pos::new(5, 5);
+    map[wall_pos] = Tile::wall().with_material(Material::Wood);
+    map[(3, 3)].left_wall = Wall::ShortWall;
+    map.update_map();
+
+    assert!(!map.is_in_fov(Pos::new(4, 5), Pos::new(6, 5), radius));
+
+    // a wooden wall takes two hits, and is left as rubble that can be seen through
+    assert!(!map.damage_terrain(wall_pos, Edge::Full, 1));
+    assert!(map[wall_pos].blocked);
+    assert!(map.damage_terrain(wall_pos, Edge::Full, 1));
+    assert!(!map[wall_pos].blocked);
+    assert_eq!(Surface::Rubble, map[wall_pos].surface);
+    assert!(map.is_in_fov(Pos::new(4, 5), Pos::new(6, 5), radius));
+
+    // floor and water can't be damaged
+    assert!(!map.damage_terrain(wall_pos, Edge::Full, 10));
+    map[(1, 1)] = Tile::water();
+    assert!(!map.damage_terrain(Pos::new(1, 1), Edge::Full, 10));
+
+    // edge walls are damaged separately from their tile
+    let blocked = map.is_blocked_by_wall(Pos::new(2, 3), 1, 0).unwrap();
+    assert_eq!(Some((Pos::new(3, 3), Edge::Left)), blocked.terrain());
+    assert!(map.damage_terrain(Pos::new(3, 3), Edge::Left, Material::Stone.hp() as Hp));
+    assert_eq!(Wall::Empty, map[(3, 3)].left_wall);
+    assert!(map.is_blocked_by_wall(Pos::new(2, 3), 1, 0).is_none());
+
+    assert_eq!(Pos::new(2, 3), debris_pos(Pos::new(3, 3), Pos::new(3, 3), Edge::Left));
+    assert_eq!(Pos::new(3, 3), debris_pos(Pos::new(2, 3), Pos::new(3, 3), Edge::Left));
+    assert_eq!(Pos::new(6, 5), debris_pos(Pos::new(4, 5), wall_pos, Edge::Full));
+
+    // raised ground blocks a move as a ledge, with no terrain to damage
+    map[(8, 8)].elevation = 1;
+    let blocked = map.is_blocked_by_wall(Pos::new(7, 8), 1, 0).unwrap();
+    assert!(blocked.ledge);
+    assert!(!blocked.blocked_tile);
+    assert_eq!(None, blocked.terrain());
+    assert!(map.is_blocked_by_wall(Pos::new(8, 8), -1, 0).is_none());
+}
+
 #[test]
 fn test_floodfill() {
     let mut map = Map::from_dims(10, 10);
@@ -46,16 +46,30 @@ pub enum Msg {
     Pass(),
     Crushed(EntityId, Pos), // object that did the crushing, position
     Sound(EntityId, Pos, usize, bool), // object causing sound, location, radius, whether animation will play
+    Triggered(EntityId, EntityId), // trigger, entity that set it off
     SoundTrapTriggered(EntityId, EntityId), // trap, entity
+    DoorClosed(EntityId), // door
+    CageOpened(EntityId, Pos), // cage, position the released monster investigates
     SpikeTrapTriggered(EntityId, EntityId), // trap, entity
+    Ignite(EntityId, Pos), // entity starting the fire, position set alight
+    Burned(EntityId, EntityId, Hp), // fire, entity, hp lost
     PlayerDeath,
     PickedUp(EntityId, EntityId), // entity, item id
+    StackChanged(EntityId, usize), // item id, number of items in the stack
+    ItemDropped(EntityId, EntityId), // entity, item id
     ItemThrow(EntityId, EntityId, Pos, Pos), // thrower, stone id, start, end
+    ItemLanded(EntityId, Pos, Pos, usize), // item id, start, landing position, sound radius
+    Stunned(EntityId, usize), // entity, turns stunned
     Attack(EntityId, EntityId, Hp), // attacker, attacked, hp lost
     Killed(EntityId, EntityId, Hp), // attacker, attacked, hp lost
     Pushed(EntityId, EntityId, Pos), // attacker, attacked, change in position
+    Toppled(EntityId, EntityId, Pos), // cause, column, direction of the fall
+    Slammed(EntityId, EntityId), // pushed entity, entity it hit
+    Drowned(EntityId, EntityId, Pos), // pusher, drowned entity, position
     Moved(EntityId, Movement, Pos),
     JumpWall(EntityId, Pos, Pos), // current pos, new pos
+    Climbed(EntityId, Pos), // entity, position climbed onto
+    Fell(EntityId, Pos, u8), // entity, landing position, height fallen
     WallKick(EntityId, Pos),
     StateChange(EntityId, Behavior),
     Collided(EntityId, Pos),
@@ -63,11 +77,19 @@ pub enum Msg {
     GameState(GameState),
     MoveMode(MoveMode),
     TriedRunWithShield,
+    TriedRunEncumbered,
+    TriedThrowSwimming(EntityId), // entity
+    InventoryFull(EntityId, EntityId), // entity, item id
+    CantEquip(EntityId, EntityId), // entity, item id
     SpawnedObject(EntityId, EntityType, Pos, EntityName),
     SwordSwing(EntityId, Pos), // entity, position swung at
     HammerSwing(EntityId, Pos), // entity, position swung at
     HammerHitEntity(EntityId, EntityId), // entity, hit entity
     HammerHitWall(EntityId, Blocked),
+    DamageTerrain(EntityId, Pos, Edge, Hp), // cause, position, part of the tile, damage
+    TerrainDestroyed(EntityId, Pos, Edge), // cause, position, part of the tile
+    ItemBroke(EntityId, EntityId), // entity, item id
+    Healed(EntityId, Hp), // entity, hp gained
     ChangeLevel(),
     Action(EntityId, Action),
 }
@@ -87,6 +109,10 @@ impl Msg {
                 return "Sound trap triggered".to_string();
             }
 
+            Msg::Burned(_fire, entity, _damage) => {
+                return format!("{:?} was burned", data.entities.name[entity]);
+            }
+
             Msg::SpikeTrapTriggered(_trap, _entity) => {
                 return "Spike trap triggered".to_string();
             }
@@ -101,10 +127,28 @@ impl Msg {
                                data.entities.name[item].clone());
             }
 
+            Msg::DoorClosed(_door) => {
+                return "A door slammed shut".to_string();
+            }
+
+            Msg::CageOpened(_cage, _pos) => {
+                return "A cage opened".to_string();
+            }
+
+            Msg::ItemDropped(entity, item) => {
+                return format!("{:?} dropped a {:?}",
+                               data.entities.name[entity].clone(),
+                               data.entities.name[item].clone());
+            }
+
             Msg::ItemThrow(_thrower, _item, _start, _end) => {
                 return "Item throw".to_string();
             }
 
+            Msg::Stunned(entity, _turns) => {
+                return format!("{:?} was stunned", data.entities.name[entity]);
+            }
+
             Msg::Attack(attacker, attacked, damage) => {
                 return format!("{:?} attacked {:?} for {} damage",
                                data.entities.name[attacker],
@@ -120,6 +164,18 @@ impl Msg {
                 return format!("{:?} pushed {:?}", data.entities.name[attacker], data.entities.name[attacked]);
             }
 
+            Msg::Toppled(_cause, _column, _direction) => {
+                return "A column toppled".to_string();
+            }
+
+            Msg::Slammed(pushed, hit) => {
+                return format!("{:?} slammed into {:?}", data.entities.name[pushed], data.entities.name[hit]);
+            }
+
+            Msg::Drowned(_pusher, drowned, _pos) => {
+                return format!("{:?} drowned", data.entities.name[drowned]);
+            }
+
             Msg::Moved(entity, movement, pos) => {
                 if let MoveType::Pass = movement.typ {
                     return format!("{:?} passed their turn", data.entities.name[entity]);
@@ -136,6 +192,14 @@ impl Msg {
                 return "Did a wallkick".to_string();
             }
 
+            Msg::Climbed(entity, _pos) => {
+                return format!("{:?} climbed up", data.entities.name[entity]);
+            }
+
+            Msg::Fell(entity, _pos, _height) => {
+                return format!("{:?} dropped down", data.entities.name[entity]);
+            }
+
             Msg::StateChange(_entity, behavior) => {
                 return format!("Changed state to {:?}", *behavior);
             }
@@ -188,6 +252,26 @@ impl Msg {
                 return "Can't run with shield!".to_string();
             }
 
+            Msg::TriedRunEncumbered => {
+                return "Carrying too much to run!".to_string();
+            }
+
+            Msg::TriedThrowSwimming(_entity) => {
+                return "Can't throw while swimming!".to_string();
+            }
+
+            Msg::InventoryFull(entity, item) => {
+                return format!("{:?} can't carry the {:?}",
+                               data.entities.name[entity],
+                               data.entities.name[item]);
+            }
+
+            Msg::CantEquip(entity, item) => {
+                return format!("{:?} can't equip the {:?}",
+                               data.entities.name[entity],
+                               data.entities.name[item]);
+            }
+
             Msg::SwordSwing(entity, _pos) => {
                 return format!("{:?} swung their sword", data.entities.name[entity]);
             }
@@ -206,6 +290,20 @@ impl Msg {
                 return format!("{:?} hit a wall with their hammer", data.entities.name[entity]);
             }
 
+            Msg::TerrainDestroyed(_entity, _pos, _edge) => {
+                return "A wall collapsed".to_string();
+            }
+
+            Msg::Healed(entity, amount) => {
+                return format!("{:?} healed {} hp", data.entities.name[entity], amount);
+            }
+
+            Msg::ItemBroke(entity, item_id) => {
+                let entity_name = &data.entities.name[entity];
+                let item_name = &data.entities.name[item_id];
+                return format!("{:?}'s {:?} broke", entity_name, item_name);
+            }
+
             _ => {
                 return "".to_string();
             }
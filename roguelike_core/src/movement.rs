@@ -20,9 +20,10 @@ pub enum Action {
     ThrowItem(Pos, EntityId), // end position, item id
     Pass,
     Yell,
-    UseItem(Pos), // item used towards position, or just player pos
+    UseItem(EntityId, Pos), // item used, towards position or just the user's pos
     ArmDisarmTrap(EntityId),
     PlaceTrap(Pos, EntityId), // position to place, trap id
+    Climb(Pos), // raised position to climb onto
     // TODO consider just using Option<Action> instead
     NoAction,
 }
@@ -562,6 +563,94 @@ impl Momentum {
         self.mx = 0;
         self.my = 0;
     }
+
+    // a running move builds momentum, but turning by 90 degrees or more
+    // loses all momentum built up so far.
+    pub fn ran(&mut self, dx: i32, dy: i32) {
+        if self.magnitude() != 0 && !self.along(dx, dy) {
+            self.clear();
+        }
+
+        self.moved(dx, dy);
+    }
+}
+
+#[test]
+pub fn test_momentum_ran() {
+    let mut momentum = Momentum::default();
+
+    momentum.ran(1, 0);
+    assert_eq!(1, momentum.mx);
+    assert!(!momentum.at_maximum());
+
+    momentum.ran(2, 0);
+    assert!(momentum.at_maximum());
+
+    // diagonal moves keep momentum along the x axis
+    momentum.ran(1, 1);
+    assert_eq!(2, momentum.mx);
+    assert_eq!(1, momentum.my);
+
+    // a hard turn loses momentum
+    momentum.ran(-1, 0);
+    assert_eq!(-1, momentum.mx);
+    assert_eq!(0, momentum.my);
+}
+
+/// Momentum an entity would have after a running move of the given delta,
+/// or None if the entity does not track momentum.
+pub fn momentum_after_run(entity_id: EntityId, delta_pos: Pos, data: &GameData) -> Option<Momentum> {
+    if let Some(momentum) = data.entities.momentum.get(&entity_id) {
+        let mut momentum = *momentum;
+        momentum.ran(delta_pos.x, delta_pos.y);
+        return Some(momentum);
+    }
+
+    return None;
+}
+
+pub fn running(entity_id: EntityId, data: &GameData) -> bool {
+    return data.entities.move_mode.get(&entity_id) == Some(&MoveMode::Run);
+}
+
+/// Running with momentum along the direction of movement carries an entity one tile further.
+pub fn momentum_reach(entity_id: EntityId, action: Direction, reach: Reach, data: &GameData) -> Reach {
+    if running(entity_id, data) {
+        if let Some(momentum) = data.entities.momentum.get(&entity_id) {
+            let (dx, dy) = action.into_move();
+            if momentum.at_maximum() && momentum.along(dx, dy) {
+                return reach.with_dist(reach.dist() + 1);
+            }
+        }
+    }
+
+    return reach;
+}
+
+/// Short walls can only be jumped when running at full speed. Entities that do not
+/// track momentum only need to be running.
+pub fn can_jump_wall(entity_id: EntityId, delta_pos: Pos, blocked: &Blocked, data: &GameData) -> bool {
+    if !running(entity_id, data) || blocked.blocked_tile || blocked.ledge || blocked.wall_type != Wall::ShortWall {
+        return false;
+    }
+
+    match momentum_after_run(entity_id, delta_pos, data) {
+        Some(momentum) => momentum.at_maximum(),
+        None => true,
+    }
+}
+
+/// Delta to push an entity that was moved into. Running entities knock their
+/// target back further the more momentum they have.
+pub fn knockback(entity_id: EntityId, delta_pos: Pos, data: &GameData) -> Pos {
+    if running(entity_id, data) {
+        if let Some(momentum) = momentum_after_run(entity_id, delta_pos, data) {
+            let dist = momentum.magnitude();
+            return Pos::new(delta_pos.x.signum() * dist, delta_pos.y.signum() * dist);
+        }
+    }
+
+    return delta_pos;
 }
 
 
@@ -653,18 +742,76 @@ pub fn entity_move_not_blocked(entity_id: EntityId, move_pos: Pos, delta_pos: Po
     return movement;
 }
 
-pub fn entity_move_blocked_by_wall(entity_id: EntityId, delta_pos: Pos, blocked: &Blocked, data: &GameData) -> Option<Movement> {
+/// Running diagonally into a wall rebounds the entity off of it, reflecting the
+/// movement along the blocked axis. Corners and walls further along the move
+/// do not result in a wall kick.
+pub fn wall_kick(entity_id: EntityId, delta_pos: Pos, blocked: &Blocked, data: &GameData) -> Option<Movement> {
+    let pos = data.entities.pos[&entity_id];
+    let (dx, dy) = (delta_pos.x.signum(), delta_pos.y.signum());
+
+    if !running(entity_id, data) || dx == 0 || dy == 0 || blocked.start_pos != pos {
+        return None;
+    }
+
+    let blocked_x = if dx > 0 { data.map.blocked_right(pos) } else { data.map.blocked_left(pos) };
+    let blocked_y = if dy > 0 { data.map.blocked_down(pos) } else { data.map.blocked_up(pos) };
+
+    let kick_dir;
+    if blocked_x && !blocked_y {
+        kick_dir = Pos::new(-dx, dy);
+    } else if blocked_y && !blocked_x {
+        kick_dir = Pos::new(dx, -dy);
+    } else {
+        return None;
+    }
+
+    let dist = pos_mag(delta_pos);
+    let kick_delta = Pos::new(kick_dir.x * dist, kick_dir.y * dist);
+    let typ = MoveType::WallKick(kick_dir.x, kick_dir.y);
+
+    let move_result = check_collision(pos, kick_delta.x, kick_delta.y, data);
+
     let mut movement: Option<Movement>;
+    match (move_result.blocked, move_result.entity) {
+        (_, Some(other_id)) if move_result.blocked.map_or(true, |blocked| {
+                                  distance(pos, data.entities.pos[&other_id]) <= distance(pos, blocked.start_pos)
+                              }) => {
+            // an entity at the landing tile is kicked back or stabbed
+            let other_pos = data.entities.pos[&other_id];
+            if can_stab(data, entity_id, other_id) {
+                let attack = Attack::Stab(other_id);
+                movement = Some(Movement::attack(move_next_to(pos, other_pos), typ, attack));
+            } else {
+                let attack = Attack::Push(other_id, kick_dir);
+                movement = Some(Movement::attack(other_pos, typ, attack));
+            }
+        }
 
-    let pos = data.entities.pos[&entity_id];
-    let mut jumped_wall = false;
+        (Some(blocked), _) => {
+            movement = Some(Movement::move_to(blocked.start_pos, typ));
+        }
 
-    if data.entities.move_mode[&entity_id] == MoveMode::Run {
-        if !blocked.blocked_tile && blocked.wall_type == Wall::ShortWall {
-            jumped_wall = true;
-        } 
+        (None, _) => {
+            movement = Some(Movement::move_to(move_result.move_pos, typ));
+        }
+    }
+
+    // rebounding into another wall leaves the entity in place
+    if let Some(moved) = movement {
+        if moved.attack.is_none() && moved.pos == pos {
+            movement = None;
+        }
     }
 
+    return movement;
+}
+
+pub fn entity_move_blocked_by_wall(entity_id: EntityId, delta_pos: Pos, blocked: &Blocked, data: &GameData) -> Option<Movement> {
+    let mut movement: Option<Movement>;
+
+    let pos = data.entities.pos[&entity_id];
+    let jumped_wall = can_jump_wall(entity_id, delta_pos, blocked, data);
+
     if jumped_wall {
         // TODO remove when confirmed that this is not desired
         // if we jump the wall, we have to recheck for collisions for the
@@ -684,6 +831,8 @@ pub fn entity_move_blocked_by_wall(entity_id: EntityId, delta_pos: Pos, blocked:
                movement = Some(Movement::attack(new_pos, MoveType::JumpWall, attack));
            }
         }
+    } else if let Some(kick) = wall_kick(entity_id, delta_pos, blocked, data) {
+        movement = Some(kick);
     } else {
         // else move up to the wall (start_pos is just before the colliding tile)
         movement = Some(Movement::move_to(blocked.start_pos, MoveType::Move));
@@ -695,13 +844,14 @@ pub fn entity_move_blocked_by_wall(entity_id: EntityId, delta_pos: Pos, blocked:
 pub fn entity_move_blocked_by_entity(entity_id: EntityId, other_id: EntityId, move_pos: Pos, delta_pos: Pos, data: &GameData) -> Option<Movement> {
     let movement: Option<Movement>;
 
-    let pos = data.entities.pos[&entity_id];
     if can_stab(data, entity_id, other_id) {
         let attack = Attack::Stab(other_id);
         movement = Some(Movement::attack(move_pos, MoveType::Move, attack));
     } else if data.entities.blocks[&other_id] {
-        let attack = Attack::Push(other_id, delta_pos);
-        movement = Some(Movement::attack(add_pos(pos, delta_pos), MoveType::Move, attack));
+        // push the entity back and step into the space it leaves behind
+        let other_pos = data.entities.pos[&other_id];
+        let attack = Attack::Push(other_id, knockback(entity_id, delta_pos, data));
+        movement = Some(Movement::attack(other_pos, MoveType::Move, attack));
     } else {
         movement = Some(Movement::move_to(move_pos, MoveType::Move));
     }
@@ -723,25 +873,20 @@ pub fn entity_move_blocked_by_entity_and_wall(entity_id: EntityId, other_id: Ent
             if can_stab(data, entity_id, other_id) {
                 Attack::Stab(other_id)
             } else {
-                Attack::Push(other_id, delta_pos)
+                Attack::Push(other_id, knockback(entity_id, delta_pos, data))
             };
         let move_pos = move_next_to(pos, entity_pos);
         movement = Some(Movement::attack(move_pos, MoveType::Move, attack));
     } else if entity_dist > wall_dist {
         // wall is first
-        let mut jumped_wall = false;
-        if data.entities.move_mode[&entity_id] == MoveMode::Run {
-            if !blocked.blocked_tile && blocked.wall_type == Wall::ShortWall {
-                jumped_wall = true;
-            } 
-        }
+        let jumped_wall = can_jump_wall(entity_id, delta_pos, blocked, data);
 
         if jumped_wall {
             let attack =
                 if can_stab(data, entity_id, other_id) {
                     Attack::Stab(other_id)
                 } else {
-                    Attack::Push(other_id, delta_pos)
+                    Attack::Push(other_id, knockback(entity_id, delta_pos, data))
                 };
             let move_pos = move_next_to(pos, entity_pos);
             movement = Some(Movement::attack(move_pos, MoveType::Move, attack));
@@ -766,12 +911,34 @@ pub fn calculate_move(action: Direction,
 
     let pos = data.entities.pos[&entity_id];
 
+    let mut reach = momentum_reach(entity_id, action, reach, data);
+
+    // wading or swimming is slow, only moving a single tile at a time
+    if data.map[pos].tile_type.is_water() {
+        reach = reach.with_dist(1);
+    }
+
     // get the location we would move to given the input action
-    if let Some(delta_pos) = reach.move_with_reach(&action) {
+    if let Some(mut delta_pos) = reach.move_with_reach(&action) {
         let (dx, dy) = delta_pos.to_tuple();
 
         // check if movement collides with a blocked location or an entity
-        let move_result = check_collision(pos, dx, dy, data);
+        let mut move_result = check_collision(pos, dx, dy, data);
+
+        // swimmers enter deep water a single tile at a time instead of being blocked by it
+        if let Some(blocked) = move_result.blocked {
+            if blocked.start_pos == pos &&
+               blocked.wall_type == Wall::Empty &&
+               data.can_swim_to(entity_id, blocked.end_pos) {
+                delta_pos = sub_pos(blocked.end_pos, pos);
+                move_result = MoveResult::with_pos(blocked.end_pos);
+
+                if let Some(other_id) = data.has_blocking_entity(blocked.end_pos) {
+                    move_result.move_pos = pos;
+                    move_result.entity = Some(other_id);
+                }
+            }
+        }
 
         match (move_result.blocked, move_result.entity) {
             // both blocked by wall and by entity
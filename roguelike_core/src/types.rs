@@ -1,6 +1,7 @@
 use std::collections::VecDeque;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::default::Default;
+use std::fmt;
 
 use serde::{Serialize, Deserialize};
 
@@ -18,6 +19,7 @@ use crate::movement::*;
 use crate::animation::AnimKey;
 use crate::utils::*;
 use crate::config::Config;
+use crate::constants::*;
 
 // TODO consider renaming this file- it really just contains
 // GameData and Entities. likely split into separate files.
@@ -42,7 +44,7 @@ impl GameData {
         }
     }
 
-    pub fn path_between(&self, start: Pos, end: Pos, reach: Reach) -> Vec<Pos> {
+    pub fn path_between(&self, start: Pos, end: Pos, reach: Reach, swims: bool) -> Vec<Pos> {
         let result;
 
         let maybe_results =
@@ -56,12 +58,25 @@ impl GameData {
                               let next_pos = add_pos(pos, offset);
                               let (dx, dy) = (next_pos.x - pos.x, next_pos.y - pos.y);
 
+                              // paths go around fire, unless the fire is where the path ends
+                              if next_pos != end && self.entities.on_fire(next_pos) {
+                                  continue;
+                              }
+
                               // use the next position if there is a clear path to it. It is also
                               // useable if the path is only blocked by an entity and not a wall,
                               // and it is the last step (in case we are pathing towards a target).
                               // This will cause stopped paths if we are a monster blocked by other
                               // monsters, but that it okay.
-                              if self.clear_path(pos, next_pos) || 
+                              // Swimmers can also take single steps into deep water.
+                              let swim_step =
+                                  swims && dx.abs() <= 1 && dy.abs() <= 1 &&
+                                  self.map.is_within_bounds(next_pos) &&
+                                  self.map[next_pos].tile_type == TileType::Water &&
+                                  self.has_blocking_entity(next_pos).is_none() &&
+                                  self.map.is_blocked_by_wall(pos, dx, dy).map_or(true, |blocked| blocked.wall_type == Wall::Empty);
+
+                              if self.clear_path(pos, next_pos) || swim_step ||
                                  (next_pos == end && self.map.is_blocked_by_wall(pos, dx, dy).is_none()) {
                                   next_positions.push((next_pos, 1));
                               }
@@ -82,6 +97,13 @@ impl GameData {
         return result;
     }
 
+    /// Deep water blocks movement, except for entities that can swim.
+    pub fn can_swim_to(&self, entity_id: EntityId, pos: Pos) -> bool {
+        return self.entities.can_swim(entity_id) &&
+               self.map.is_within_bounds(pos) &&
+               self.map[pos].tile_type == TileType::Water;
+    }
+
     pub fn find_player(&self) -> Option<EntityId> {
         for (key, typ) in self.entities.typ.iter() {
             if *typ == EntityType::Player {
@@ -137,28 +159,32 @@ impl GameData {
         return None;
     }
 
+    // an item is being used if it is held in either hand
     pub fn using(&self, entity_id: EntityId, item: Item) -> bool {
-        if let Some(inventory) = self.entities.inventory.get(&entity_id) {
-            if let Some(item_id) = inventory.get(0) {
-                return self.entities.item[item_id] == item;
+        for slot in [Slot::MainHand, Slot::OffHand].iter() {
+            if let Some(item_id) = self.entities.equipped(entity_id, *slot) {
+                if self.entities.item[&item_id] == item {
+                    return true;
+                }
             }
         }
 
         return false;
     }
 
-    pub fn used_up_item(&mut self, entity_id: EntityId) {
-        let mut option_item_id = None;
-        if let Some(inventory) = self.entities.inventory.get_mut(&entity_id) {
-            if let Some(item_id) = inventory.get(0) {
-                option_item_id = Some(*item_id);
+    // wear down the held item, returning the item if this broke it
+    pub fn wear_item(&mut self, entity_id: EntityId, amount: i32) -> Option<EntityId> {
+        if let Some(item_id) = self.entities.equipped(entity_id, Slot::MainHand) {
+            if let Some(durability) = self.entities.durability.get_mut(&item_id) {
+                durability.wear(amount);
+
+                if durability.broken() {
+                    return Some(item_id);
+                }
             }
         }
 
-        if let Some(item_id) = option_item_id {
-            self.remove_entity(item_id);
-            self.entities.inventory[&entity_id].remove(0);
-        }
+        return None;
     }
 
     pub fn within_aoe(&mut self, aoe: &Aoe) -> Vec<EntityId> {
@@ -189,10 +215,20 @@ impl GameData {
         self.entities.movement.remove(&id);
         self.entities.attack.remove(&id);
         self.entities.inventory.remove(&id);
+        self.entities.capacity.remove(&id);
+        self.entities.swims.remove(&id);
+        self.entities.count.remove(&id);
+        self.entities.slot.remove(&id);
+        self.entities.durability.remove(&id);
+        self.entities.stunned.remove(&id);
         self.entities.trap.remove(&id);
+        self.entities.trigger.remove(&id);
+        self.entities.links.remove(&id);
         self.entities.armed.remove(&id);
         self.entities.count_down.remove(&id);
+        self.entities.light.remove(&id);
         self.entities.move_mode.remove(&id);
+        self.entities.momentum.remove(&id);
         self.entities.direction.remove(&id);
         self.entities.selected_item.remove(&id);
         self.entities.action.remove(&id);
@@ -205,12 +241,40 @@ impl GameData {
         self.entities.needs_removal.remove(&id);
         self.entities.messages.remove(&id);
     }
+
+    /// The entities that belong to the current level- everything except
+    /// the player and the items they are carrying.
+    pub fn level_entities(&self, player_id: EntityId) -> Vec<EntityId> {
+        let carried = self.entities.inventory.get(&player_id).map_or(VecDeque::new(), |inventory| inventory.clone());
+
+        return self.entities.ids.iter()
+                                .filter(|id| **id != player_id && !carried.contains(*id))
+                                .map(|id| *id)
+                                .collect();
+    }
 }
 
+/// The effect of a trap when it is set off.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Trap {
     Spikes,
     Sound,
+    // closes, blocking its tile
+    Door,
+    // holds the monster on its tile until opened
+    Cage,
+}
+
+/// What sets off a trap. Triggers set off the traps they are linked to,
+/// or themselves if they have no links.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Trigger {
+    // stepping onto the trigger's tile
+    PressurePlate,
+    // crossing the line from the trigger's tile to the given position
+    Tripwire(Pos),
+    // going off every so many turns: period, turns until it next goes off
+    Timer(usize, usize),
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, Default)]
@@ -276,6 +340,11 @@ pub enum Item {
     Sword,
     SpikeTrap,
     SoundTrap,
+    HealingDraught,
+    SmokeBomb,
+    Noisemaker,
+    Torch,
+    Lantern,
 }
 
 impl Item {
@@ -289,6 +358,157 @@ impl Item {
             Item::Sword => ItemClass::Primary,
             Item::SpikeTrap => ItemClass::Secondary,
             Item::SoundTrap => ItemClass::Secondary,
+            Item::HealingDraught => ItemClass::Secondary,
+            Item::SmokeBomb => ItemClass::Secondary,
+            Item::Noisemaker => ItemClass::Secondary,
+            Item::Torch => ItemClass::Primary,
+            Item::Lantern => ItemClass::Secondary,
+        }
+    }
+
+    // the slot an item is placed in when picked up, if there is space
+    pub fn slot(&self) -> Slot {
+        match self {
+            Item::Dagger => Slot::MainHand,
+            Item::Hammer => Slot::MainHand,
+            Item::Sword => Slot::MainHand,
+            Item::Shield => Slot::OffHand,
+            Item::Stone => Slot::Belt,
+            Item::Goal => Slot::Pack,
+            Item::SpikeTrap => Slot::Pack,
+            Item::SoundTrap => Slot::Pack,
+            Item::HealingDraught => Slot::Pack,
+            Item::SmokeBomb => Slot::Belt,
+            Item::Noisemaker => Slot::Belt,
+            Item::Torch => Slot::MainHand,
+            Item::Lantern => Slot::Pack,
+        }
+    }
+
+    // items too heavy to run with while held
+    pub fn prevents_running(&self) -> bool {
+        return self.heavy();
+    }
+
+    // items too heavy to carry while swimming
+    pub fn heavy(&self) -> bool {
+        return *self == Item::Shield || *self == Item::Hammer;
+    }
+
+    // identical stackable items share one inventory slot
+    pub fn stackable(&self) -> bool {
+        match self {
+            Item::Stone => true,
+            Item::SpikeTrap => true,
+            Item::SoundTrap => true,
+            Item::HealingDraught => true,
+            Item::SmokeBomb => true,
+            Item::Noisemaker => true,
+            _ => false,
+        }
+    }
+
+    pub fn weight(&self) -> usize {
+        match self {
+            Item::Stone => 1,
+            Item::Goal => 1,
+            Item::Dagger => 1,
+            Item::Shield => 3,
+            Item::Hammer => 4,
+            Item::Sword => 2,
+            Item::SpikeTrap => 2,
+            Item::SoundTrap => 2,
+            Item::HealingDraught => 1,
+            Item::SmokeBomb => 1,
+            Item::Noisemaker => 1,
+            Item::Torch => 1,
+            Item::Lantern => 2,
+        }
+    }
+
+    /// Heavier items can't be thrown as far.
+    pub fn throw_range(&self, config: &Config) -> usize {
+        return std::cmp::max(1, config.throw_strength / self.weight());
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Durability {
+    pub current: i32,
+    pub max: i32,
+}
+
+impl Durability {
+    pub fn new(max: i32) -> Durability {
+        return Durability { current: max, max };
+    }
+
+    pub fn wear(&mut self, amount: i32) {
+        self.current = std::cmp::max(0, self.current - amount);
+    }
+
+    pub fn broken(&self) -> bool {
+        return self.current <= 0;
+    }
+}
+
+/// How much an entity can carry. Entities without a capacity can carry anything.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capacity {
+    pub max_weight: usize,
+    pub max_items: usize,
+    // carrying more than this weight slows the entity down
+    pub encumbered_weight: usize,
+}
+
+impl Capacity {
+    pub fn new(max_weight: usize, max_items: usize, encumbered_weight: usize) -> Capacity {
+        return Capacity { max_weight, max_items, encumbered_weight };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Slot {
+    MainHand,
+    OffHand,
+    Belt,
+    Pack,
+}
+
+impl Slot {
+    pub fn capacity(&self) -> usize {
+        match self {
+            Slot::MainHand => 1,
+            Slot::OffHand => 1,
+            Slot::Belt => BELT_CAPACITY,
+            Slot::Pack => usize::MAX,
+        }
+    }
+
+    pub fn holds(&self, item: Item) -> bool {
+        match self {
+            Slot::MainHand => item.class() == ItemClass::Primary && item != Item::Shield,
+            Slot::OffHand => item == Item::Shield,
+            Slot::Belt => {
+                item == Item::Stone || item == Item::Dagger ||
+                item == Item::SmokeBomb || item == Item::Noisemaker
+            }
+            Slot::Pack => true,
+        }
+    }
+
+    pub fn in_hand(&self) -> bool {
+        return *self == Slot::MainHand || *self == Slot::OffHand;
+    }
+}
+
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Slot::MainHand => write!(f, "main hand"),
+            Slot::OffHand => write!(f, "off hand"),
+            Slot::Belt => write!(f, "belt"),
+            Slot::Pack => write!(f, "pack"),
         }
     }
 }
@@ -354,6 +574,18 @@ pub enum EntityName {
     Spike,
     Stone,
     Mouse,
+    HealingDraught,
+    SmokeBomb,
+    Noisemaker,
+    Smoke,
+    PressurePlate,
+    Tripwire,
+    Timer,
+    Door,
+    Cage,
+    Torch,
+    Lantern,
+    Fire,
     Other,
 }
 
@@ -408,10 +640,20 @@ pub struct Entities {
     pub movement: CompStore<Reach>,
     pub attack: CompStore<Reach>,
     pub inventory: CompStore<VecDeque<EntityId>>,
+    pub capacity: CompStore<Capacity>,
+    pub swims: CompStore<bool>,
+    pub count: CompStore<usize>,
+    pub slot: CompStore<Slot>,
+    pub durability: CompStore<Durability>,
+    pub stunned: CompStore<usize>,
     pub trap: CompStore<Trap>,
+    pub trigger: CompStore<Trigger>,
+    pub links: CompStore<Vec<EntityId>>,
     pub armed: CompStore<bool>,
     pub count_down: CompStore<usize>,
+    pub light: CompStore<i32>, // radius of light given off
     pub move_mode: CompStore<MoveMode>,
+    pub momentum: CompStore<Momentum>,
     pub direction: CompStore<Direction>,
     pub selected_item: CompStore<EntityId>,
     pub action: CompStore<Action>,
@@ -446,10 +688,20 @@ impl Entities {
         self.movement.remove(entity_id);
         self.attack.remove(entity_id);
         self.inventory.remove(entity_id);
+        self.capacity.remove(entity_id);
+        self.swims.remove(entity_id);
+        self.count.remove(entity_id);
+        self.slot.remove(entity_id);
+        self.durability.remove(entity_id);
+        self.stunned.remove(entity_id);
         self.trap.remove(entity_id);
+        self.trigger.remove(entity_id);
+        self.links.remove(entity_id);
         self.armed.remove(entity_id);
         self.count_down.remove(entity_id);
+        self.light.remove(entity_id);
         self.move_mode.remove(entity_id);
+        self.momentum.remove(entity_id);
         self.direction.remove(entity_id);
         self.selected_item.remove(entity_id);
         self.action.remove(entity_id);
@@ -492,6 +744,196 @@ impl Entities {
         return id;
     }
 
+    pub fn slot_items(&self, entity_id: EntityId, slot: Slot) -> Vec<EntityId> {
+        let mut items = Vec::new();
+
+        if let Some(inventory) = self.inventory.get(&entity_id) {
+            for item_id in inventory.iter() {
+                if self.slot.get(item_id) == Some(&slot) {
+                    items.push(*item_id);
+                }
+            }
+        }
+
+        return items;
+    }
+
+    pub fn equipped(&self, entity_id: EntityId, slot: Slot) -> Option<EntityId> {
+        return self.slot_items(entity_id, slot).get(0).map(|item_id| *item_id);
+    }
+
+    pub fn can_run(&self, entity_id: EntityId) -> bool {
+        let main_hand = self.equipped(entity_id, Slot::MainHand);
+        let off_hand = self.equipped(entity_id, Slot::OffHand);
+
+        return !self.encumbered(entity_id) &&
+               !main_hand.iter().chain(off_hand.iter())
+                         .any(|item_id| self.item[item_id].prevents_running());
+    }
+
+    pub fn can_swim(&self, entity_id: EntityId) -> bool {
+        return self.swims.get(&entity_id) == Some(&true);
+    }
+
+    pub fn carried_weight(&self, entity_id: EntityId) -> usize {
+        return self.inventory.get(&entity_id).map_or(0, |inventory| {
+            inventory.iter().map(|item_id| self.stack_weight(*item_id)).sum()
+        });
+    }
+
+    pub fn can_carry(&self, entity_id: EntityId, item_id: EntityId) -> bool {
+        if let Some(capacity) = self.capacity.get(&entity_id) {
+            let mut num_items = self.inventory.get(&entity_id).map_or(0, |inventory| inventory.len());
+            // adding to a stack doesn't take up another slot
+            if self.find_stack(entity_id, item_id).is_some() {
+                num_items -= 1;
+            }
+            let weight = self.carried_weight(entity_id) + self.stack_weight(item_id);
+
+            return num_items < capacity.max_items && weight <= capacity.max_weight;
+        }
+
+        return true;
+    }
+
+    pub fn stack_count(&self, item_id: EntityId) -> usize {
+        return *self.count.get(&item_id).unwrap_or(&1);
+    }
+
+    pub fn stack_weight(&self, item_id: EntityId) -> usize {
+        return self.item[&item_id].weight() * self.stack_count(item_id);
+    }
+
+    // find a carried stack that an item can be added to
+    pub fn find_stack(&self, entity_id: EntityId, item_id: EntityId) -> Option<EntityId> {
+        if !self.item[&item_id].stackable() {
+            return None;
+        }
+
+        return self.inventory.get(&entity_id).and_then(|inventory| {
+            inventory.iter()
+                     .find(|other_id| **other_id != item_id && self.stacks_with(item_id, **other_id))
+                     .map(|other_id| *other_id)
+        });
+    }
+
+    // items only stack when they are in the same state, so an armed trap
+    // or a worn item is not merged into a fresh one
+    fn stacks_with(&self, item_id: EntityId, other_id: EntityId) -> bool {
+        return self.item[&item_id] == self.item[&other_id] &&
+               self.armed.get(&item_id) == self.armed.get(&other_id) &&
+               self.durability.get(&item_id) == self.durability.get(&other_id) &&
+               self.trap.get(&item_id) == self.trap.get(&other_id) &&
+               self.trigger.get(&item_id) == self.trigger.get(&other_id) &&
+               self.links.get(&item_id) == self.links.get(&other_id);
+    }
+
+    /// Take a single item off of a stack, returning the new entity for it.
+    /// Items that are not stacked are returned as they are.
+    pub fn split_stack(&mut self, item_id: EntityId) -> EntityId {
+        let count = self.stack_count(item_id);
+        if count <= 1 {
+            return item_id;
+        }
+        self.count[&item_id] = count - 1;
+
+        let pos = self.pos[&item_id];
+        let typ = self.typ[&item_id];
+        let chr = self.chr[&item_id];
+        let color = self.color[&item_id];
+        let name = self.name[&item_id];
+        let blocks = self.blocks[&item_id];
+        let split_id = self.create_entity(pos.x, pos.y, typ, chr, color, name, blocks);
+
+        let item = self.item[&item_id];
+        self.item.insert(split_id, item);
+        if let Some(trap) = self.trap.get(&item_id).map(|trap| *trap) {
+            self.trap.insert(split_id, trap);
+        }
+        if let Some(trigger) = self.trigger.get(&item_id).map(|trigger| *trigger) {
+            self.trigger.insert(split_id, trigger);
+        }
+        if let Some(armed) = self.armed.get(&item_id).map(|armed| *armed) {
+            self.armed.insert(split_id, armed);
+        }
+        if let Some(durability) = self.durability.get(&item_id).map(|durability| *durability) {
+            self.durability.insert(split_id, durability);
+        }
+        if let Some(links) = self.links.get(&item_id).map(|links| links.clone()) {
+            self.links.insert(split_id, links);
+        }
+
+        return split_id;
+    }
+
+    pub fn encumbered(&self, entity_id: EntityId) -> bool {
+        return self.capacity.get(&entity_id).map_or(false, |capacity| {
+            self.carried_weight(entity_id) > capacity.encumbered_weight
+        });
+    }
+
+    pub fn can_equip(&self, entity_id: EntityId, item_id: EntityId, slot: Slot) -> bool {
+        let item = self.item[&item_id];
+        let running = self.move_mode.get(&entity_id) == Some(&MoveMode::Run);
+
+        return slot.holds(item) &&
+               self.slot_items(entity_id, slot).len() < slot.capacity() &&
+               !(running && slot.in_hand() && item.prevents_running());
+    }
+
+    // place a carried item into a slot, returning whether the slot accepted it
+    pub fn equip(&mut self, entity_id: EntityId, item_id: EntityId, slot: Slot) -> bool {
+        if self.can_equip(entity_id, item_id, slot) {
+            self.slot.insert(item_id, slot);
+            return true;
+        }
+
+        return false;
+    }
+
+    pub fn remove_item(&mut self, entity_id: EntityId, item_id: EntityId) {
+        if let Some(inventory) = self.inventory.get_mut(&entity_id) {
+            inventory.retain(|id| *id != item_id);
+        }
+        self.slot.remove(&item_id);
+    }
+
+    pub fn link(&mut self, trigger_id: EntityId, effect_id: EntityId) {
+        self.links.entry(trigger_id).or_insert(Vec::new()).push(effect_id);
+    }
+
+    // the traps set off by a trigger
+    pub fn trigger_effects(&self, trigger_id: EntityId) -> Vec<EntityId> {
+        return self.links.get(&trigger_id).map_or(vec!(trigger_id), |links| links.clone());
+    }
+
+    // whether an entity standing at the given position sets off the trigger
+    pub fn triggered_at(&self, trigger_id: EntityId, pos: Pos) -> bool {
+        let trigger_pos = self.pos[&trigger_id];
+
+        match self.trigger.get(&trigger_id) {
+            Some(Trigger::PressurePlate) => {
+                return pos == trigger_pos;
+            }
+
+            Some(Trigger::Tripwire(end)) => {
+                return pos == trigger_pos || line_inclusive(trigger_pos, *end).contains(&pos);
+            }
+
+            _ => {
+                return false;
+            }
+        }
+    }
+
+    // drop a carried item onto the entity's tile
+    pub fn drop_item(&mut self, entity_id: EntityId, item_id: EntityId) {
+        self.remove_item(entity_id, item_id);
+
+        let pos = self.pos[&entity_id];
+        self.set_pos(item_id, pos);
+    }
+
     pub fn set_xy(&mut self, entity_id: EntityId, x: i32, y: i32) {
         self.pos[&entity_id] = Pos::new(x, y);
     }
@@ -575,9 +1017,22 @@ impl Entities {
             }
         }
 
+        // lit positions are noticed whichever way the entity is facing
+        if self.is_lit(other_pos) && map.is_in_fov(pos, other_pos, radius) {
+            return true;
+        }
+
         return false;
     }
 
+    pub fn is_lit(&self, pos: Pos) -> bool {
+        return self.light.iter().any(|(key, radius)| distance(self.pos[key], pos) < *radius);
+    }
+
+    pub fn on_fire(&self, pos: Pos) -> bool {
+        return self.name.iter().any(|(key, name)| *name == EntityName::Fire && self.pos[key] == pos);
+    }
+
     pub fn was_attacked(&mut self, entity_id: EntityId) -> Option<Message> {
         if let Some(index) = self.messages[&entity_id].iter().position(|msg| matches!(msg, Message::Attack(_))) {
             return Some(self.messages[&entity_id].remove(index));
@@ -4,10 +4,10 @@ use std::hash::{Hash, Hasher};
 use serde::{Serialize, Deserialize};
 
 use crate::ai::Behavior;
-use crate::constants::{HAMMER_DAMAGE};
-use crate::map::{Surface};
+use crate::constants::{HAMMER_DAMAGE, ELEVATION_ATTACK_BONUS};
+use crate::map::{Surface, Wall, TileType};
 use crate::types::*;
-use crate::movement::{Reach, MoveMode, check_collision, MoveType, Movement};
+use crate::movement::{Reach, MoveMode, MoveType, Movement};
 use crate::messaging::*;
 
 
@@ -171,34 +171,58 @@ pub fn push_attack(handle: EntityId,
                    data: &mut GameData,
                    msg_log: &mut MsgLog) {
     let mut killed = false;
+    let mut drowned = false;
     let mut damage = 0;
 
+    // what the target slid into, if it hit another entity
+    let mut toppled = None;
+    let mut slammed = None;
+
+    // the target's position is tracked here, as its moves are not resolved
+    // until the messages below are processed.
+    let mut other_pos = data.entities.pos[&target];
+    let mut moves = Vec::new();
+
     for _ in 0..pos_mag(delta_pos) {
         let pos = data.entities.pos[&handle];
-        let other_pos = data.entities.pos[&target];
         let diff = other_pos - pos;
 
         let x_diff = signedness(diff.x);
         let y_diff = signedness(diff.y);
+        let direction = Pos::new(x_diff, y_diff);
 
-        let move_result = check_collision(other_pos, x_diff, y_diff, data);
-
-        let past_pos = move_by(other_pos, Pos::new(x_diff, y_diff));
+        let past_pos = move_by(other_pos, direction);
 
         if move_into {
             let movement = Movement::new(other_pos, MoveType::Move, None);
-            msg_log.log_front(Msg::Moved(target, movement, past_pos));
+            moves.push(Msg::Moved(target, movement, past_pos));
 
             let movement = Movement::new(past_pos, MoveType::Move, None);
-            msg_log.log_front(Msg::Moved(target, movement, past_pos));
+            moves.push(Msg::Moved(target, movement, past_pos));
         }
 
-        if move_result.no_collision() {
-            // if not blocked, push the other entity, taking their space
-            let movement = Movement::new(past_pos, MoveType::Move, None);
-            msg_log.log_front(Msg::Moved(target, movement, past_pos));
-        } else {
-            // otherwise crush them against the wall/entity
+        let blocked = data.map.is_blocked_by_wall(other_pos, x_diff, y_diff);
+
+        // deep water is a blocked tile, but it does not crush what is pushed into it
+        let into_water = blocked.map_or(false, |blocked| {
+            blocked.blocked_tile &&
+            blocked.wall_type == Wall::Empty &&
+            data.map.is_within_bounds(past_pos) &&
+            data.map[past_pos].tile_type == TileType::Water
+        });
+
+        if into_water {
+            if data.entities.can_swim(target) {
+                // swimmers end up in the water, which stops their slide
+                let movement = Movement::new(past_pos, MoveType::Move, None);
+                moves.push(Msg::Moved(target, movement, past_pos));
+            } else {
+                drowned = true;
+                other_pos = past_pos;
+            }
+            break;
+        } else if blocked.is_some() {
+            // crush them against the wall
             data.entities.alive[&target] = false;
             data.entities.blocks[&target] = false;
             damage = data.entities.fighter[&target].hp;
@@ -207,12 +231,42 @@ pub fn push_attack(handle: EntityId,
 
             // once we crush an entity, we lose the rest of the move
             break;
+        } else if let Some(other_id) = data.has_blocking_entity(past_pos) {
+            // columns are knocked over, and anything else is slammed into
+            if data.entities.typ[&other_id] == EntityType::Column {
+                toppled = Some((other_id, direction));
+            } else {
+                slammed = Some(other_id);
+            }
+            break;
+        } else {
+            // if not blocked, push the other entity, taking their space.
+            // any traps they slide over are set off by the move.
+            let movement = Movement::new(past_pos, MoveType::Move, None);
+            moves.push(Msg::Moved(target, movement, past_pos));
+
+            other_pos = past_pos;
         }
     }
 
+    // log in reverse so the moves are resolved in the order they occurred
+    for msg in moves.into_iter().rev() {
+        msg_log.log_front(msg);
+    }
+
     if killed {
         msg_log.log(Msg::Killed(handle, target, damage));
+    } else if drowned {
+        msg_log.log(Msg::Drowned(handle, target, other_pos));
     } else {
+        if let Some((column, direction)) = toppled {
+            msg_log.log(Msg::Toppled(target, column, direction));
+        }
+
+        if let Some(other_id) = slammed {
+            msg_log.log(Msg::Slammed(target, other_id));
+        }
+
         data.entities.messages[&target].push(Message::Attack(handle));
     }
 }
@@ -229,6 +283,26 @@ pub fn crush(handle: EntityId, target: EntityId, entities: &mut Entities, msg_lo
     }
 }
 
+/// Take hp from an entity, killing it if it has none left.
+/// Returns whether the entity was killed.
+pub fn damage_entity(cause: EntityId, target: EntityId, damage: Hp, entities: &mut Entities, msg_log: &mut MsgLog) -> bool {
+    if entities.fighter.get(&target).is_none() {
+        return false;
+    }
+
+    entities.take_damage(target, damage);
+
+    if entities.fighter[&target].hp <= 0 {
+        entities.alive[&target] = false;
+        entities.blocks[&target] = false;
+
+        msg_log.log(Msg::Killed(cause, target, damage));
+        return true;
+    }
+
+    return false;
+}
+
 pub fn attack(entity: EntityId, target: EntityId, data: &mut GameData, msg_log: &mut MsgLog) {
     if data.using(entity, Item::Hammer) {
         data.entities.alive[&target] = false;
@@ -264,8 +338,15 @@ pub fn attack(entity: EntityId, target: EntityId, data: &mut GameData, msg_log:
         }
     } else {
         // NOTE could add another section for the sword- currently the same as normal attacks
-        let damage = data.entities.fighter.get(&entity).map_or(0, |f| f.power) -
-                     data.entities.fighter.get(&target).map_or(0, |f| f.defense);
+        let mut damage = data.entities.fighter.get(&entity).map_or(0, |f| f.power) -
+                         data.entities.fighter.get(&target).map_or(0, |f| f.defense);
+
+        // attacking down from higher ground hits harder
+        let entity_pos = data.entities.pos[&entity];
+        let target_pos = data.entities.pos[&target];
+        if data.map[entity_pos].elevation > data.map[target_pos].elevation {
+            damage += ELEVATION_ATTACK_BONUS;
+        }
         if damage > 0 && data.entities.alive[&target] {
             data.entities.take_damage(target, damage);
 
@@ -301,20 +382,6 @@ pub fn stab(handle: EntityId, target: EntityId, entities: &mut Entities, msg_log
     }
 }
 
-pub fn item_primary_at(entity_id: EntityId, entities: &mut Entities, index: usize) -> bool {
-    let inv_len = entities.inventory[&entity_id].len();
-
-    if inv_len <= index {
-        return false;
-    }
-
-    let item_id = entities.inventory[&entity_id][index];
-    let is_primary =
-        entities.item[&item_id].class() == ItemClass::Primary;
-
-    return is_primary;
-}
-
 pub fn add_pos(pos1: Pos, pos2: Pos) -> Pos {
     return Pos::new(pos1.x + pos2.x, pos1.y + pos2.y);
 }
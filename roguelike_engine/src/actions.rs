@@ -3,13 +3,15 @@ use serde::{Serialize, Deserialize};
 use roguelike_core::movement::{Direction, Action, Reach};
 use roguelike_core::types::*;
 use roguelike_core::movement;
-use roguelike_core::utils::{reach_by_mode, item_primary_at, line};
+use roguelike_core::utils::{reach_by_mode, line, add_pos, sub_pos};
 use roguelike_core::messaging::{Msg, MsgLog};
 use roguelike_core::constants::*;
 use roguelike_core::config::Config;
+use roguelike_core::map::{Wall, TileType};
 
 use crate::game::*;
 use crate::make_map;
+use crate::items::{item_behavior, UseKind};
 
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -69,9 +71,12 @@ pub enum InputAction {
 pub fn handle_input_inventory(input: InputAction,
                               data: &mut GameData,
                               settings: &mut GameSettings,
-                              msg_log: &mut MsgLog) {
+                              config: &Config,
+                              msg_log: &mut MsgLog) -> Action {
     let player_id = data.find_player().unwrap();
 
+    let mut player_turn: Action = Action::none();
+
     match input {
         InputAction::Inventory => {
             settings.state = GameState::Playing;
@@ -88,13 +93,17 @@ pub fn handle_input_inventory(input: InputAction,
                 settings.selection.only_visible = false;
 
                 // if the item is a trap, set it.
+                // if it can be used, use it.
                 // otherwise, throw it.
                 if data.entities.trap.get(&item_key).is_some() {
                     settings.selection =
                         Selection::new(SelectionType::WithinReach(Reach::single(1)), SelectionAction::PlaceTrap);
+                } else if item_behavior(data.entities.item[&item_key]).kind != UseKind::Passive {
+                    player_turn = start_item_use(player_id, item_key, data, settings);
                 } else {
+                    let range = data.entities.item[&item_key].throw_range(config);
                     settings.selection =
-                        Selection::new(SelectionType::WithinRadius(PLAYER_THROW_DIST), SelectionAction::Throw);
+                        Selection::new(SelectionType::WithinRadius(range), SelectionAction::Throw);
                 }
 
                 msg_log.log(Msg::GameState(settings.state));
@@ -114,6 +123,36 @@ pub fn handle_input_inventory(input: InputAction,
         _ => {
         }
     }
+
+    return player_turn;
+}
+
+/// Self-use items produce an action immediately, while targeted items
+/// enter the selection state to choose where they are used.
+pub fn start_item_use(entity_id: EntityId,
+                      item_id: EntityId,
+                      data: &mut GameData,
+                      settings: &mut GameSettings) -> Action {
+    let mut action = Action::none();
+
+    data.entities.selected_item.insert(entity_id, item_id);
+
+    match item_behavior(data.entities.item[&item_id]).kind {
+        UseKind::Targeted(selection_type) => {
+            settings.state = GameState::Selection;
+            settings.selection = Selection::new(selection_type, SelectionAction::UseItem);
+        }
+
+        UseKind::SelfUse => {
+            settings.state = GameState::Playing;
+            action = Action::UseItem(item_id, data.entities.pos[&entity_id]);
+        }
+
+        UseKind::Passive => {
+        }
+    }
+
+    return action;
 }
 
 pub fn handle_input_selection(input: InputAction,
@@ -189,9 +228,12 @@ pub fn handle_input(game: &mut Game) -> Action {
         }
 
         (InputAction::DropItem, true) => {
-            if let Some(item_id) = game.data.entities.inventory[&player_id].remove(0) {
-               let player_pos = game.data.entities.pos[&player_id];
-               game.data.entities.set_pos(item_id, player_pos);
+            // drop the held item, or the first item carried if nothing is held
+            let held_item = game.data.entities.equipped(player_id, Slot::MainHand);
+            let first_item = game.data.entities.inventory[&player_id].front().map(|id| *id);
+
+            if let Some(item_id) = held_item.or(first_item) {
+               game.data.entities.drop_item(player_id, item_id);
             }
         }
 
@@ -220,13 +262,12 @@ pub fn handle_input(game: &mut Game) -> Action {
         }
 
         (InputAction::IncreaseMoveMode, true) => {
-            let holding_shield = game.data.using(player_id, Item::Shield);
-            let holding_hammer = game.data.using(player_id, Item::Hammer);
-
             let move_mode = game.data.entities.move_mode.get(&player_id).expect("Player should have a move mode");
             let new_move_mode = move_mode.increase();
 
-            if new_move_mode == movement::MoveMode::Run && (holding_shield || holding_hammer) {
+            if new_move_mode == movement::MoveMode::Run && game.data.entities.encumbered(player_id) {
+                game.msg_log.log(Msg::TriedRunEncumbered);
+            } else if new_move_mode == movement::MoveMode::Run && !game.data.entities.can_run(player_id) {
                 game.msg_log.log(Msg::TriedRunWithShield);
             } else {
                 game.data.entities.move_mode[&player_id] = new_move_mode;
@@ -245,6 +286,11 @@ pub fn handle_input(game: &mut Game) -> Action {
             game.data.entities.movement[&player_id] =
                 reach_by_mode(game.data.entities.move_mode[&player_id]);
 
+            // slowing down loses any built up momentum
+            if let Some(momentum) = game.data.entities.momentum.get_mut(&player_id) {
+                momentum.clear();
+            }
+
             game.msg_log.log(Msg::MoveMode(game.data.entities.move_mode[&player_id]));
 
             player_turn = Action::none();
@@ -280,15 +326,27 @@ pub fn handle_input(game: &mut Game) -> Action {
         }
 
         (InputAction::SwapPrimaryItem, _) => {
-            if item_primary_at(player_id, &mut game.data.entities, 0) &&
-               item_primary_at(player_id, &mut game.data.entities, 1) {
-                   let temp_id = game.data.entities.inventory[&player_id][0];
+            // swap the held item with the next item in the pack that can be wielded
+            let entities = &mut game.data.entities;
+            let main_hand = entities.equipped(player_id, Slot::MainHand);
+            let next_item =
+                entities.slot_items(player_id, Slot::Pack)
+                        .into_iter()
+                        .find(|item_id| Slot::MainHand.holds(entities.item[item_id]));
+
+            if let Some(next_id) = next_item {
+                if let Some(main_id) = main_hand {
+                    entities.slot[&main_id] = Slot::Pack;
+                }
 
-                   game.data.entities.inventory[&player_id][0] = 
-                       game.data.entities.inventory[&player_id][1];
+                if !entities.equip(player_id, next_id, Slot::MainHand) {
+                    if let Some(main_id) = main_hand {
+                        entities.slot[&main_id] = Slot::MainHand;
+                    }
 
-                   game.data.entities.inventory[&player_id][1] = temp_id;
-           }
+                    game.msg_log.log(Msg::CantEquip(player_id, next_id));
+                }
+            }
         }
 
         (InputAction::RegenerateMap, _) => {
@@ -337,15 +395,13 @@ pub fn handle_input(game: &mut Game) -> Action {
         }
 
         (InputAction::UseItem, _) => {
-            if game.data.using(player_id, Item::Hammer) {
-                game.settings.state = GameState::Selection;
-                let reach = Reach::Horiz(1);
-                game.settings.selection =
-                    Selection::new(SelectionType::WithinReach(reach), SelectionAction::Hammer);
-                game.msg_log.log(Msg::GameState(game.settings.state));
-            } else if game.data.using(player_id, Item::Sword) {
-                let player_pos = game.data.entities.pos[&player_id];
-                player_turn = Action::UseItem(player_pos);
+            if let Some(item_id) = game.data.entities.equipped(player_id, Slot::MainHand) {
+                let previous_state = game.settings.state;
+                player_turn = start_item_use(player_id, item_id, &mut game.data, &mut game.settings);
+
+                if game.settings.state != previous_state {
+                    game.msg_log.log(Msg::GameState(game.settings.state));
+                }
             }
         }
 
@@ -356,52 +412,111 @@ pub fn handle_input(game: &mut Game) -> Action {
     return player_turn;
 }
 
+/// Pick up an item, returning the id of the inventory entry it ended up in.
 pub fn pick_item_up(entity_id: EntityId,
                     pickedup_id: EntityId,
-                    entities: &mut Entities) {
-    // pick up item
-    let item = entities.item[&pickedup_id];
-    let item_class = item.class();
-
-    match item_class {
-        ItemClass::Primary => {
-            if item_primary_at(entity_id, entities, 0) &&
-               item_primary_at(entity_id, entities, 1) {
-                entities.inventory[&entity_id][0] = pickedup_id;
+                    entities: &mut Entities) -> EntityId {
+    // identical items are added to a stack that is already carried
+    if let Some(stack_id) = entities.find_stack(entity_id, pickedup_id) {
+        let count = entities.stack_count(stack_id) + entities.stack_count(pickedup_id);
+        entities.count.insert(stack_id, count);
+        entities.remove(&pickedup_id);
+
+        return stack_id;
+    }
 
-                let obj_pos = entities.pos[&entity_id];
-                entities.set_pos(entity_id, obj_pos);
-            } else {
-                entities.inventory[&entity_id].push_front(pickedup_id);
-            }
-        }
+    // pick up item, placing it in its own slot if there is room, or the pack otherwise
+    let item = entities.item[&pickedup_id];
 
-        ItemClass::Secondary => {
-            entities.inventory[&entity_id].push_back(pickedup_id);
-        }
-    } 
+    entities.inventory[&entity_id].push_back(pickedup_id);
+    if !entities.equip(entity_id, pickedup_id, item.slot()) {
+        entities.slot.insert(pickedup_id, Slot::Pack);
+    }
 
     entities.set_xy(pickedup_id, -1, -1);
+
+    return pickedup_id;
 }
 
-pub fn throw_item(_player_id: EntityId,
+/// Moves a thrown item along its path, returning the entity it hit, if any.
+/// Items fly over water and short walls stop them, but they bounce off tall walls.
+pub fn throw_item(thrower_id: EntityId,
                   item_id: EntityId,
                   start_pos: Pos,
                   end_pos: Pos,
-                  game_data: &mut GameData) {
-    let throw_line = line(start_pos, end_pos);
+                  game_data: &mut GameData,
+                  config: &Config) -> Option<EntityId> {
+    let range = game_data.entities.item[&item_id].throw_range(config);
 
     // get target position in direction of player click
-    let mut end_pos =
-        Pos::from(throw_line.into_iter().take(PLAYER_THROW_DIST).last().unwrap());
+    let throw_line = line(start_pos, end_pos);
+    let mut prev_pos = start_pos;
+    let mut steps = Vec::new();
+    for pos in throw_line.into_iter().take(range) {
+        steps.push(sub_pos(pos, prev_pos));
+        prev_pos = pos;
+    }
 
-    let pos_diff = end_pos - start_pos;
-    if let Some(blocked) = game_data.map.is_blocked_by_wall(start_pos, pos_diff.x, pos_diff.y) {
-        // the start pos of the blocked struct is the last reached position
-        end_pos = blocked.start_pos;
+    let mut hit_entity = None;
+    let mut pos = start_pos;
+    let mut last_dry_pos = start_pos;
+    // after a bounce, the rest of the path is mirrored along the blocked axes
+    let mut flip = Pos::new(1, 1);
+    let mut bounced = false;
+    for step in steps {
+        let step = Pos::new(step.x * flip.x, step.y * flip.y);
+        let next_pos = add_pos(pos, step);
+
+        if let Some(blocked) = game_data.map.is_blocked_by_wall(pos, step.x, step.y) {
+            let in_bounds = game_data.map.is_within_bounds(next_pos);
+            let tile_type = if in_bounds { game_data.map[next_pos].tile_type } else { TileType::Wall };
+
+            let over_water = blocked.wall_type == Wall::Empty && tile_type == TileType::Water;
+            if !over_water {
+                let tall_wall = blocked.wall_type == Wall::TallWall ||
+                                (blocked.blocked_tile && tile_type == TileType::Wall);
+                if !tall_wall || bounced {
+                    break;
+                }
+
+                bounced = true;
+                if step.x != 0 && step.y != 0 {
+                    let blocked_x = if step.x > 0 { game_data.map.blocked_right(pos) } else { game_data.map.blocked_left(pos) };
+                    let blocked_y = if step.y > 0 { game_data.map.blocked_down(pos) } else { game_data.map.blocked_up(pos) };
+                    if blocked_x || !blocked_y {
+                        flip.x = -flip.x;
+                    }
+                    if blocked_y || !blocked_x {
+                        flip.y = -flip.y;
+                    }
+                } else if step.x != 0 {
+                    flip.x = -flip.x;
+                } else {
+                    flip.y = -flip.y;
+                }
+
+                // the step into the wall is lost in the bounce
+                continue;
+            }
+        }
+
+        if let Some(other_id) = game_data.has_blocking_entity(next_pos) {
+            if other_id != thrower_id {
+                hit_entity = Some(other_id);
+                break;
+            }
+        }
+
+        pos = next_pos;
+        if game_data.map[pos].tile_type != TileType::Water {
+            last_dry_pos = pos;
+        }
     }
 
-    game_data.entities.set_pos(item_id, end_pos);
+    // items can't land in water, so they fall short at its edge
+    game_data.entities.set_pos(item_id, last_dry_pos);
+
+    return hit_entity;
 }
 
 pub fn place_trap(trap_id: EntityId,
@@ -0,0 +1,235 @@
+use std::env;
+use std::fs;
+
+use roguelike_core::types::*;
+use roguelike_core::map::*;
+use roguelike_core::config::Config;
+
+use roguelike_engine::game::Game;
+use roguelike_engine::make_map::{make_map, write_map_xp, MapCache};
+use roguelike_engine::text_map::write_map_text;
+use roguelike_engine::solvability::{check_solvable, path_length, key_positions, exit_positions};
+
+
+const USAGE: &str =
+"usage: mapgen [options]
+  --generator NAME    island, bsp, caves, wfc, test_map, wall_test, corner_test,
+                      player_test, empty, or the name of an .xp or .txt map file
+                      (default island)
+  --seeds START..END  range of seeds to generate (default 0..10)
+  --width WIDTH       map width for generated maps
+  --height HEIGHT     map height for generated maps
+  --depth DEPTH       level depth used when placing monsters and items (default 0)
+  --config FILE       config file (default config.yaml)
+  --set NAME=VALUE    override a config value, given as YAML
+  --output DIR        directory to write maps to (default mapgen)";
+
+struct Options {
+    map_config: MapConfig,
+    map_name: String,
+    seeds: std::ops::Range<u64>,
+    depth: usize,
+    config_file: String,
+    overrides: Vec<(String, String)>,
+    output: String,
+}
+
+/// Statistics for a single generated map.
+struct MapStats {
+    open_ratio: f32,
+    key_to_exit: Option<usize>,
+    num_monsters: usize,
+    solvable: bool,
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let config = match Config::from_file(&options.config_file).with_overrides(&options.overrides) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(err) = fs::create_dir_all(&options.output) {
+        eprintln!("Could not create {}: {}", options.output, err);
+        std::process::exit(1);
+    }
+
+    println!("{:>8} {:>6} {:>11} {:>9} {}", "seed", "open", "key->exit", "monsters", "solvable");
+
+    let mut all_stats = Vec::new();
+    let mut map_cache = MapCache::default();
+    for seed in options.seeds.clone() {
+        let mut game = Game::new(seed, config.clone()).unwrap();
+        game.settings.depth = options.depth;
+
+        // vaults and the wfc model are made once for the whole batch
+        game.map_cache = map_cache;
+        make_map(&options.map_config, &mut game);
+        map_cache = std::mem::take(&mut game.map_cache);
+        let player_pos = game.data.entities.pos[&game.data.find_player().unwrap()];
+        let stats = map_stats(&game, player_pos);
+
+        let key_to_exit = stats.key_to_exit.map_or("-".to_string(), |length| length.to_string());
+        println!("{:>8} {:>6.2} {:>11} {:>9} {}",
+                 seed, stats.open_ratio, key_to_exit, stats.num_monsters, stats.solvable);
+
+        let file_name = format!("{}/{}_{}", options.output, options.map_name, seed);
+        let written =
+            fs::write(format!("{}.txt", file_name), write_map_text(&game.data))
+                .and_then(|_| write_map_xp(&game.data, &format!("{}.xp", file_name)));
+        if let Err(err) = written {
+            eprintln!("Could not write {}: {}", file_name, err);
+            std::process::exit(1);
+        }
+
+        all_stats.push(stats);
+    }
+
+    print_summary(&all_stats);
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        map_config: MapConfig::new(MapSource::Island),
+        map_name: "island".to_string(),
+        seeds: 0..10,
+        depth: 0,
+        config_file: "config.yaml".to_string(),
+        overrides: Vec::new(),
+        output: "mapgen".to_string(),
+    };
+
+    let mut index = 0;
+    while index < args.len() {
+        let flag = &args[index];
+        if flag == "--help" {
+            println!("{}", USAGE);
+            std::process::exit(0);
+        }
+
+        let value = args.get(index + 1).ok_or(format!("Missing value for {}", flag))?;
+
+        match flag.as_str() {
+            "--generator" => {
+                options.map_config.source = parse_map_source(value)?;
+                options.map_name = value.trim_end_matches(".xp").trim_end_matches(".txt").replace('/', "_");
+            }
+
+            "--seeds" => {
+                let mut bounds = value.split("..");
+                let start = bounds.next().and_then(|start| start.parse().ok());
+                let end = bounds.next().and_then(|end| end.parse().ok());
+                match (start, end) {
+                    (Some(start), Some(end)) => options.seeds = start..end,
+                    _ => return Err(format!("Seeds should be given as START..END, not {}", value)),
+                }
+            }
+
+            "--width" => options.map_config.width = parse_number(flag, value)?,
+            "--height" => options.map_config.height = parse_number(flag, value)?,
+            "--depth" => options.depth = parse_number(flag, value)?,
+
+            "--config" => options.config_file = value.clone(),
+
+            "--set" => {
+                let mut parts = value.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(name), Some(field_value)) => {
+                        options.overrides.push((name.to_string(), field_value.to_string()));
+                    }
+                    _ => return Err(format!("Overrides should be given as NAME=VALUE, not {}", value)),
+                }
+            }
+
+            "--output" => options.output = value.clone(),
+
+            _ => return Err(format!("Unknown option {}", flag)),
+        }
+
+        index += 2;
+    }
+
+    options.map_config.validate()?;
+
+    return Ok(options);
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    return value.parse().map_err(|_| format!("Expected a number for {}, not {}", flag, value));
+}
+
+fn parse_map_source(name: &str) -> Result<MapSource, String> {
+    match name {
+        "island" => Ok(MapSource::Island),
+        "bsp" => Ok(MapSource::Bsp),
+        "caves" => Ok(MapSource::Caves),
+        "wfc" => Ok(MapSource::Wfc),
+        "test_map" => Ok(MapSource::TestMap),
+        "wall_test" => Ok(MapSource::TestWall),
+        "corner_test" => Ok(MapSource::TestCorner),
+        "player_test" => Ok(MapSource::TestPlayer),
+        "empty" => Ok(MapSource::Empty),
+        _ if name.ends_with(".xp") || name.ends_with(".txt") => Ok(MapSource::FromFile(name.to_string())),
+        _ => Err(format!("Unknown generator {}", name)),
+    }
+}
+
+fn map_stats(game: &Game, player_pos: Pos) -> MapStats {
+    let data = &game.data;
+    let swims = data.find_player().map_or(false, |player_id| data.entities.can_swim(player_id));
+
+    let num_tiles = data.map.width() * data.map.height();
+    let num_open = data.map.tiles.iter().flatten().filter(|tile| !tile.blocked).count();
+
+    let mut key_to_exit = None;
+    for key_pos in key_positions(data) {
+        for exit_pos in exit_positions(data) {
+            if let Some(length) = path_length(&data.map, key_pos, exit_pos, swims) {
+                key_to_exit = Some(key_to_exit.map_or(length, |shortest: usize| shortest.min(length)));
+            }
+        }
+    }
+
+    let num_monsters = data.entities.typ.values().filter(|typ| **typ == EntityType::Enemy).count();
+
+    return MapStats {
+        open_ratio: num_open as f32 / num_tiles as f32,
+        key_to_exit,
+        num_monsters,
+        solvable: check_solvable(data, player_pos).is_ok(),
+    };
+}
+
+fn print_summary(all_stats: &[MapStats]) {
+    if all_stats.len() == 0 {
+        return;
+    }
+    let num_maps = all_stats.len() as f32;
+
+    let open_ratio = all_stats.iter().map(|stats| stats.open_ratio).sum::<f32>() / num_maps;
+    let num_monsters = all_stats.iter().map(|stats| stats.num_monsters).sum::<usize>() as f32 / num_maps;
+
+    let lengths: Vec<usize> = all_stats.iter().filter_map(|stats| stats.key_to_exit).collect();
+    let key_to_exit = lengths.iter().sum::<usize>() as f32 / std::cmp::max(1, lengths.len()) as f32;
+
+    let failures = all_stats.iter().filter(|stats| !stats.solvable).count();
+
+    println!();
+    println!("maps generated:      {}", all_stats.len());
+    println!("average open ratio:  {:.2}", open_ratio);
+    println!("average key->exit:   {:.1}", key_to_exit);
+    println!("average monsters:    {:.1}", num_monsters);
+    println!("reachability failures: {}", failures);
+}
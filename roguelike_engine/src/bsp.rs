@@ -0,0 +1,293 @@
+use rand::prelude::*;
+
+use log::warn;
+
+use roguelike_core::types::*;
+use roguelike_core::map::*;
+use roguelike_core::config::*;
+use roguelike_core::messaging::MsgLog;
+use roguelike_core::utils::{move_x, move_y, signedness, distance};
+
+use crate::generation::{make_key, make_island};
+use crate::solvability::mutually_reachable;
+#[cfg(test)]
+use roguelike_core::constants::*;
+#[cfg(test)]
+use crate::game::Game;
+#[cfg(test)]
+use crate::solvability::check_solvable;
+
+
+/// Number of times to regenerate the layout if it can't be solved.
+const BSP_ATTEMPTS: usize = 10;
+
+/// The smallest leaf that still fits a room with a tile of space around it.
+const BSP_SMALLEST_LEAF: i32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Rect {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl Rect {
+    fn new(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        return Rect { x, y, width, height };
+    }
+
+    fn center(&self) -> Pos {
+        return Pos::new(self.x + self.width / 2, self.y + self.height / 2);
+    }
+
+    fn random_pos(&self, rng: &mut SmallRng) -> Pos {
+        return Pos::new(rng.gen_range(self.x, self.x + self.width),
+                        rng.gen_range(self.y, self.y + self.height));
+    }
+}
+
+enum BspNode {
+    Leaf(Rect),
+    Split(Box<BspNode>, Box<BspNode>),
+}
+
+/// Generate rooms connected by corridors by recursively splitting the map.
+/// Rooms are walled in either by full wall tiles, or by walls between tiles,
+/// and the corridors knock doorways through whichever walls they cross.
+/// Returns the player's starting position.
+pub fn make_bsp(data: &mut GameData,
+                config: &Config,
+                msg_log: &mut MsgLog,
+                rng: &mut SmallRng) -> Pos {
+    let swims = data.find_player().map_or(false, |player_id| data.entities.can_swim(player_id));
+
+    let mut rooms = Vec::new();
+    for _ in 0..BSP_ATTEMPTS {
+        for x in 0..data.map.width() {
+            for y in 0..data.map.height() {
+                data.map[Pos::new(x, y)] = Tile::wall();
+            }
+        }
+
+        let bounds = Rect::new(0, 0, data.map.width(), data.map.height());
+        let tree = split(bounds, config, rng);
+
+        rooms.clear();
+        carve_node(&tree, &mut data.map, config, rng, &mut rooms);
+
+        // the key is placed in the room furthest from the start, and the exit in another room
+        let start = rooms[0].center();
+        let key_room = *rooms.iter().max_by_key(|room| distance(start, room.center())).unwrap();
+        let key_pos = key_room.random_pos(rng);
+
+        let exit_rooms: Vec<Rect> =
+            rooms.iter().skip(1).filter(|room| **room != key_room).map(|room| *room).collect();
+        let exit_room = *exit_rooms.choose(rng).unwrap_or(&key_room);
+        let exit_pos = exit_room.random_pos(rng);
+
+        // a layout with only one small room may not have space for both the key and exit
+        let separate = exit_pos != key_pos && exit_pos != start;
+        if separate && mutually_reachable(&data.map, &[start, key_pos, exit_pos], swims).is_ok() {
+            // the exit keeps any walls along its edges
+            let exit_walls = (data.map[exit_pos].left_wall, data.map[exit_pos].bottom_wall);
+            data.map[exit_pos] = Tile::exit();
+            data.map[exit_pos].left_wall = exit_walls.0;
+            data.map[exit_pos].bottom_wall = exit_walls.1;
+
+            make_key(&mut data.entities, config, key_pos, msg_log);
+
+            data.map.update_map();
+
+            return start;
+        }
+    }
+
+    warn!("no solvable bsp layout in {} attempts, making an island instead", BSP_ATTEMPTS);
+    let (width, height) = (data.map.width(), data.map.height());
+    data.map = Map::from_dims(width as usize, height as usize);
+    return make_island(data, config, msg_log, rng);
+}
+
+fn split(rect: Rect, config: &Config, rng: &mut SmallRng) -> BspNode {
+    let min_size = std::cmp::max(BSP_SMALLEST_LEAF, config.bsp_min_leaf_size);
+    let can_split_x = rect.width >= 2 * min_size;
+    let can_split_y = rect.height >= 2 * min_size;
+
+    // split across the longer side, so leaves stay roughly square
+    let split_x;
+    if can_split_x && can_split_y {
+        if rect.width == rect.height {
+            split_x = rng.gen::<bool>();
+        } else {
+            split_x = rect.width > rect.height;
+        }
+    } else if can_split_x || can_split_y {
+        split_x = can_split_x;
+    } else {
+        return BspNode::Leaf(rect);
+    }
+
+    let first;
+    let second;
+    if split_x {
+        let offset = rng.gen_range(min_size, rect.width - min_size + 1);
+        first = Rect::new(rect.x, rect.y, offset, rect.height);
+        second = Rect::new(rect.x + offset, rect.y, rect.width - offset, rect.height);
+    } else {
+        let offset = rng.gen_range(min_size, rect.height - min_size + 1);
+        first = Rect::new(rect.x, rect.y, rect.width, offset);
+        second = Rect::new(rect.x, rect.y + offset, rect.width, rect.height - offset);
+    }
+
+    return BspNode::Split(Box::new(split(first, config, rng)), Box::new(split(second, config, rng)));
+}
+
+/// Carve out the rooms in a node and connect them, returning a position
+/// within one of the node's rooms for connecting to its sibling.
+fn carve_node(node: &BspNode, map: &mut Map, config: &Config, rng: &mut SmallRng, rooms: &mut Vec<Rect>) -> Pos {
+    match node {
+        BspNode::Leaf(leaf) => {
+            // leave a tile of space around the room within the leaf
+            let width = room_size(leaf.width, config, rng);
+            let height = room_size(leaf.height, config, rng);
+            let x = rng.gen_range(leaf.x + 1, leaf.x + leaf.width - width);
+            let y = rng.gen_range(leaf.y + 1, leaf.y + leaf.height - height);
+            let room = Rect::new(x, y, width, height);
+
+            let thin_walls = rng.gen::<f32>() < config.bsp_thin_wall_chance;
+            carve_room(map, room, thin_walls);
+            rooms.push(room);
+
+            return room.center();
+        }
+
+        BspNode::Split(first, second) => {
+            let first_pos = carve_node(first, map, config, rng, rooms);
+            let second_pos = carve_node(second, map, config, rng, rooms);
+
+            carve_corridor(map, first_pos, second_pos, rng);
+
+            if rng.gen::<bool>() {
+                return first_pos;
+            } else {
+                return second_pos;
+            }
+        }
+    }
+}
+
+/// A room size that leaves a tile of space on either side within a leaf. The
+/// configured minimum is clamped so a room always fits in the leaf.
+fn room_size(leaf_size: i32, config: &Config, rng: &mut SmallRng) -> i32 {
+    let max_size = leaf_size - 1;
+    let min_size = std::cmp::max(1, std::cmp::min(config.bsp_min_room_size, max_size - 1));
+    return rng.gen_range(min_size, max_size);
+}
+
+fn carve_room(map: &mut Map, room: Rect, thin_walls: bool) {
+    if thin_walls {
+        // the space around the room is opened up, and the room is
+        // bounded by walls between its tiles and that space.
+        for x in (room.x - 1)..(room.x + room.width + 1) {
+            for y in (room.y - 1)..(room.y + room.height + 1) {
+                map[Pos::new(x, y)] = Tile::empty();
+            }
+        }
+
+        for y in room.y..(room.y + room.height) {
+            map[Pos::new(room.x, y)].left_wall = Wall::TallWall;
+            map[Pos::new(room.x + room.width, y)].left_wall = Wall::TallWall;
+        }
+
+        for x in room.x..(room.x + room.width) {
+            map[Pos::new(x, room.y - 1)].bottom_wall = Wall::TallWall;
+            map[Pos::new(x, room.y + room.height - 1)].bottom_wall = Wall::TallWall;
+        }
+    } else {
+        for x in room.x..(room.x + room.width) {
+            for y in room.y..(room.y + room.height) {
+                map[Pos::new(x, y)] = Tile::empty();
+            }
+        }
+    }
+}
+
+/// Carve an L shaped corridor, opening doorways in any walls along the way.
+fn carve_corridor(map: &mut Map, start: Pos, end: Pos, rng: &mut SmallRng) {
+    let horizontal_first = rng.gen::<bool>();
+
+    let mut pos = start;
+    while pos != end {
+        let move_horizontal;
+        if horizontal_first {
+            move_horizontal = pos.x != end.x;
+        } else {
+            move_horizontal = pos.y == end.y;
+        }
+
+        let next_pos;
+        if move_horizontal {
+            next_pos = move_x(pos, signedness(end.x - pos.x));
+        } else {
+            next_pos = move_y(pos, signedness(end.y - pos.y));
+        }
+
+        if map[next_pos].blocked {
+            map[next_pos] = Tile::empty();
+        }
+
+        if next_pos.x > pos.x {
+            map[next_pos].left_wall = Wall::Empty;
+        } else if next_pos.x < pos.x {
+            map[pos].left_wall = Wall::Empty;
+        } else if next_pos.y > pos.y {
+            map[pos].bottom_wall = Wall::Empty;
+        } else {
+            map[next_pos].bottom_wall = Wall::Empty;
+        }
+
+        pos = next_pos;
+    }
+}
+
+#[test]
+pub fn test_bsp() {
+    let mut config = Config::from_file("../config.yaml");
+
+    for seed in 0..5 {
+        let mut game = Game::new(seed, config.clone()).unwrap();
+        game.data.map = Map::from_dims(MAP_WIDTH as usize, MAP_HEIGHT as usize);
+
+        let player_pos = make_bsp(&mut game.data, &game.config, &mut game.msg_log, &mut game.rng);
+        assert_eq!(Ok(()), check_solvable(&game.data, player_pos));
+    }
+
+    let has_edge_walls = |map: &Map| {
+        map.tiles.iter().flatten().any(|tile| tile.left_wall != Wall::Empty || tile.bottom_wall != Wall::Empty)
+    };
+
+    // rooms can be bounded by thin walls
+    config.bsp_thin_wall_chance = 1.0;
+    let mut game = Game::new(0, config.clone()).unwrap();
+    game.data.map = Map::from_dims(MAP_WIDTH as usize, MAP_HEIGHT as usize);
+    let player_pos = make_bsp(&mut game.data, &game.config, &mut game.msg_log, &mut game.rng);
+    assert!(has_edge_walls(&game.data.map));
+    assert_eq!(Ok(()), check_solvable(&game.data, player_pos));
+
+    // or full wall tiles
+    config.bsp_thin_wall_chance = 0.0;
+    let mut game = Game::new(0, config.clone()).unwrap();
+    game.data.map = Map::from_dims(MAP_WIDTH as usize, MAP_HEIGHT as usize);
+    let player_pos = make_bsp(&mut game.data, &game.config, &mut game.msg_log, &mut game.rng);
+    assert!(!has_edge_walls(&game.data.map));
+    assert_eq!(Ok(()), check_solvable(&game.data, player_pos));
+
+    // rooms are shrunk to fit when the minimum room size doesn't fit in a leaf
+    config.bsp_min_leaf_size = 4;
+    config.bsp_min_room_size = 6;
+    let mut game = Game::new(0, config.clone()).unwrap();
+    game.data.map = Map::from_dims(MAP_WIDTH as usize, MAP_HEIGHT as usize);
+    let player_pos = make_bsp(&mut game.data, &game.config, &mut game.msg_log, &mut game.rng);
+    assert_eq!(Ok(()), check_solvable(&game.data, player_pos));
+}
@@ -0,0 +1,236 @@
+use std::collections::HashSet;
+
+use rand::prelude::*;
+
+use log::warn;
+
+use noise::{Perlin, NoiseFn, Seedable};
+
+use roguelike_core::types::*;
+use roguelike_core::map::*;
+use roguelike_core::config::*;
+use roguelike_core::messaging::MsgLog;
+use roguelike_core::utils::distance;
+
+use crate::generation::{make_key, make_island};
+use crate::solvability::reachable_positions;
+#[cfg(test)]
+use roguelike_core::constants::*;
+#[cfg(test)]
+use crate::game::Game;
+#[cfg(test)]
+use crate::solvability::check_solvable;
+
+
+/// Number of times to regenerate the cave if it comes out too small.
+const CAVE_ATTEMPTS: usize = 10;
+
+/// The fraction of the map the cave must cover to be kept.
+const CAVE_MIN_OPEN_FRACTION: f32 = 0.3;
+
+/// Generate a cave by smoothing random noise with a cellular automata,
+/// keeping only the largest open area. Patches of grass and rubble are
+/// laid down using Perlin noise so there is cover scattered through the cave.
+/// Returns the player's starting position.
+pub fn make_caves(data: &mut GameData,
+                  config: &Config,
+                  msg_log: &mut MsgLog,
+                  rng: &mut SmallRng) -> Pos {
+    let (width, height) = (data.map.width(), data.map.height());
+    let num_tiles = width * height;
+
+    for _ in 0..CAVE_ATTEMPTS {
+        random_fill(&mut data.map, config, rng);
+
+        for _ in 0..config.cave_smooth_iterations {
+            smooth(&mut data.map);
+        }
+
+        let cave = remove_pockets(&mut data.map);
+
+        // the start, key and exit each need their own tile
+        if cave.len() > 2 && cave.len() as f32 >= num_tiles as f32 * CAVE_MIN_OPEN_FRACTION {
+            place_surfaces(&mut data.map, config, rng);
+
+            // sort so the layout only depends on the rng, not on hashing order
+            let mut open: Vec<Pos> = cave.into_iter().collect();
+            open.sort_by_key(|pos| (pos.x, pos.y));
+
+            // the key is placed as far from the start as possible, and the exit anywhere else
+            let start = *open.choose(rng).unwrap();
+            let key_pos = *open.iter().max_by_key(|pos| distance(start, **pos)).unwrap();
+
+            let mut exit_pos = *open.choose(rng).unwrap();
+            while exit_pos == start || exit_pos == key_pos {
+                exit_pos = *open.choose(rng).unwrap();
+            }
+
+            data.map[exit_pos] = Tile::exit();
+            make_key(&mut data.entities, config, key_pos, msg_log);
+
+            data.map.update_map();
+
+            return start;
+        }
+    }
+
+    warn!("no cave large enough in {} attempts, making an island instead", CAVE_ATTEMPTS);
+    data.map = Map::from_dims(width as usize, height as usize);
+    return make_island(data, config, msg_log, rng);
+}
+
+/// Fill the map with walls at random, with a solid border around the edge.
+fn random_fill(map: &mut Map, config: &Config, rng: &mut SmallRng) {
+    for x in 0..map.width() {
+        for y in 0..map.height() {
+            let pos = Pos::new(x, y);
+            let border = x == 0 || y == 0 || x == map.width() - 1 || y == map.height() - 1;
+
+            if border || rng.gen::<f32>() < config.cave_fill_chance {
+                map[pos] = Tile::wall();
+            } else {
+                map[pos] = Tile::empty();
+            }
+        }
+    }
+}
+
+/// One step of the cellular automata- a tile becomes a wall when most of its
+/// neighbors are walls, and is opened up otherwise.
+fn smooth(map: &mut Map) {
+    let mut walls = Vec::new();
+
+    for x in 0..map.width() {
+        for y in 0..map.height() {
+            let pos = Pos::new(x, y);
+            walls.push((pos, wall_count(map, pos) >= 5));
+        }
+    }
+
+    for (pos, wall) in walls {
+        if wall {
+            map[pos] = Tile::wall();
+        } else {
+            map[pos] = Tile::empty();
+        }
+    }
+}
+
+/// Count the walls in the 3x3 square around a position, where anything
+/// outside of the map counts as a wall.
+fn wall_count(map: &Map, pos: Pos) -> usize {
+    let mut count = 0;
+
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let neighbor = Pos::new(pos.x + dx, pos.y + dy);
+            if !map.is_within_bounds(neighbor) || map[neighbor].blocked {
+                count += 1;
+            }
+        }
+    }
+
+    return count;
+}
+
+/// Fill in every open area except the largest, returning the positions left open.
+fn remove_pockets(map: &mut Map) -> HashSet<Pos> {
+    let mut regions: Vec<HashSet<Pos>> = Vec::new();
+
+    for x in 0..map.width() {
+        for y in 0..map.height() {
+            let pos = Pos::new(x, y);
+
+            if !map[pos].blocked && !regions.iter().any(|region| region.contains(&pos)) {
+                regions.push(reachable_positions(map, pos, false));
+            }
+        }
+    }
+
+    let largest =
+        regions.iter().enumerate()
+                      .max_by_key(|(_, region)| region.len())
+                      .map_or(0, |(index, _)| index);
+
+    let mut cave = HashSet::new();
+    for (index, region) in regions.into_iter().enumerate() {
+        if index == largest {
+            cave = region;
+        } else {
+            for pos in region {
+                map[pos] = Tile::wall();
+            }
+        }
+    }
+
+    return cave;
+}
+
+/// Lay down grass where the noise is high and rubble where it is low.
+fn place_surfaces(map: &mut Map, config: &Config, rng: &mut SmallRng) {
+    let perlin = Perlin::new().set_seed(rng.gen());
+
+    for x in 0..map.width() {
+        for y in 0..map.height() {
+            let pos = Pos::new(x, y);
+            if map[pos].blocked {
+                continue;
+            }
+
+            let value = perlin.get([x as f64 / config.cave_noise_scaler,
+                                    y as f64 / config.cave_noise_scaler]);
+
+            if value > config.cave_grass_threshold {
+                map[pos].surface = Surface::Grass;
+            } else if value < config.cave_rubble_threshold {
+                map[pos].surface = Surface::Rubble;
+            }
+        }
+    }
+}
+
+#[test]
+pub fn test_caves() {
+    let mut config = Config::from_file("../config.yaml");
+
+    let mut grass = 0;
+    let mut rubble = 0;
+    for seed in 0..5 {
+        let mut game = Game::new(seed, config.clone()).unwrap();
+        game.data.map = Map::from_dims(MAP_WIDTH as usize, MAP_HEIGHT as usize);
+
+        let player_pos = make_caves(&mut game.data, &game.config, &mut game.msg_log, &mut game.rng);
+        assert_eq!(Ok(()), check_solvable(&game.data, player_pos));
+
+        // there are no pockets left that can't be reached from the start
+        let reachable = reachable_positions(&game.data.map, player_pos, false);
+        let num_open = game.data.map.tiles.iter().flatten().filter(|tile| !tile.blocked).count();
+        assert_eq!(num_open, reachable.len());
+
+        grass += game.data.map.tiles.iter().flatten().filter(|tile| tile.surface == Surface::Grass).count();
+        rubble += game.data.map.tiles.iter().flatten().filter(|tile| tile.surface == Surface::Rubble).count();
+    }
+
+    assert!(grass > 0);
+    assert!(rubble > 0);
+
+    // the same seed produces the same cave
+    let mut first = Game::new(1, config.clone()).unwrap();
+    first.data.map = Map::from_dims(MAP_WIDTH as usize, MAP_HEIGHT as usize);
+    make_caves(&mut first.data, &first.config, &mut first.msg_log, &mut first.rng);
+
+    let mut second = Game::new(1, config.clone()).unwrap();
+    second.data.map = Map::from_dims(MAP_WIDTH as usize, MAP_HEIGHT as usize);
+    make_caves(&mut second.data, &second.config, &mut second.msg_log, &mut second.rng);
+
+    assert!(first.data.map.tiles.iter().flatten().zip(second.data.map.tiles.iter().flatten())
+                 .all(|(first_tile, second_tile)| first_tile.blocked == second_tile.blocked &&
+                                                  first_tile.surface == second_tile.surface));
+
+    // a cave that fills in completely is replaced by an island
+    config.cave_fill_chance = 1.0;
+    let mut game = Game::new(0, config.clone()).unwrap();
+    game.data.map = Map::from_dims(MAP_WIDTH as usize, MAP_HEIGHT as usize);
+    let player_pos = make_caves(&mut game.data, &game.config, &mut game.msg_log, &mut game.rng);
+    assert_eq!(Ok(()), check_solvable(&game.data, player_pos));
+}
@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use rand::prelude::*;
 
 use serde::{Serialize, Deserialize};
@@ -10,18 +12,20 @@ use roguelike_core::ai::*;
 use roguelike_core::map::*;
 use roguelike_core::messaging::{Msg, MsgLog};
 use roguelike_core::movement::{Action, Reach};
-use roguelike_core::utils::{move_towards, distance};
+use roguelike_core::utils::{move_towards, distance, add_pos};
 #[cfg(test)]
 use roguelike_core::movement::*;
+#[cfg(test)]
+use roguelike_core::utils::{push_attack, attack};
+#[cfg(test)]
+use roguelike_core::constants::{ELEVATION_ATTACK_BONUS, ELEVATION_FOV_BONUS};
 
 
 use crate::actions;
 use crate::actions::{InputAction, KeyDirection};
 use crate::generation::*;
-use crate::make_map::read_map_xp;
+use crate::make_map::{make_map, MapCache};
 use crate::resolve::resolve_messages;
-#[cfg(test)]
-use crate::make_map::*;
 
 
 #[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
@@ -33,7 +37,7 @@ pub enum GameResult {
 #[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum SelectionAction {
     Throw,
-    Hammer,
+    UseItem,
     Interact,
     PlaceTrap,
 }
@@ -50,8 +54,11 @@ impl SelectionAction {
                 action = Action::ThrowItem(pos, *item_id);
             }
 
-            SelectionAction::Hammer => {
-                action = Action::UseItem(pos);
+            SelectionAction::UseItem => {
+                let player_id = data.find_player().unwrap();
+                let item_id =
+                    data.entities.selected_item.get(&player_id).expect("Using an item, but nothing selected!");
+                action = Action::UseItem(*item_id, pos);
             }
 
             SelectionAction::Interact => {
@@ -64,6 +71,12 @@ impl SelectionAction {
                         action = Action::ArmDisarmTrap(entity_id);
                     }
                 }
+
+                // raised ground next to the player can be climbed onto
+                let player_id = data.find_player().unwrap();
+                if action == Action::NoAction && data.map.can_climb(data.entities.pos[&player_id], pos) {
+                    action = Action::Climb(pos);
+                }
             }
 
             SelectionAction::PlaceTrap => {
@@ -158,8 +171,8 @@ impl Selection {
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct GameSettings {
     pub turn_count: usize,
+    pub depth: usize,
     pub god_mode: bool,
-    pub map_type: MapGenType,
     pub exiting: bool,
     pub state: GameState,
     pub draw_selection_overlay: bool,
@@ -175,8 +188,8 @@ impl GameSettings {
                god_mode: bool) -> GameSettings {
         return GameSettings {
             turn_count,
+            depth: 0,
             god_mode,
-            map_type: MapGenType::Island,
             exiting: false,
             state: GameState::Playing,
             draw_selection_overlay: false,
@@ -198,6 +211,7 @@ pub struct Game {
     pub settings: GameSettings,
     pub msg_log: MsgLog,
     pub rng: SmallRng,
+    pub map_cache: MapCache,
 }
 
 impl Game {
@@ -215,7 +229,7 @@ impl Game {
         data.entities.pos[&player_id] = Pos::new(-1, -1);
 
         let stone_id = make_stone(&mut data.entities, &config, Pos::new(-1, -1), &mut msg_log);
-        data.entities.inventory[&player_id].push_back(stone_id);
+        actions::pick_item_up(player_id, stone_id, &mut data.entities);
 
         let state = Game {
             config,
@@ -226,6 +240,7 @@ impl Game {
             msg_log,
             //key_input: Vec::new(),
             rng: rng,
+            map_cache: MapCache::default(),
         };
 
         return Ok(state);
@@ -264,10 +279,26 @@ impl Game {
         }
 
         self.msg_log.log(Msg::ChangeLevel());
+        self.settings.depth += 1;
+
+        // the player keeps their health and inventory on the next level,
+        // but the key stays behind with the level it was for
+        let player_id = self.data.find_player().unwrap();
+        let keys: Vec<EntityId> =
+            self.data.entities.inventory[&player_id].iter()
+                                                    .filter(|id| self.data.entities.item.get(*id) == Some(&Item::Goal))
+                                                    .map(|id| *id)
+                                                    .collect();
+        for key in keys {
+            self.data.entities.remove_item(player_id, key);
+        }
 
-        self.data.entities.clear();
-        let _player_pos =
-            read_map_xp(&self.config, &mut self.data, &mut self.msg_log, "resources/map.xp");
+        for id in self.data.level_entities(player_id) {
+            self.data.remove_entity(id);
+        }
+
+        let map_config = self.config.map_next_level.clone();
+        make_map(&map_config, self);
 
         self.settings.state = GameState::Playing;
 
@@ -287,7 +318,17 @@ impl Game {
         let input = self.input_action;
         self.input_action = InputAction::None;
 
-        actions::handle_input_inventory(input, &mut self.data, &mut self.settings, &mut self.msg_log);
+        let player_action =
+            actions::handle_input_inventory(input, &mut self.data, &mut self.settings, &self.config, &mut self.msg_log);
+
+        if player_action != Action::NoAction {
+            step_logic(player_action,
+                       &mut self.data,
+                       &mut self.settings,
+                       &self.config,
+                       &mut self.msg_log,
+                       &mut self.rng);
+        }
 
         if self.settings.exiting {
             return GameResult::Stop;
@@ -314,7 +355,8 @@ impl Game {
                        &mut self.data,
                        &mut self.settings,
                        &self.config,
-                       &mut self.msg_log);
+                       &mut self.msg_log,
+                       &mut self.rng);
         }
 
         if self.settings.exiting {
@@ -361,7 +403,8 @@ impl Game {
                        &mut self.data,
                        &mut self.settings,
                        &self.config,
-                       &mut self.msg_log);
+                       &mut self.msg_log,
+                       &mut self.rng);
 
             if win_condition_met(&self.data) {
                 self.settings.state = GameState::Win;
@@ -402,7 +445,8 @@ pub fn step_logic(player_action: Action,
                   data: &mut GameData, 
                   settings: &mut GameSettings,
                   config: &Config,
-                  msg_log: &mut MsgLog) {
+                  msg_log: &mut MsgLog,
+                  rng: &mut SmallRng) {
     msg_log.clear();
 
     let player_id = data.find_player().unwrap();
@@ -458,6 +502,62 @@ pub fn step_logic(player_action: Action,
         }
     }
 
+    /* Timed Triggers */
+    let mut timers_fired: Vec<EntityId> = Vec::new();
+    for (key, trigger) in data.entities.trigger.iter_mut() {
+        if data.entities.armed.get(key) != Some(&true) {
+            continue;
+        }
+
+        if let Trigger::Timer(period, remaining) = trigger {
+            if *remaining <= 1 {
+                *remaining = *period;
+                timers_fired.push(*key);
+            } else {
+                *remaining -= 1;
+            }
+        }
+    }
+
+    if timers_fired.len() > 0 {
+        // a timer is considered the cause of its own traps going off
+        for key in timers_fired {
+            msg_log.log(Msg::Triggered(key, key));
+        }
+        resolve_messages(data, msg_log, settings, config);
+    }
+
+    /* Fire */
+    // fire spreads to neighboring grass by chance, and burns anyone standing in it
+    let fires: Vec<(EntityId, Pos)> =
+        data.entities.name.iter()
+                          .filter(|(_, name)| **name == EntityName::Fire)
+                          .map(|(key, _)| (*key, data.entities.pos[key]))
+                          .collect();
+
+    for (fire_id, fire_pos) in fires.iter() {
+        for direction in Direction::move_actions() {
+            let (dx, dy) = direction.into_move();
+            let next_pos = add_pos(*fire_pos, Pos::new(dx, dy));
+
+            if data.map.is_within_bounds(next_pos) &&
+               data.map[next_pos].surface == Surface::Grass &&
+               rng.gen::<f32>() < config.fire_spread_chance {
+                msg_log.log(Msg::Ignite(*fire_id, next_pos));
+            }
+        }
+
+        for (key, pos) in data.entities.pos.iter() {
+            if pos == fire_pos && data.entities.alive[key] && data.entities.fighter.get(key).is_some() {
+                msg_log.log(Msg::Burned(*fire_id, *key, config.fire_damage));
+            }
+        }
+    }
+
+    if fires.len() > 0 {
+        resolve_messages(data, msg_log, settings, config);
+    }
+
     // TODO this shouldn't be necessary- it should be part of msg handling
     // check if player lost all hp
     if let Some(fighter) = data.entities.fighter.get(&player_id) {
@@ -497,6 +597,22 @@ pub fn step_logic(player_action: Action,
         data.entities.remove(&key);
     }
 
+    /* Smoke */
+    // smoke blocks sight while it lasts, so the map keeps track of it for
+    // every later update_map, and transparency is refreshed when it changes.
+    let smoke: HashSet<Pos> =
+        data.entities.name.iter()
+                          .filter(|(_, name)| **name == EntityName::Smoke)
+                          .map(|(key, _)| data.entities.pos[key])
+                          .collect();
+    if smoke != data.map.smoke {
+        data.map.smoke = smoke;
+        data.map.update_map();
+
+        let player_pos = data.entities.pos[&player_id];
+        data.map.compute_fov(player_pos, config.fov_radius_player);
+    }
+
     /* Recompute FOV */
     let player_pos = data.entities.pos[&player_id];
     if previous_player_position != player_pos {
@@ -504,14 +620,26 @@ pub fn step_logic(player_action: Action,
     }
 }
 
+/// A game on an empty map of the given size, with the player placed at player_pos.
+#[cfg(test)]
+pub fn test_game(config: &Config, width: usize, height: usize, player_pos: Pos) -> (Game, EntityId) {
+    let mut game = Game::new(0, config.clone()).unwrap();
+
+    let player_id = game.data.find_player().unwrap();
+    game.data.map = Map::from_dims(width, height);
+    game.data.entities.pos[&player_id] = player_pos;
+
+    return (game, player_id);
+}
+
 #[test]
 pub fn test_game_step() {
     let mut config = Config::from_file("../config.yaml");
-    config.map_load = MapLoadConfig::Empty;
+    config.map_load = MapConfig::new(MapSource::Empty);
     let mut game = Game::new(0, config.clone()).unwrap();
 
     let player_id = game.data.find_player().unwrap();
-    make_map(&MapLoadConfig::Empty, &mut game);
+    make_map(&MapConfig::new(MapSource::Empty), &mut game);
     let player_pos = game.data.entities.pos[&player_id];
     assert_eq!(Pos::new(0, 0), player_pos);
 
@@ -536,6 +664,844 @@ pub fn test_game_step() {
     assert_eq!(Pos::new(0, 0), player_pos);
 }
 
+#[test]
+pub fn test_win_keeps_player() {
+    let mut config = Config::from_file("../config.yaml");
+    config.map_next_level = MapConfig::new(MapSource::Empty);
+    let (mut game, player_id) = test_game(&config, 10, 10, Pos::new(4, 4));
+
+    let hammer = make_hammer(&mut game.data.entities, &game.config, Pos::new(4, 4), &mut game.msg_log);
+    actions::pick_item_up(player_id, hammer, &mut game.data.entities);
+    let key = make_key(&mut game.data.entities, &game.config, Pos::new(4, 4), &mut game.msg_log);
+    actions::pick_item_up(player_id, key, &mut game.data.entities);
+    let gol = make_gol(&mut game.data.entities, &game.config, Pos::new(7, 7), &mut game.msg_log);
+    game.data.entities.fighter[&player_id].hp -= 1;
+    let hp = game.data.entities.fighter[&player_id].hp;
+
+    game.settings.state = GameState::Win;
+    game.step_game(0.1);
+
+    // the player moves on with everything but the key, and the level's monsters are gone
+    assert_eq!(Some(player_id), game.data.find_player());
+    assert_eq!(hp, game.data.entities.fighter[&player_id].hp);
+    assert!(game.data.entities.inventory[&player_id].contains(&hammer));
+    assert!(!game.data.entities.inventory[&player_id].contains(&key));
+    assert!(!game.data.entities.ids.contains(&key));
+    assert!(!game.data.entities.ids.contains(&gol));
+}
+
+#[test]
+pub fn test_running_momentum() {
+    let mut config = Config::from_file("../config.yaml");
+    config.map_load = MapConfig::new(MapSource::Empty);
+    let mut game = Game::new(0, config.clone()).unwrap();
+
+    let player_id = game.data.find_player().unwrap();
+    make_map(&MapConfig::new(MapSource::Empty), &mut game);
+
+    let mut test_move = |game: &mut Game, dir, pos| {
+        game.input_action = InputAction::Move(dir);
+        game.step_game(0.1);
+        let player_pos = game.data.entities.pos[&player_id];
+        assert_eq!(Pos::from(pos), player_pos);
+    };
+
+    game.input_action = InputAction::IncreaseMoveMode;
+    game.step_game(0.1);
+
+    // build up speed, then cover an extra tile at full speed
+    test_move(&mut game, Direction::Right, (2, 0));
+    test_move(&mut game, Direction::Right, (4, 0));
+    assert!(game.data.entities.momentum[&player_id].at_maximum());
+    test_move(&mut game, Direction::Right, (7, 0));
+
+    // a hard turn loses the built up speed
+    test_move(&mut game, Direction::Down, (7, 2));
+    assert_eq!(0, game.data.entities.momentum[&player_id].mx);
+    assert_eq!(1, game.data.entities.momentum[&player_id].my);
+
+    // running into a monster at full speed knocks it back
+    let pawn = make_elf(&mut game.data.entities, &game.config, Pos::new(7, 7), &mut game.msg_log);
+    game.data.entities.ai.remove(&pawn);
+    test_move(&mut game, Direction::Down, (7, 4));
+    test_move(&mut game, Direction::Down, (7, 7));
+    assert_eq!(Pos::new(7, 9), game.data.entities.pos[&pawn]);
+}
+
+#[test]
+pub fn test_wall_kick() {
+    let mut config = Config::from_file("../config.yaml");
+    config.map_load = MapConfig::new(MapSource::Empty);
+    let mut game = Game::new(0, config.clone()).unwrap();
+
+    let player_id = game.data.find_player().unwrap();
+    make_map(&MapConfig::new(MapSource::Empty), &mut game);
+
+    // a line of wall tiles to kick off of
+    for y in 0..10 {
+        game.data.map[(5, y)].blocked = true;
+        game.data.map[(5, y)].tile_type = TileType::Wall;
+    }
+    game.data.entities.pos[&player_id] = Pos::new(4, 4);
+
+    let mut test_move = |game: &mut Game, dir, pos| {
+        game.input_action = InputAction::Move(dir);
+        game.step_game(0.1);
+        let player_pos = game.data.entities.pos[&player_id];
+        assert_eq!(Pos::from(pos), player_pos);
+    };
+
+    let mut input = |game: &mut Game, action| {
+        game.input_action = action;
+        game.step_game(0.1);
+    };
+
+    // walking into the wall does not kick off of it
+    test_move(&mut game, Direction::DownRight, (4, 4));
+
+    // running into the wall at an angle rebounds off of it
+    input(&mut game, InputAction::IncreaseMoveMode);
+    test_move(&mut game, Direction::DownRight, (2, 6));
+
+    assert!(game.msg_log.turn_messages.iter().any(|msg| {
+        matches!(msg, Msg::WallKick(id, _) if *id == player_id)
+    }));
+
+    assert!(game.msg_log.turn_messages.iter().any(|msg| {
+        matches!(msg, Msg::Sound(_, _, radius, _) if *radius == config.sound_radius_wall_kick)
+    }));
+
+    // momentum is redirected by the kick
+    assert_eq!(-1, game.data.entities.momentum[&player_id].mx);
+    assert_eq!(1, game.data.entities.momentum[&player_id].my);
+
+    // running straight into the wall does not kick
+    test_move(&mut game, Direction::Right, (4, 6));
+    test_move(&mut game, Direction::Right, (4, 6));
+
+    // a monster at the landing tile is knocked back by the kick
+    let pawn = make_elf(&mut game.data.entities, &game.config, Pos::new(3, 5), &mut game.msg_log);
+    game.data.entities.ai.remove(&pawn);
+    test_move(&mut game, Direction::UpRight, (3, 5));
+    assert_eq!(Pos::new(2, 4), game.data.entities.pos[&pawn]);
+}
+
+#[test]
+pub fn test_equipment_slots() {
+    let mut config = Config::from_file("../config.yaml");
+    config.map_load = MapConfig::new(MapSource::Empty);
+    let mut game = Game::new(0, config.clone()).unwrap();
+
+    let player_id = game.data.find_player().unwrap();
+    make_map(&MapConfig::new(MapSource::Empty), &mut game);
+    let player_pos = game.data.entities.pos[&player_id];
+
+    // the starting stone goes on the belt
+    let stone = game.data.entities.inventory[&player_id][0];
+    assert_eq!(Some(stone), game.data.entities.equipped(player_id, Slot::Belt));
+
+    let dagger = make_dagger(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    let hammer = make_hammer(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    let shield = make_shield(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+
+    // the main hand is taken, so the hammer goes in the pack
+    actions::pick_item_up(player_id, dagger, &mut game.data.entities);
+    actions::pick_item_up(player_id, hammer, &mut game.data.entities);
+    actions::pick_item_up(player_id, shield, &mut game.data.entities);
+    assert_eq!(Some(dagger), game.data.entities.equipped(player_id, Slot::MainHand));
+    assert_eq!(Some(hammer), game.data.entities.equipped(player_id, Slot::Pack));
+    assert_eq!(Some(shield), game.data.entities.equipped(player_id, Slot::OffHand));
+    assert!(game.data.using(player_id, Item::Dagger));
+    assert!(game.data.using(player_id, Item::Shield));
+
+    // can't run while holding a shield
+    game.input_action = InputAction::IncreaseMoveMode;
+    game.step_game(0.1);
+    assert_eq!(MoveMode::Walk, game.data.entities.move_mode[&player_id]);
+    assert!(game.msg_log.turn_messages.iter().any(|msg| matches!(msg, Msg::TriedRunWithShield)));
+
+    game.input_action = InputAction::SwapPrimaryItem;
+    game.step_game(0.1);
+    assert!(game.data.using(player_id, Item::Hammer));
+    assert!(!game.data.using(player_id, Item::Dagger));
+    assert_eq!(Some(dagger), game.data.entities.equipped(player_id, Slot::Pack));
+
+    // dropping removes the held item from its slot
+    game.input_action = InputAction::DropItem;
+    game.step_game(0.1);
+    assert!(!game.data.using(player_id, Item::Hammer));
+    assert!(!game.data.entities.inventory[&player_id].contains(&hammer));
+    assert_eq!(player_pos, game.data.entities.pos[&hammer]);
+}
+
+#[test]
+pub fn test_item_durability() {
+    let mut config = Config::from_file("../config.yaml");
+    config.hammer_durability = 3;
+    config.wear_hammer_wall = 2;
+    let player_pos = Pos::new(4, 4);
+    let (mut game, player_id) = test_game(&config, 10, 10, player_pos);
+
+    let hammer = make_hammer(&mut game.data.entities, &game.config, Pos::new(4, 7), &mut game.msg_log);
+    actions::pick_item_up(player_id, hammer, &mut game.data.entities);
+
+    let wall_pos = Pos::new(4, 5);
+    let mut hit_wall = |game: &mut Game| {
+        game.data.map[player_pos].bottom_wall = Wall::ShortWall;
+
+        game.input_action = InputAction::UseItem;
+        game.step_game(0.1);
+
+        game.input_action = InputAction::MapClick(wall_pos, wall_pos);
+        game.step_game(0.1);
+    };
+
+    // breaking a wall wears the hammer down
+    hit_wall(&mut game);
+    assert_eq!(1, game.data.entities.durability[&hammer].current);
+    assert!(game.data.using(player_id, Item::Hammer));
+
+    // the next wall breaks the hammer, leaving debris behind
+    hit_wall(&mut game);
+    assert!(game.msg_log.turn_messages.iter().any(|msg| {
+        matches!(msg, Msg::ItemBroke(id, item) if *id == player_id && *item == hammer)
+    }));
+    assert!(!game.data.entities.ids.contains(&hammer));
+    assert!(!game.data.using(player_id, Item::Hammer));
+    assert_eq!(Surface::Rubble, game.data.map[player_pos].surface);
+}
+
+#[test]
+pub fn test_use_consumables() {
+    let config = Config::from_file("../config.yaml");
+    let player_pos = Pos::new(1, 4);
+    let (mut game, player_id) = test_game(&config, 10, 10, player_pos);
+
+    // a healing draught is used up on its holder from the inventory
+    let draught = make_healing_draught(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    actions::pick_item_up(player_id, draught, &mut game.data.entities);
+    let max_hp = game.data.entities.fighter[&player_id].max_hp;
+    game.data.entities.fighter[&player_id].hp = max_hp - 1;
+
+    let draught_index = game.data.entities.inventory[&player_id].iter().position(|item| *item == draught).unwrap();
+    game.settings.state = GameState::Inventory;
+    game.input_action = InputAction::SelectItem(draught_index);
+    game.step_game(0.1);
+
+    assert_eq!(max_hp, game.data.entities.fighter[&player_id].hp);
+    assert_eq!(GameState::Playing, game.settings.state);
+    assert!(!game.data.entities.ids.contains(&draught));
+
+    // a smoke bomb is targeted, and its smoke blocks sight until it clears
+    let smoke_bomb = make_smoke_bomb(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    actions::pick_item_up(player_id, smoke_bomb, &mut game.data.entities);
+
+    let bomb_index = game.data.entities.inventory[&player_id].iter().position(|item| *item == smoke_bomb).unwrap();
+    game.settings.state = GameState::Inventory;
+    game.input_action = InputAction::SelectItem(bomb_index);
+    game.step_game(0.1);
+    assert_eq!(GameState::Selection, game.settings.state);
+
+    let target_pos = Pos::new(4, 4);
+    game.input_action = InputAction::MapClick(target_pos, target_pos);
+    game.step_game(0.1);
+
+    assert!(!game.data.entities.ids.contains(&smoke_bomb));
+    assert!(game.data.entities.ids.iter().any(|id| {
+        game.data.entities.name[id] == EntityName::Smoke && game.data.entities.pos[id] == target_pos
+    }));
+    let behind_smoke = Pos::new(7, 4);
+    assert!(!game.data.map.is_in_fov(player_pos, behind_smoke, game.config.fov_radius_player));
+
+    // other changes to the map don't clear the smoke
+    game.data.map.update_map();
+    assert!(!game.data.map.is_in_fov(player_pos, behind_smoke, game.config.fov_radius_player));
+
+    for _ in 0..(game.config.smoke_turns + 1) {
+        game.input_action = InputAction::Pass;
+        game.step_game(0.1);
+    }
+    assert!(!game.data.entities.name.values().any(|name| *name == EntityName::Smoke));
+    assert!(game.data.map.is_in_fov(player_pos, behind_smoke, game.config.fov_radius_player));
+}
+
+#[test]
+pub fn test_throw_items() {
+    let config = Config::from_file("../config.yaml");
+    let player_pos = Pos::new(1, 1);
+    let (mut game, player_id) = test_game(&config, 10, 10, player_pos);
+
+    // drop the starting stone so thrown stones are not stacked onto it
+    let start_stone = game.data.entities.inventory[&player_id][0];
+    game.data.entities.remove_item(player_id, start_stone);
+
+    let gol = make_gol(&mut game.data.entities, &game.config, Pos::new(4, 1), &mut game.msg_log);
+
+    let throw = |game: &mut Game, item_id: EntityId, start: Pos, end: Pos| -> Pos {
+        game.data.entities.pos[&player_id] = start;
+        actions::pick_item_up(player_id, item_id, &mut game.data.entities);
+        game.msg_log.log(Msg::ItemThrow(player_id, item_id, start, end));
+        resolve_messages(&mut game.data, &mut game.msg_log, &mut game.settings, &game.config);
+        return game.data.entities.pos[&item_id];
+    };
+
+    // a stone stops at the entity it hits and stuns it
+    let stone = make_stone(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    assert_eq!(Pos::new(3, 1), throw(&mut game, stone, player_pos, Pos::new(5, 1)));
+    assert_eq!(Some(&game.config.stun_turns_stone), game.data.entities.stunned.get(&gol));
+    assert!(game.msg_log.turn_messages.iter().any(|msg| {
+        *msg == Msg::Sound(player_id, Pos::new(3, 1), game.config.sound_radius_stone, false)
+    }));
+    assert_eq!(Action::Pass, ai_take_turn(gol, &mut game.data, &game.config, &mut game.msg_log));
+
+    // a dagger damages the entity it hits
+    let gol_hp = game.data.entities.fighter[&gol].hp;
+    let dagger = make_dagger(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    throw(&mut game, dagger, player_pos, Pos::new(5, 1));
+    assert_eq!(gol_hp - game.config.throw_damage_dagger, game.data.entities.fighter[&gol].hp);
+
+    // items bounce back off of tall walls
+    game.data.map[(4, 5)].left_wall = Wall::TallWall;
+    let stone = make_stone(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    assert_eq!(Pos::new(2, 5), throw(&mut game, stone, Pos::new(1, 5), Pos::new(5, 5)));
+
+    // items fly over water, but fall short rather than landing in it
+    game.data.map[(3, 7)] = Tile::water();
+    game.data.map[(4, 7)] = Tile::water();
+    let stone = make_stone(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    assert_eq!(Pos::new(5, 7), throw(&mut game, stone, Pos::new(1, 7), Pos::new(5, 7)));
+    let stone = make_stone(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    assert_eq!(Pos::new(2, 7), throw(&mut game, stone, Pos::new(1, 7), Pos::new(4, 7)));
+
+    // heavy items can't be thrown far
+    let hammer = make_hammer(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    let range = game.config.throw_strength / Item::Hammer.weight();
+    assert_eq!(Pos::new(1 + range as i32, 8), throw(&mut game, hammer, Pos::new(1, 8), Pos::new(6, 8)));
+}
+
+#[test]
+pub fn test_inventory_capacity() {
+    let mut config = Config::from_file("../config.yaml");
+    config.player_max_weight = 6;
+    config.player_max_items = 5;
+    config.player_encumbered_weight = 4;
+    let player_pos = Pos::new(4, 4);
+    let (mut game, player_id) = test_game(&config, 10, 10, player_pos);
+    game.data.entities.move_mode[&player_id] = MoveMode::Run;
+
+    // the player starts with a stone
+    assert_eq!(1, game.data.entities.carried_weight(player_id));
+
+    let sword = make_sword(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    game.input_action = InputAction::Pickup;
+    game.step_game(0.1);
+    let dagger = make_dagger(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    game.input_action = InputAction::Pickup;
+    game.step_game(0.1);
+    assert!(game.data.entities.inventory[&player_id].contains(&sword));
+    assert!(game.data.entities.inventory[&player_id].contains(&dagger));
+    assert!(!game.data.entities.encumbered(player_id));
+    assert_eq!(MoveMode::Run, game.data.entities.move_mode[&player_id]);
+
+    // going over the encumbered weight slows the player to a walk
+    make_stone(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    game.input_action = InputAction::Pickup;
+    game.step_game(0.1);
+    assert_eq!(5, game.data.entities.carried_weight(player_id));
+    assert!(game.data.entities.encumbered(player_id));
+    assert_eq!(MoveMode::Walk, game.data.entities.move_mode[&player_id]);
+
+    game.input_action = InputAction::IncreaseMoveMode;
+    game.step_game(0.1);
+    assert!(game.msg_log.turn_messages.iter().any(|msg| matches!(msg, Msg::TriedRunEncumbered)));
+    assert_eq!(MoveMode::Walk, game.data.entities.move_mode[&player_id]);
+
+    // a full inventory can't pick up any more
+    let shield = make_shield(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    game.input_action = InputAction::Pickup;
+    game.step_game(0.1);
+    assert!(!game.data.entities.inventory[&player_id].contains(&shield));
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::InventoryFull(player_id, shield)));
+    assert_eq!(player_pos, game.data.entities.pos[&shield]);
+
+    // carrying too much makes moving louder
+    game.input_action = InputAction::Move(Direction::Right);
+    game.step_game(0.1);
+    let move_pos = Pos::new(5, 4);
+    let sound_radius = game.config.sound_radius_walk + game.config.sound_radius_encumbered;
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::Sound(player_id, move_pos, sound_radius, true)));
+}
+
+#[test]
+pub fn test_stack_items() {
+    let config = Config::from_file("../config.yaml");
+    let player_pos = Pos::new(4, 4);
+    let (mut game, player_id) = test_game(&config, 10, 10, player_pos);
+
+    // the player starts with a single stone
+    let stack = game.data.entities.inventory[&player_id][0];
+    assert_eq!(Item::Stone, game.data.entities.item[&stack]);
+    assert_eq!(1, game.data.entities.stack_count(stack));
+
+    // picking up more stones adds to the stack
+    for _ in 0..2 {
+        make_stone(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+        game.input_action = InputAction::Pickup;
+        game.step_game(0.1);
+    }
+    assert_eq!(1, game.data.entities.inventory[&player_id].len());
+    assert_eq!(3, game.data.entities.stack_count(stack));
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::StackChanged(stack, 3)));
+    assert_eq!(3 * Item::Stone.weight(), game.data.entities.carried_weight(player_id));
+
+    // throwing a stone splits one off of the stack
+    let target_pos = Pos::new(4, 6);
+    game.data.entities.selected_item.insert(player_id, stack);
+    game.settings.state = GameState::Selection;
+    game.settings.selection = Selection::new(SelectionType::WithinRadius(5), SelectionAction::Throw);
+    game.input_action = InputAction::MapClick(target_pos, target_pos);
+    game.step_game(0.1);
+
+    assert_eq!(2, game.data.entities.stack_count(stack));
+    assert!(game.data.entities.inventory[&player_id].contains(&stack));
+    let thrown = game.data.entities.ids.iter().map(|id| *id).find(|id| {
+        game.data.entities.item.get(id) == Some(&Item::Stone) && game.data.entities.pos[id] == target_pos
+    });
+    assert!(thrown.is_some());
+    assert_ne!(Some(stack), thrown);
+
+    // items in a different state are kept in their own slots
+    let armed = make_spikes(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    let disarmed = make_spikes(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    game.data.entities.armed[&disarmed] = false;
+    actions::pick_item_up(player_id, armed, &mut game.data.entities);
+    assert_eq!(None, game.data.entities.find_stack(player_id, disarmed));
+    assert_eq!(disarmed, actions::pick_item_up(player_id, disarmed, &mut game.data.entities));
+    assert_eq!(1, game.data.entities.stack_count(armed));
+
+    // an item split off of a stack keeps the stack's state
+    let worn = Durability { current: 1, max: 3 };
+    let door = make_door(&mut game.data.entities, &game.config, Pos::new(0, 0), &mut game.msg_log);
+    let worn_stack = make_spikes(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    let worn_spikes = make_spikes(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    for spikes in vec!(worn_stack, worn_spikes) {
+        game.data.entities.durability.insert(spikes, worn);
+        game.data.entities.link(spikes, door);
+    }
+    actions::pick_item_up(player_id, worn_stack, &mut game.data.entities);
+    assert_eq!(worn_stack, actions::pick_item_up(player_id, worn_spikes, &mut game.data.entities));
+
+    let split = game.data.entities.split_stack(worn_stack);
+    assert_ne!(worn_stack, split);
+    assert_eq!(Some(&worn), game.data.entities.durability.get(&split));
+    assert_eq!(vec!(door), game.data.entities.trigger_effects(split));
+}
+
+#[test]
+pub fn test_monster_items() {
+    let config = Config::from_file("../config.yaml");
+    let (mut game, player_id) = test_game(&config, 10, 10, Pos::new(9, 9));
+
+    // an idle gol picks up a stone it is standing on
+    let gol_pos = Pos::new(1, 1);
+    let gol = make_gol(&mut game.data.entities, &game.config, gol_pos, &mut game.msg_log);
+    game.data.entities.direction[&gol] = Direction::Up;
+    let stone = make_stone(&mut game.data.entities, &game.config, gol_pos, &mut game.msg_log);
+
+    let action = ai_take_turn(gol, &mut game.data, &game.config, &mut game.msg_log);
+    assert_eq!(Action::Pickup(stone), action);
+    game.msg_log.log(Msg::Action(gol, action));
+    resolve_messages(&mut game.data, &mut game.msg_log, &mut game.settings, &game.config);
+    assert!(game.data.entities.inventory[&gol].contains(&stone));
+
+    // when it can't reach the player, it throws the stone instead
+    let player_pos = Pos::new(4, 1);
+    game.data.entities.pos[&player_id] = player_pos;
+    game.data.entities.behavior[&gol] = Behavior::Attacking(player_id);
+    let action = ai_take_turn(gol, &mut game.data, &game.config, &mut game.msg_log);
+    assert_eq!(Action::ThrowItem(player_pos, stone), action);
+
+    // everything a monster carries is dropped when it dies
+    let dagger = make_dagger(&mut game.data.entities, &game.config, gol_pos, &mut game.msg_log);
+    actions::pick_item_up(gol, dagger, &mut game.data.entities);
+    game.msg_log.log(Msg::Killed(player_id, gol, 10));
+    resolve_messages(&mut game.data, &mut game.msg_log, &mut game.settings, &game.config);
+
+    assert!(game.data.entities.inventory[&gol].is_empty());
+    assert_eq!(gol_pos, game.data.entities.pos[&stone]);
+    assert_eq!(gol_pos, game.data.entities.pos[&dagger]);
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::ItemDropped(gol, dagger)));
+}
+
+#[test]
+pub fn test_triggers() {
+    let config = Config::from_file("../config.yaml");
+    let (mut game, player_id) = test_game(&config, 10, 10, Pos::new(1, 1));
+
+    // a sound trap goes off when stepped on
+    let sound_pos = Pos::new(2, 1);
+    let sound_trap = make_trap_sound(&mut game.data.entities, &game.config, sound_pos, &mut game.msg_log);
+
+    // a pressure plate closes a door and sets off spikes elsewhere on the map
+    let plate = make_pressure_plate(&mut game.data.entities, &game.config, Pos::new(3, 1), &mut game.msg_log);
+    let door_pos = Pos::new(3, 3);
+    let door = make_door(&mut game.data.entities, &game.config, door_pos, &mut game.msg_log);
+    let spikes = make_spikes(&mut game.data.entities, &game.config, Pos::new(5, 5), &mut game.msg_log);
+    let gol = make_gol(&mut game.data.entities, &game.config, Pos::new(5, 5), &mut game.msg_log);
+    game.data.entities.stunned.insert(gol, 10);
+    game.data.entities.link(plate, door);
+    game.data.entities.link(plate, spikes);
+
+    // a tripwire across the corridor opens a cage
+    let wire = make_tripwire(&mut game.data.entities, &game.config, Pos::new(4, 0), Pos::new(4, 3), &mut game.msg_log);
+    let cage_pos = Pos::new(8, 8);
+    let cage = make_cage(&mut game.data.entities, &game.config, cage_pos, &mut game.msg_log);
+    let elf = make_elf(&mut game.data.entities, &game.config, cage_pos, &mut game.msg_log);
+    game.data.entities.link(wire, cage);
+
+    game.data.entities.behavior[&elf] = Behavior::Attacking(player_id);
+    assert_eq!(Action::Pass, ai_take_turn(elf, &mut game.data, &game.config, &mut game.msg_log));
+    game.data.entities.behavior[&elf] = Behavior::Idle;
+
+    game.input_action = InputAction::Move(Direction::Right);
+    game.step_game(0.1);
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::SoundTrapTriggered(sound_trap, player_id)));
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::Sound(player_id, sound_pos, config.sound_radius_trap, true)));
+
+    game.input_action = InputAction::Move(Direction::Right);
+    game.step_game(0.1);
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::Triggered(plate, player_id)));
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::DoorClosed(door)));
+    assert!(game.data.map[door_pos].blocked);
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::SpikeTrapTriggered(spikes, gol)));
+
+    game.input_action = InputAction::Move(Direction::Right);
+    game.step_game(0.1);
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::CageOpened(cage, Pos::new(4, 0))));
+    assert!(!game.data.entities.ids.contains(&cage));
+
+    // a timer sets off its traps every few turns
+    let timer_sound_pos = Pos::new(0, 9);
+    let timer = make_timer(&mut game.data.entities, &game.config, Pos::new(0, 8), &mut game.msg_log);
+    let timer_trap = make_trap_sound(&mut game.data.entities, &game.config, timer_sound_pos, &mut game.msg_log);
+    game.data.entities.link(timer, timer_trap);
+
+    for _ in 0..(config.trap_timer_turns - 1) {
+        game.input_action = InputAction::Pass;
+        game.step_game(0.1);
+        assert!(!game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::Triggered(timer, timer)));
+    }
+
+    game.input_action = InputAction::Pass;
+    game.step_game(0.1);
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::Triggered(timer, timer)));
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::SoundTrapTriggered(timer_trap, timer)));
+}
+
+#[test]
+pub fn test_fire() {
+    let mut config = Config::from_file("../config.yaml");
+    config.fire_spread_chance = 1.0;
+    let player_pos = Pos::new(0, 0);
+    let (mut game, player_id) = test_game(&config, 10, 10, player_pos);
+
+    // a strip of grass along the top of the map, with a gol standing in it
+    for x in 1..10 {
+        game.data.map[Pos::new(x, 0)].surface = Surface::Grass;
+    }
+    let gol = make_gol(&mut game.data.entities, &game.config, Pos::new(4, 0), &mut game.msg_log);
+    game.data.entities.stunned.insert(gol, 10);
+
+    // a torch sets the grass next to the player alight
+    let torch = make_torch(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    actions::pick_item_up(player_id, torch, &mut game.data.entities);
+    actions::start_item_use(player_id, torch, &mut game.data, &mut game.settings);
+    let fire_pos = Pos::new(1, 0);
+    game.input_action = InputAction::MapClick(fire_pos, fire_pos);
+    game.step_game(0.1);
+
+    assert_eq!(Surface::Burnt, game.data.map[fire_pos].surface);
+    assert!(game.data.entities.on_fire(fire_pos));
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::Sound(player_id, fire_pos, config.sound_radius_fire, true)));
+
+    // the fire spreads along the grass until it reaches the gol
+    let mut gol_burned = false;
+    for _ in 0..4 {
+        game.input_action = InputAction::Pass;
+        game.step_game(0.1);
+        gol_burned |= game.msg_log.turn_messages.iter().any(|msg| matches!(msg, Msg::Burned(_, entity_id, _) if *entity_id == gol));
+    }
+    assert!(gol_burned);
+    assert!(game.data.entities.fighter[&gol].hp < game.data.entities.fighter[&gol].max_hp);
+
+    // fire gives off light and paths go around it
+    let center = Pos::new(5, 5);
+    make_fire(&mut game.data.entities, &game.config, center, &mut game.msg_log);
+    assert!(game.data.entities.is_lit(Pos::new(5, 6)));
+    assert!(!game.data.entities.is_lit(Pos::new(9, 9)));
+    let path = game.data.path_between(Pos::new(5, 4), Pos::new(5, 6), Reach::Single(1), false);
+    assert!(path.len() > 0);
+    assert!(!path.contains(&center));
+
+    // a thrown lantern breaks and sets grass alight where it lands
+    let lantern_target = Pos::new(2, 2);
+    game.data.map[lantern_target].surface = Surface::Grass;
+    let lantern = make_lantern(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    actions::pick_item_up(player_id, lantern, &mut game.data.entities);
+    game.data.entities.selected_item.insert(player_id, lantern);
+    game.settings.state = GameState::Selection;
+    game.settings.selection = Selection::new(SelectionType::WithinRadius(5), SelectionAction::Throw);
+    game.input_action = InputAction::MapClick(lantern_target, lantern_target);
+    game.step_game(0.1);
+
+    assert!(game.data.entities.on_fire(lantern_target));
+    assert!(!game.data.entities.ids.contains(&lantern));
+}
+
+#[test]
+pub fn test_fire_deterministic() {
+    fn burn_field(seed: u64) -> Vec<Pos> {
+        let config = Config::from_file("../config.yaml");
+        let mut game = Game::new(seed, config.clone()).unwrap();
+
+        let player_id = game.data.find_player().unwrap();
+        game.data.map = Map::from_dims(10, 10);
+        game.data.entities.pos[&player_id] = Pos::new(0, 0);
+
+        for y in 2..10 {
+            for x in 0..10 {
+                game.data.map[Pos::new(x, y)].surface = Surface::Grass;
+            }
+        }
+
+        game.msg_log.log(Msg::Ignite(player_id, Pos::new(5, 5)));
+        resolve_messages(&mut game.data, &mut game.msg_log, &mut game.settings, &game.config);
+
+        for _ in 0..10 {
+            game.input_action = InputAction::Pass;
+            game.step_game(0.1);
+        }
+
+        let mut burnt = Vec::new();
+        for y in 0..10 {
+            for x in 0..10 {
+                if game.data.map[Pos::new(x, y)].surface == Surface::Burnt {
+                    burnt.push(Pos::new(x, y));
+                }
+            }
+        }
+        return burnt;
+    }
+
+    // the same seed always burns the same tiles
+    assert_eq!(burn_field(1), burn_field(1));
+}
+
+#[test]
+pub fn test_water() {
+    let config = Config::from_file("../config.yaml");
+    let player_pos = Pos::new(3, 5);
+    let (mut game, player_id) = test_game(&config, 10, 10, player_pos);
+
+    // a shallow shore next to a channel of deep water
+    for y in 0..10 {
+        game.data.map[Pos::new(4, y)] = Tile::shallow_water();
+        game.data.map[Pos::new(5, y)] = Tile::water();
+    }
+    game.data.map.update_map();
+
+    let hammer = make_hammer(&mut game.data.entities, &game.config, player_pos, &mut game.msg_log);
+    actions::pick_item_up(player_id, hammer, &mut game.data.entities);
+
+    // wading through shallow water splashes
+    let shore_pos = Pos::new(4, 5);
+    game.input_action = InputAction::Move(Direction::Right);
+    game.step_game(0.1);
+    assert_eq!(shore_pos, game.data.entities.pos[&player_id]);
+    let splash_radius = config.sound_radius_walk + config.sound_radius_shallow_water;
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::Sound(player_id, shore_pos, splash_radius, true)));
+
+    // swimming into deep water leaves heavy items on the shore
+    let deep_pos = Pos::new(5, 5);
+    game.input_action = InputAction::Move(Direction::Right);
+    game.step_game(0.1);
+    assert_eq!(deep_pos, game.data.entities.pos[&player_id]);
+    assert!(!game.data.entities.inventory[&player_id].contains(&hammer));
+    assert_eq!(shore_pos, game.data.entities.pos[&hammer]);
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::ItemDropped(player_id, hammer)));
+
+    // nothing can be thrown while swimming
+    let stone = game.data.entities.inventory[&player_id][0];
+    let target_pos = Pos::new(8, 5);
+    game.data.entities.selected_item.insert(player_id, stone);
+    game.settings.state = GameState::Selection;
+    game.settings.selection = Selection::new(SelectionType::WithinRadius(5), SelectionAction::Throw);
+    game.input_action = InputAction::MapClick(target_pos, target_pos);
+    game.step_game(0.1);
+    assert!(game.msg_log.turn_messages.iter().any(|msg| *msg == Msg::TriedThrowSwimming(player_id)));
+    assert!(game.data.entities.inventory[&player_id].contains(&stone));
+
+    // gols can't swim, but elves can
+    let gol = make_gol(&mut game.data.entities, &game.config, Pos::new(6, 1), &mut game.msg_log);
+    let gol_reach = game.data.entities.movement[&gol];
+    let gol_move = calculate_move(Direction::Left, gol_reach, gol, &game.data);
+    assert!(gol_move.map_or(true, |movement| movement.pos != Pos::new(5, 1)));
+
+    let elf_pos = Pos::new(6, 8);
+    let elf = make_elf(&mut game.data.entities, &game.config, elf_pos, &mut game.msg_log);
+    let elf_reach = game.data.entities.movement[&elf];
+    let elf_move = calculate_move(Direction::Left, elf_reach, elf, &game.data);
+    assert_eq!(Some(Pos::new(5, 8)), elf_move.map(|movement| movement.pos));
+
+    let far_shore = Pos::new(3, 8);
+    assert!(game.data.path_between(elf_pos, far_shore, elf_reach, true).len() > 0);
+    assert!(game.data.path_between(elf_pos, far_shore, elf_reach, false).is_empty());
+}
+
+#[test]
+pub fn test_push_physics() {
+    let config = Config::from_file("../config.yaml");
+    let (mut game, player_id) = test_game(&config, 12, 12, Pos::new(1, 2));
+    game.data.map[Pos::new(6, 2)] = Tile::wall();
+    game.data.map[Pos::new(3, 6)] = Tile::water();
+    game.data.map[Pos::new(3, 7)] = Tile::water();
+    game.data.map.update_map();
+
+    // a column falls along its length, crushing a gol and toppling a second column
+    // into a wall
+    let col1 = make_column(&mut game.data.entities, &game.config, Pos::new(2, 2), &mut game.msg_log);
+    let crushed_gol = make_gol(&mut game.data.entities, &game.config, Pos::new(3, 2), &mut game.msg_log);
+    let col2 = make_column(&mut game.data.entities, &game.config, Pos::new(4, 2), &mut game.msg_log);
+
+    game.msg_log.log(Msg::Pushed(player_id, col1, Pos::new(1, 0)));
+    resolve_messages(&mut game.data, &mut game.msg_log, &mut game.settings, &game.config);
+
+    let turn_messages: Vec<Msg> = game.msg_log.turn_messages.iter().map(|msg| *msg).collect();
+    let toppled_index = turn_messages.iter().position(|msg| *msg == Msg::Toppled(player_id, col1, Pos::new(1, 0))).unwrap();
+    let crushed_index = turn_messages.iter().position(|msg| *msg == Msg::Crushed(col1, Pos::new(3, 2))).unwrap();
+    let chain_index = turn_messages.iter().position(|msg| *msg == Msg::Toppled(col1, col2, Pos::new(1, 0))).unwrap();
+    let second_crush_index = turn_messages.iter().position(|msg| *msg == Msg::Crushed(col2, Pos::new(5, 2))).unwrap();
+    assert!(toppled_index < crushed_index);
+    assert!(crushed_index < chain_index);
+    assert!(chain_index < second_crush_index);
+
+    assert!(!game.data.entities.alive[&crushed_gol]);
+    assert!(game.data.entities.needs_removal[&col1]);
+    assert!(game.data.entities.needs_removal[&col2]);
+    assert!(turn_messages.contains(&Msg::DamageTerrain(col2, Pos::new(6, 2), Edge::Full, config.crush_terrain_damage)));
+    let hp = { game.data.map[Pos::new(6, 2)].hp };
+    assert_eq!(Material::Stone.hp() as Hp - config.crush_terrain_damage, hp as Hp);
+    game.msg_log.clear();
+
+    // a gol pushed into deep water drowns
+    game.data.entities.pos[&player_id] = Pos::new(1, 6);
+    let gol = make_gol(&mut game.data.entities, &game.config, Pos::new(2, 6), &mut game.msg_log);
+    push_attack(player_id, gol, Pos::new(1, 0), false, &mut game.data, &mut game.msg_log);
+    resolve_messages(&mut game.data, &mut game.msg_log, &mut game.settings, &game.config);
+    assert!(game.msg_log.turn_messages.contains(&Msg::Drowned(player_id, gol, Pos::new(3, 6))));
+    assert!(!game.data.entities.alive[&gol]);
+    game.msg_log.clear();
+
+    // an elf pushed into deep water swims
+    game.data.entities.pos[&player_id] = Pos::new(1, 7);
+    let elf = make_elf(&mut game.data.entities, &game.config, Pos::new(2, 7), &mut game.msg_log);
+    push_attack(player_id, elf, Pos::new(1, 0), false, &mut game.data, &mut game.msg_log);
+    resolve_messages(&mut game.data, &mut game.msg_log, &mut game.settings, &game.config);
+    assert_eq!(Pos::new(3, 7), game.data.entities.pos[&elf]);
+    assert!(game.data.entities.alive[&elf]);
+    game.msg_log.clear();
+
+    // a monster pushed into another monster hurts them both
+    game.data.entities.pos[&player_id] = Pos::new(1, 9);
+    let pushed_gol = make_gol(&mut game.data.entities, &game.config, Pos::new(2, 9), &mut game.msg_log);
+    let hit_gol = make_gol(&mut game.data.entities, &game.config, Pos::new(3, 9), &mut game.msg_log);
+    let gol_hp = game.data.entities.fighter[&pushed_gol].hp;
+    push_attack(player_id, pushed_gol, Pos::new(1, 0), false, &mut game.data, &mut game.msg_log);
+    resolve_messages(&mut game.data, &mut game.msg_log, &mut game.settings, &game.config);
+    assert!(game.msg_log.turn_messages.contains(&Msg::Slammed(pushed_gol, hit_gol)));
+    assert_eq!(Pos::new(2, 9), game.data.entities.pos[&pushed_gol]);
+    assert_eq!(gol_hp - config.push_collision_damage, game.data.entities.fighter[&pushed_gol].hp);
+    assert_eq!(gol_hp - config.push_collision_damage, game.data.entities.fighter[&hit_gol].hp);
+    game.msg_log.clear();
+
+    // a monster pushed onto a trap sets it off
+    game.data.entities.pos[&player_id] = Pos::new(1, 11);
+    let trapped_gol = make_gol(&mut game.data.entities, &game.config, Pos::new(2, 11), &mut game.msg_log);
+    let spikes = make_spikes(&mut game.data.entities, &game.config, Pos::new(3, 11), &mut game.msg_log);
+    push_attack(player_id, trapped_gol, Pos::new(1, 0), false, &mut game.data, &mut game.msg_log);
+    resolve_messages(&mut game.data, &mut game.msg_log, &mut game.settings, &game.config);
+    assert_eq!(Pos::new(3, 11), game.data.entities.pos[&trapped_gol]);
+    assert!(game.msg_log.turn_messages.contains(&Msg::Triggered(spikes, trapped_gol)));
+}
+
+#[test]
+pub fn test_elevation() {
+    let config = Config::from_file("../config.yaml");
+    let ground_pos = Pos::new(4, 5);
+    let (mut game, player_id) = test_game(&config, 10, 10, ground_pos);
+
+    // a raised platform covering the right half of the map
+    for x in 5..10 {
+        for y in 0..10 {
+            game.data.map[Pos::new(x, y)].elevation = 1;
+        }
+    }
+    game.data.map.update_map();
+
+    let platform_pos = Pos::new(5, 5);
+
+    // the ledge can't be walked up
+    game.input_action = InputAction::Move(Direction::Right);
+    game.step_game(0.1);
+    assert_eq!(ground_pos, game.data.entities.pos[&player_id]);
+    assert!(game.data.map.is_blocked_by_wall(ground_pos, 1, 0).is_some());
+    assert!(game.data.map.is_blocked_by_wall(platform_pos, -1, 0).is_none());
+
+    // paths can drop off the platform, but not climb onto it
+    let reach = game.data.entities.movement[&player_id];
+    assert!(game.data.path_between(Pos::new(3, 2), Pos::new(7, 2), reach, false).is_empty());
+    assert!(game.data.path_between(Pos::new(7, 2), Pos::new(3, 2), reach, false).len() > 0);
+
+    // but it can be climbed
+    game.settings.state = GameState::Selection;
+    game.settings.selection = Selection::new(SelectionType::WithinReach(Reach::single(1)), SelectionAction::Interact);
+    game.input_action = InputAction::MapClick(platform_pos, platform_pos);
+    game.step_game(0.1);
+    assert_eq!(platform_pos, game.data.entities.pos[&player_id]);
+    assert!(game.msg_log.turn_messages.contains(&Msg::Climbed(player_id, platform_pos)));
+
+    // high ground sees further
+    let radius = config.fov_radius_player;
+    assert_eq!(radius + ELEVATION_FOV_BONUS, game.data.map.elevated_radius(platform_pos, radius));
+    assert_eq!(radius, game.data.map.elevated_radius(ground_pos, radius));
+
+    // and hits harder
+    let gol = make_gol(&mut game.data.entities, &game.config, Pos::new(4, 6), &mut game.msg_log);
+    let gol_hp = game.data.entities.fighter[&gol].hp;
+    let power = game.data.entities.fighter[&player_id].power;
+    attack(player_id, gol, &mut game.data, &mut game.msg_log);
+    assert_eq!(gol_hp - power - ELEVATION_ATTACK_BONUS, game.data.entities.fighter[&gol].hp);
+    game.data.remove_entity(gol);
+
+    // a short drop only makes a noise
+    let player_hp = game.data.entities.fighter[&player_id].hp;
+    game.input_action = InputAction::Move(Direction::Left);
+    game.step_game(0.1);
+    assert_eq!(ground_pos, game.data.entities.pos[&player_id]);
+    assert!(game.msg_log.turn_messages.contains(&Msg::Fell(player_id, ground_pos, 1)));
+    assert_eq!(player_hp, game.data.entities.fighter[&player_id].hp);
+
+    // a longer one hurts
+    let cliff_pos = Pos::new(2, 2);
+    game.data.map[cliff_pos].elevation = 2;
+    game.data.entities.pos[&player_id] = cliff_pos;
+    game.input_action = InputAction::Move(Direction::Down);
+    game.step_game(0.1);
+    let fall_damage = (2 - config.safe_fall_height) as i32 * config.fall_damage;
+    assert!(game.msg_log.turn_messages.contains(&Msg::Fell(player_id, Pos::new(2, 3), 2)));
+    assert_eq!(player_hp - fall_damage, game.data.entities.fighter[&player_id].hp);
+}
+
 #[test]
 pub fn test_hammer_small_wall() {
     let mut config = Config::from_file("../config.yaml");
@@ -557,7 +1523,7 @@ pub fn test_hammer_small_wall() {
 
     let hammer = make_hammer(&mut game.data.entities, &game.config, Pos::new(4, 7), &mut game.msg_log);
 
-    game.data.entities.inventory[&player_id].push_front(hammer);
+    actions::pick_item_up(player_id, hammer, &mut game.data.entities);
 
     game.input_action = InputAction::UseItem;
     game.step_game(0.1);
@@ -581,7 +1547,7 @@ pub fn test_hammer_small_wall() {
 
     // add the hammer back and hit the pawn with it to test hitting entities
     let hammer = make_hammer(&mut game.data.entities, &game.config, Pos::new(4, 7), &mut game.msg_log);
-    game.data.entities.inventory[&player_id].push_front(hammer);
+    actions::pick_item_up(player_id, hammer, &mut game.data.entities);
 
     game.input_action = InputAction::UseItem;
     game.step_game(0.1);
@@ -603,11 +1569,11 @@ pub fn test_hammer_small_wall() {
 pub fn test_game_map() {
     // Create Game and Map
     let mut config = Config::from_file("../config.yaml");
-    config.map_load = MapLoadConfig::TestMap;
+    config.map_load = MapConfig::new(MapSource::TestMap);
     let mut game = Game::new(0, config.clone()).unwrap();
 
     let player_id = game.data.find_player().unwrap();
-    make_map(&MapLoadConfig::TestMap, &mut game);
+    make_map(&MapConfig::new(MapSource::TestMap), &mut game);
     let player_pos = game.data.entities.pos[&player_id];
     assert_eq!(Pos::new(0, 0), player_pos);
 
@@ -666,13 +1632,14 @@ pub fn test_game_map() {
     // hit short wall below us
     test_move(&mut game, Direction::Down,  (0, 1));
 
-    // test out short wall jumping
+    // short walls can't be jumped without building up speed
     input(&mut game, InputAction::IncreaseMoveMode);
-    test_move(&mut game, Direction::Down,  (0, 2));
-    test_move(&mut game, Direction::Up,    (0, 1));
+    test_move(&mut game, Direction::Down,  (0, 1));
     input(&mut game, InputAction::DecreaseMoveMode);
     test_move(&mut game, Direction::Up,    (0, 0));
     input(&mut game, InputAction::IncreaseMoveMode);
+    // run up to the wall, then jump it at full speed
+    test_move(&mut game, Direction::Down,  (0, 1));
     test_move(&mut game, Direction::Down,  (0, 2));
 
     // run into wall
@@ -689,15 +1656,20 @@ pub fn test_game_map() {
     // hits left wall, doesn't move
     test_move(&mut game, Direction::Left,  (2, 3));
     input(&mut game, InputAction::IncreaseMoveMode);
+    // not enough speed to jump the wall
+    test_move(&mut game, Direction::Left,  (2, 3));
+    // back up and get a running start
+    test_move(&mut game, Direction::Right, (4, 3));
+    test_move(&mut game, Direction::Left,  (2, 3));
     // jumps over wall
     test_move(&mut game, Direction::Left,  (1, 3));
-    // jumps back over wall
-    test_move(&mut game, Direction::Right, (2, 3));
+    // turning around loses speed, so we can't jump back
+    test_move(&mut game, Direction::Right, (1, 3));
 
     // trigger a trap by pushing a monster into it
     input(&mut game, InputAction::DecreaseMoveMode);
     input(&mut game, InputAction::DecreaseMoveMode);
-    test_move(&mut game, Direction::Down, (2, 4));
+    test_move(&mut game, Direction::DownRight, (2, 4));
     test_move(&mut game, Direction::Down, (2, 5));
     test_move(&mut game, Direction::Down, (2, 6));
     test_move(&mut game, Direction::Down, (2, 7));
@@ -2,7 +2,7 @@ use std::collections::VecDeque;
 
 use rand::prelude::*;
 
-use serde::{Serialize, Deserialize};
+use log::warn;
 
 use roguelike_core::ai::{Behavior, Ai};
 use roguelike_core::map::*;
@@ -14,17 +14,7 @@ use roguelike_core::config::*;
 use roguelike_core::utils::{rand_from_pos, distance};
 
 use crate::game::*;
-
-
-#[derive(Clone, PartialEq, Serialize, Deserialize)]
-pub enum MapGenType {
-    Island,
-    WallTest,
-    CornerTest,
-    PlayerTest,
-    FromFile(String),
-    Animations,
-}
+use crate::solvability::mutually_reachable;
 
 
 pub fn make_player(entities: &mut Entities, config: &Config, msg_log: &mut MsgLog) -> EntityId {
@@ -39,8 +29,14 @@ pub fn make_player(entities: &mut Entities, config: &Config, msg_log: &mut MsgLo
     entities.movement.insert(player,  Reach::Single(1));
     entities.attack.insert(player,  Reach::Single(1));
     entities.move_mode.insert(player,  MoveMode::Walk);
+    entities.momentum.insert(player,  Momentum::default());
     entities.direction.insert(player,  Direction::Up);
     entities.inventory.insert(player,  VecDeque::new());
+    entities.swims.insert(player,  true);
+    entities.capacity.insert(player,
+        Capacity::new(config.player_max_weight,
+                      config.player_max_items,
+                      config.player_encumbered_weight));
 
     msg_log.log(Msg::SpawnedObject(player, entities.typ[&player], Pos::new(0, 0), EntityName::Player));
 
@@ -59,6 +55,7 @@ pub fn make_dagger(entities: &mut Entities, config: &Config, pos: Pos, msg_log:
     let object = entities.create_entity(pos.x, pos.y, EntityType::Item, ENTITY_DAGGER as char, config.color_light_grey, EntityName::Dagger, false);
 
     entities.item.insert(object,  Item::Dagger);
+    entities.durability.insert(object,  Durability::new(config.dagger_durability));
 
     msg_log.log(Msg::SpawnedObject(object, entities.typ[&object], pos, EntityName::Dagger));
 
@@ -69,6 +66,7 @@ pub fn make_hammer(entities: &mut Entities, config: &Config, pos: Pos, msg_log:
     let object = entities.create_entity(pos.x, pos.y, EntityType::Item, ENTITY_HAMMER as char, config.color_light_grey, EntityName::Hammer, false);
 
     entities.item.insert(object,  Item::Hammer);
+    entities.durability.insert(object,  Durability::new(config.hammer_durability));
 
     msg_log.log(Msg::SpawnedObject(object, entities.typ[&object], pos, EntityName::Hammer));
 
@@ -79,6 +77,7 @@ pub fn make_sword(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &
     let object = entities.create_entity(pos.x, pos.y, EntityType::Item, ENTITY_SWORD as char, config.color_light_grey, EntityName::Sword, false);
 
     entities.item.insert(object,  Item::Sword);
+    entities.durability.insert(object,  Durability::new(config.sword_durability));
 
     msg_log.log(Msg::SpawnedObject(object, entities.typ[&object], pos, EntityName::Sword));
 
@@ -123,7 +122,10 @@ pub fn make_gol(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &mu
     entities.movement.insert(gol,  Reach::Single(GOL_MOVE_DISTANCE));
     entities.attack.insert(gol,  Reach::Diag(GOL_ATTACK_DISTANCE));
     entities.alive.insert(gol,  true);
+    entities.momentum.insert(gol,  Momentum::default());
     entities.direction.insert(gol,  Direction::from_f32(rand_from_pos(pos)));
+    entities.inventory.insert(gol,  VecDeque::new());
+    entities.swims.insert(gol,  false);
 
     msg_log.log(Msg::SpawnedObject(gol, entities.typ[&gol], pos, EntityName::Gol));
     
@@ -140,7 +142,10 @@ pub fn make_spire(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &
     entities.movement.insert(spire,  Reach::Single(SPIRE_MOVE_DISTANCE));
     entities.attack.insert(spire,  Reach::Single(SPIRE_ATTACK_DISTANCE));
     entities.alive.insert(spire,  true);
+    entities.momentum.insert(spire,  Momentum::default());
     entities.direction.insert(spire,  Direction::Up);
+    entities.inventory.insert(spire,  VecDeque::new());
+    entities.swims.insert(spire,  false);
 
     msg_log.log(Msg::SpawnedObject(spire, entities.typ[&spire], pos, EntityName::Spire));
 
@@ -157,7 +162,10 @@ pub fn make_elf(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &mu
     entities.movement.insert(elf,  Reach::Single(PAWN_MOVE_DISTANCE));
     entities.attack.insert(elf,  Reach::Single(PAWN_ATTACK_DISTANCE));
     entities.alive.insert(elf,  true);
+    entities.momentum.insert(elf,  Momentum::default());
     entities.direction.insert(elf,  Direction::from_f32(rand_from_pos(pos)));
+    entities.inventory.insert(elf,  VecDeque::new());
+    entities.swims.insert(elf,  true);
 
     msg_log.log(Msg::SpawnedObject(elf, entities.typ[&elf], pos, EntityName::Pawn));
 
@@ -168,6 +176,7 @@ pub fn make_trap_sound(entities: &mut Entities, config: &Config, pos: Pos, msg_l
     let sound = entities.create_entity(pos.x, pos.y, EntityType::Item, ENTITY_TRAP_SOUND as char, config.color_ice_blue, EntityName::Sound, false);
 
     entities.trap.insert(sound,  Trap::Sound);
+    entities.trigger.insert(sound,  Trigger::PressurePlate);
     entities.armed.insert(sound,  true);
     entities.item.insert(sound,  Item::SoundTrap);
 
@@ -180,6 +189,7 @@ pub fn make_spikes(entities: &mut Entities, config: &Config, pos: Pos, msg_log:
     let spikes = entities.create_entity(pos.x, pos.y, EntityType::Item, MAP_TALL_SPIKES as char, config.color_ice_blue, EntityName::Spike, false);
 
     entities.trap.insert(spikes,  Trap::Spikes);
+    entities.trigger.insert(spikes,  Trigger::PressurePlate);
     entities.armed.insert(spikes,  true);
     entities.item.insert(spikes,  Item::SpikeTrap);
 
@@ -188,6 +198,61 @@ pub fn make_spikes(entities: &mut Entities, config: &Config, pos: Pos, msg_log:
     return spikes;
 }
 
+pub fn make_pressure_plate(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &mut MsgLog) -> EntityId {
+    let plate = entities.create_entity(pos.x, pos.y, EntityType::Other, ENTITY_PRESSURE_PLATE as char, config.color_light_grey, EntityName::PressurePlate, false);
+
+    entities.trigger.insert(plate,  Trigger::PressurePlate);
+    entities.armed.insert(plate,  true);
+
+    msg_log.log(Msg::SpawnedObject(plate, entities.typ[&plate], pos, EntityName::PressurePlate));
+
+    return plate;
+}
+
+pub fn make_tripwire(entities: &mut Entities, config: &Config, start: Pos, end: Pos, msg_log: &mut MsgLog) -> EntityId {
+    let wire = entities.create_entity(start.x, start.y, EntityType::Other, ENTITY_TRIPWIRE as char, config.color_light_grey, EntityName::Tripwire, false);
+
+    entities.trigger.insert(wire,  Trigger::Tripwire(end));
+    entities.armed.insert(wire,  true);
+
+    msg_log.log(Msg::SpawnedObject(wire, entities.typ[&wire], start, EntityName::Tripwire));
+
+    return wire;
+}
+
+pub fn make_timer(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &mut MsgLog) -> EntityId {
+    let timer = entities.create_entity(pos.x, pos.y, EntityType::Other, ENTITY_TIMER as char, config.color_light_grey, EntityName::Timer, false);
+
+    entities.trigger.insert(timer,  Trigger::Timer(config.trap_timer_turns, config.trap_timer_turns));
+    entities.armed.insert(timer,  true);
+
+    msg_log.log(Msg::SpawnedObject(timer, entities.typ[&timer], pos, EntityName::Timer));
+
+    return timer;
+}
+
+pub fn make_door(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &mut MsgLog) -> EntityId {
+    let door = entities.create_entity(pos.x, pos.y, EntityType::Other, ENTITY_DOOR as char, config.color_orange, EntityName::Door, false);
+
+    entities.trap.insert(door,  Trap::Door);
+    entities.armed.insert(door,  true);
+
+    msg_log.log(Msg::SpawnedObject(door, entities.typ[&door], pos, EntityName::Door));
+
+    return door;
+}
+
+pub fn make_cage(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &mut MsgLog) -> EntityId {
+    let cage = entities.create_entity(pos.x, pos.y, EntityType::Other, ENTITY_CAGE as char, config.color_orange, EntityName::Cage, false);
+
+    entities.trap.insert(cage,  Trap::Cage);
+    entities.armed.insert(cage,  true);
+
+    msg_log.log(Msg::SpawnedObject(cage, entities.typ[&cage], pos, EntityName::Cage));
+
+    return cage;
+}
+
 pub fn make_exit(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &mut MsgLog) -> EntityId {
     let exit = entities.create_entity(pos.x, pos.y, EntityType::Item, ENTITY_EXIT as char, config.color_orange, EntityName::Exit, false);
 
@@ -210,84 +275,140 @@ pub fn make_stone(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &
     return stone;
 }
 
-/* TODO remove
-pub fn make_map(map_type: &MapGenType,
-                entities: &mut Entities,
-                config: &Config,
-                msg_log: &mut MsgLog,
-                rng: &mut SmallRng) -> (GameData, Pos) {
-    let result;
-    match map_type {
-        MapGenType::WallTest => {
-            let (map, player_position) = make_wall_test_map(entities, config, msg_log);
-            
-            result = (GameData::new(map, entities.clone()), player_position);
-        }
+pub fn make_healing_draught(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &mut MsgLog) -> EntityId {
+    let object = entities.create_entity(pos.x, pos.y, EntityType::Item, ENTITY_HEALING_DRAUGHT as char, config.color_rose_red, EntityName::HealingDraught, false);
 
-        MapGenType::CornerTest => {
-            let (map, player_position) = make_corner_test_map(entities, config, msg_log);
-            
-            result = (GameData::new(map, entities.clone()), player_position);
-        }
+    entities.item.insert(object,  Item::HealingDraught);
 
-        MapGenType::PlayerTest => {
-            let (map, player_position) = make_player_test_map(entities, config, msg_log);
-            
-            result = (GameData::new(map, entities.clone()), player_position);
-        }
+    msg_log.log(Msg::SpawnedObject(object, entities.typ[&object], pos, EntityName::HealingDraught));
 
-        MapGenType::Island => {
-            let map = Map::from_dims(MAP_WIDTH as usize, MAP_HEIGHT as usize);
+    return object;
+}
 
-            let mut data = GameData::new(map, entities.clone());
-            let starting_position = make_island(&mut data, config, msg_log, rng);
+pub fn make_smoke_bomb(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &mut MsgLog) -> EntityId {
+    let object = entities.create_entity(pos.x, pos.y, EntityType::Item, ENTITY_SMOKE_BOMB as char, config.color_medium_grey, EntityName::SmokeBomb, false);
 
-            data.map[starting_position.to_tuple()].tile_type = TileType::Empty;
+    entities.item.insert(object,  Item::SmokeBomb);
 
-            data.map.update_map();
+    msg_log.log(Msg::SpawnedObject(object, entities.typ[&object], pos, EntityName::SmokeBomb));
 
-            result = (data, starting_position);
-        }
+    return object;
+}
 
-        MapGenType::FromFile(file_name) => {
-            let mut entities = Entities::new();
-            let (new_map, player_position) =
-                read_map_xp(config, &mut entities, msg_log, &file_name);
+pub fn make_noisemaker(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &mut MsgLog) -> EntityId {
+    let object = entities.create_entity(pos.x, pos.y, EntityType::Item, ENTITY_NOISEMAKER as char, config.color_orange, EntityName::Noisemaker, false);
 
-            let data = GameData::new(new_map, entities);
+    entities.item.insert(object,  Item::Noisemaker);
 
-            result = (data, Pos::from(player_position));
-        }
+    msg_log.log(Msg::SpawnedObject(object, entities.typ[&object], pos, EntityName::Noisemaker));
 
-        MapGenType::Animations => {
-            let (map, player_position) = make_player_test_map(entities, config, msg_log);
-            
-            result = (GameData::new(map, entities.clone()), player_position);
-        }
-    }
+    return object;
+}
 
-    return result;
+pub fn make_torch(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &mut MsgLog) -> EntityId {
+    let object = entities.create_entity(pos.x, pos.y, EntityType::Item, ENTITY_TORCH as char, config.color_light_orange, EntityName::Torch, false);
+
+    entities.item.insert(object,  Item::Torch);
+
+    msg_log.log(Msg::SpawnedObject(object, entities.typ[&object], pos, EntityName::Torch));
+
+    return object;
+}
+
+pub fn make_lantern(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &mut MsgLog) -> EntityId {
+    let object = entities.create_entity(pos.x, pos.y, EntityType::Item, ENTITY_LANTERN as char, config.color_light_orange, EntityName::Lantern, false);
+
+    entities.item.insert(object,  Item::Lantern);
+
+    msg_log.log(Msg::SpawnedObject(object, entities.typ[&object], pos, EntityName::Lantern));
+
+    return object;
+}
+
+pub fn make_fire(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &mut MsgLog) -> EntityId {
+    let fire = entities.create_entity(pos.x, pos.y, EntityType::Other, ENTITY_FIRE as char, config.color_orange, EntityName::Fire, false);
+
+    entities.count_down.insert(fire,  config.fire_turns);
+    entities.light.insert(fire,  config.fire_light_radius);
+
+    msg_log.log(Msg::SpawnedObject(fire, entities.typ[&fire], pos, EntityName::Fire));
+
+    return fire;
+}
+
+pub fn make_smoke(entities: &mut Entities, config: &Config, pos: Pos, msg_log: &mut MsgLog) -> EntityId {
+    let smoke = entities.create_entity(pos.x, pos.y, EntityType::Other, ENTITY_SMOKE as char, config.color_warm_grey, EntityName::Smoke, false);
+
+    entities.count_down.insert(smoke,  config.smoke_turns);
+
+    msg_log.log(Msg::SpawnedObject(smoke, entities.typ[&smoke], pos, EntityName::Smoke));
+
+    return smoke;
 }
-*/
 
 pub fn make_island(data: &mut GameData,
                    config: &Config,
                    msg_log: &mut MsgLog,
                    rng: &mut SmallRng) -> Pos {
     let center = Pos::new(data.map.width() / 2, data.map.height() / 2);
+    let radius = island_radius(&data.map, center);
+    let swims = data.find_player().map_or(false, |player_id| data.entities.can_swim(player_id));
 
-    let mut water_tile_positions = Vec::new();
+    // an island is regenerated until the key and the exit can both be reached
+    for _ in 0..ISLAND_ATTEMPTS {
+        make_island_terrain(&mut data.map, center, radius, rng);
 
-    /* Create Island */
-    // the center has land, the remaining square are filled with water
+        if let Some(key_pos) = place_island_exit(&mut data.map, center, radius, swims, rng) {
+            make_key(&mut data.entities, &config, key_pos, msg_log);
+            return center;
+        }
+    }
+
+    // obstacles are all that can cut the island up, so an island
+    // cleared of them can always be solved
+    warn!("could not make a solvable island in {} attempts, clearing its obstacles", ISLAND_ATTEMPTS);
     for x in 0..data.map.width() {
         for y in 0..data.map.height() {
             let pos = Pos::new(x, y);
+            if distance(pos, center) <= radius {
+                data.map[pos] = Tile::empty();
+            }
+        }
+    }
+
+    let key_pos = place_island_exit(&mut data.map, center, radius, swims, rng).expect("a bare island is always solvable");
+    make_key(&mut data.entities, &config, key_pos, msg_log);
+
+    return center;
+}
+
+/// The island radius, up to ISLAND_RADIUS, that keeps the island and the
+/// obstacles placed on it within the map.
+fn island_radius(map: &Map, center: Pos) -> i32 {
+    let (width, height) = map.size();
+    let space = *[center.x, center.y, width - 1 - center.x, height - 1 - center.y].iter().min().unwrap();
+
+    return std::cmp::max(1, std::cmp::min(ISLAND_RADIUS, space - ISLAND_OBSTACLE_MARGIN));
+}
+
+fn make_island_terrain(map: &mut Map, center: Pos, radius: i32, rng: &mut SmallRng) {
+    let mut water_tile_positions = Vec::new();
+    let mut shore_tile_positions = Vec::new();
+
+    /* Create Island */
+    // the center has land surrounded by a shallow shore, and the
+    // remaining squares are filled with deep water
+    for x in 0..map.width() {
+        for y in 0..map.height() {
+            let pos = Pos::new(x, y);
 
-            if distance(pos, center) <= ISLAND_RADIUS {
-                data.map.tiles[x as usize][y as usize] = Tile::empty();
+            if distance(pos, center) <= radius {
+                map.tiles[x as usize][y as usize] = Tile::empty();
+            } else if distance(pos, center) <= radius + ISLAND_SHORE_WIDTH {
+                map.tiles[x as usize][y as usize] = Tile::shallow_water();
+                shore_tile_positions.push(pos);
             } else {
-                data.map.tiles[x as usize][y as usize] = Tile::water();
+                map.tiles[x as usize][y as usize] = Tile::water();
                 water_tile_positions.push((x, y));
             }
         }
@@ -297,128 +418,109 @@ pub fn make_island(data: &mut GameData,
     let obstacles = Obstacle::all_obstacles();
 
     for _ in 0..ISLAND_NUM_OBSTACLES {
-        let rand_pos = random_offset(rng, ISLAND_RADIUS);
+        let rand_pos = random_offset(rng, radius);
         let pos = Pos::new(center.x + rand_pos.x, center.y + rand_pos.y);
 
         let obstacle = *obstacles.choose(rng).unwrap();
 
         // Buildings are generated separately, so don't add them in random generation
         if obstacle != Obstacle::Building {
-            add_obstacle(&mut data.map, pos, obstacle, rng);
+            add_obstacle(map, pos, obstacle, rng);
         }
     }
 
     /* add buildings */
     for _ in 0..rng.gen_range(3, 5) {
-        let rand_pos = random_offset(rng, ISLAND_RADIUS);
+        let rand_pos = random_offset(rng, radius);
         let pos = Pos::new(center.x + rand_pos.x, center.y + rand_pos.y);
-        add_obstacle(&mut data.map, pos, Obstacle::Building, rng);
+        add_obstacle(map, pos, Obstacle::Building, rng);
     }
 
     /* random subtraction */
     for _ in 0..ISLAND_NUM_SUBTRACTIONS_ATTEMPTS {
-        let pos = pos_in_radius(center, ISLAND_RADIUS, rng);
+        let pos = pos_in_radius(center, radius, rng);
 
-        if data.map[pos].tile_type == TileType::Wall {
-            data.map[pos] = Tile::empty();
+        if map[pos].tile_type == TileType::Wall {
+            map[pos] = Tile::empty();
         }
     }
 
     /* random additions */
     for _ in 0..ISLAND_NUM_ADDITION_ATTEMPTS {
-        let pos = pos_in_radius(center, ISLAND_RADIUS, rng);
+        let pos = pos_in_radius(center, radius, rng);
         let obstacle = *obstacles.choose(rng).unwrap();
 
-        if data.map[pos].tile_type == TileType::Wall {
-            add_obstacle(&mut data.map, pos, obstacle, rng);
+        if map[pos].tile_type == TileType::Wall {
+            add_obstacle(map, pos, obstacle, rng);
         }
     }
 
-    /* random stones */
-    for _ in 0..10 {
-        let pos = pos_in_radius(center, ISLAND_RADIUS, rng);
-
-        if data.map.is_empty(pos) {
-            make_stone(&mut data.entities, config, pos, msg_log);
-        }
+    /* Ensure that objects placed outside of the island are removed */
+    for pos in water_tile_positions {
+        map[pos].tile_type = TileType::Water;
     }
 
-    /* add monsters */
-    for _ in 0..0 {
-        loop {
-            let pos = pos_in_radius(center, ISLAND_RADIUS, rng);
-
-            if !data.has_blocking_entity(pos).is_some()  {
-                make_gol(&mut data.entities, config, pos, msg_log);
-                break;
-            }
-        }
+    for pos in shore_tile_positions {
+        map[pos] = Tile::shallow_water();
     }
 
-    for _ in 0..1 {
-        loop {
-            let pos = pos_in_radius(center, ISLAND_RADIUS, rng);
-
-            if !data.has_blocking_entity(pos).is_some()  {
-                make_elf(&mut data.entities, config, pos, msg_log);
-                break;
-            }
-        }
-    }
-    
-    for _ in 0..0 {
-        loop {
-            let pos = pos_in_radius(center, ISLAND_RADIUS, rng);
+    // the player starts in the center, so it can't be inside an obstacle
+    map[center] = Tile::empty();
+}
 
-            if !data.has_blocking_entity(pos).is_some() {
-                make_spire(&mut data.entities, config, pos, msg_log);
-                break;
+/// Place an exit on the edge of the island, returning a position for the key
+/// such that the start, the key and the exit can all reach each other.
+fn place_island_exit(map: &mut Map, center: Pos, radius: i32, swims: bool, rng: &mut SmallRng) -> Option<Pos> {
+    /* find key position */
+    // the key goes somewhere the player can get to and back from
+    let mut key_positions = Vec::new();
+    for x in 0..map.width() {
+        for y in 0..map.height() {
+            let pos = Pos::new(x, y);
+            if pos != center && distance(pos, center) <= radius && map.is_empty(pos) {
+                key_positions.push(pos);
             }
         }
     }
+    key_positions.shuffle(rng);
 
-    let x = rng.gen_range(0, data.map.width());
-    let y = rng.gen_range(0, data.map.height());
-    let pos = Pos::new(x, y);
-
-    if !data.has_blocking_entity(pos).is_some()  {
-        make_key(&mut data.entities, config, Pos::new(x, y), msg_log);
-    }
-
-    /* add key object */
-    let mut pos = pos_in_radius(center, ISLAND_RADIUS, rng);
-
-    while !data.map.is_empty(pos) {
-        pos = pos_in_radius(center, ISLAND_RADIUS, rng);
-    }
-    make_key(&mut data.entities, &config, pos, msg_log);
+    let key_pos = key_positions.iter()
+                               .find(|pos| mutually_reachable(map, &[center, **pos], swims).is_ok())
+                               .map(|pos| *pos)?;
 
     /* add exit */
     // find edge of island
-    let map_size = data.map.size();
+    let map_size = map.size();
     let mut edge_positions = Vec::new();
     for x in 0..map_size.0 {
         for y in 0..map_size.1 {
             let pos = Pos::from((x, y));
 
-            if !(data.map[pos].tile_type == TileType::Water) &&
-                 near_tile_type(&data.map, pos, TileType::Water) {
+            if !map[pos].tile_type.is_water() &&
+                 near_tile_type(map, pos, TileType::ShallowWater) {
                 edge_positions.push(pos);
             }
         }
     }
-    // choose a random edge position
-    let edge_pos = edge_positions[rng.gen_range(0, edge_positions.len())];
 
-    // make the random edge position the exit
-    data.map.tiles[edge_pos.x as usize][edge_pos.y as usize] = Tile::exit();
+    // try random edge positions until one can be reached along with the key
+    edge_positions.shuffle(rng);
+    for edge_pos in edge_positions {
+        if edge_pos == key_pos {
+            continue;
+        }
 
-    /* Ensure that objects placed outside of the island are removed */
-    for pos in water_tile_positions {
-        data.map[pos].tile_type = TileType::Water;
+        let previous_tile = map[edge_pos];
+        map[edge_pos] = Tile::exit();
+
+        if mutually_reachable(map, &[center, key_pos, edge_pos], swims).is_ok() {
+            return Some(key_pos);
+        }
+
+        map[edge_pos] = previous_tile;
     }
 
-    return center;
+    return None;
 }
 
 pub fn make_player_test_map(entities: &mut Entities,
@@ -451,7 +553,7 @@ pub fn make_player_test_map(entities: &mut Entities,
 pub fn make_test_map(game: &mut Game) {
     //right down left should work
     //down will hit wall
-    //running should jump over wall
+    //running at full speed should jump over wall
     //back up should also work
     //up then down should jump over wall
     game.data.map[(0, 1)].bottom_wall = Wall::ShortWall;
@@ -0,0 +1,178 @@
+use roguelike_core::types::*;
+use roguelike_core::config::Config;
+use roguelike_core::constants::*;
+use roguelike_core::messaging::{Msg, MsgLog};
+use roguelike_core::movement::Reach;
+use roguelike_core::utils::damage_entity;
+
+use crate::game::SelectionType;
+use crate::generation::make_smoke;
+
+
+/// How an item is used once it is chosen by its holder.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum UseKind {
+    // used towards a position picked with a Selection
+    Targeted(SelectionType),
+    // used immediately on the entity holding the item
+    SelfUse,
+    // has its effect just by being carried, so it can't be used directly
+    Passive,
+}
+
+/// An item's effect when used: user, item, position used towards.
+pub type UseEffect = fn(EntityId, EntityId, Pos, &mut GameData, &Config, &mut MsgLog);
+
+#[derive(Copy, Clone)]
+pub struct ItemBehavior {
+    pub kind: UseKind,
+    pub effect: Option<UseEffect>,
+    pub consumed: bool,
+}
+
+impl ItemBehavior {
+    pub fn targeted(typ: SelectionType, effect: UseEffect) -> ItemBehavior {
+        return ItemBehavior { kind: UseKind::Targeted(typ), effect: Some(effect), consumed: false };
+    }
+
+    pub fn self_use(effect: UseEffect) -> ItemBehavior {
+        return ItemBehavior { kind: UseKind::SelfUse, effect: Some(effect), consumed: false };
+    }
+
+    pub fn passive() -> ItemBehavior {
+        return ItemBehavior { kind: UseKind::Passive, effect: None, consumed: false };
+    }
+
+    pub fn consumable(mut self) -> ItemBehavior {
+        self.consumed = true;
+        return self;
+    }
+}
+
+/// The table of item behaviors. New items only need an entry here to be usable.
+pub fn item_behavior(item: Item) -> ItemBehavior {
+    match item {
+        Item::Hammer => ItemBehavior::targeted(SelectionType::WithinReach(Reach::Horiz(1)), use_hammer),
+        Item::Sword => ItemBehavior::self_use(use_sword),
+        Item::HealingDraught => ItemBehavior::self_use(use_healing_draught).consumable(),
+        Item::SmokeBomb => ItemBehavior::targeted(SelectionType::WithinRadius(PLAYER_THROW_DIST), use_smoke_bomb).consumable(),
+        Item::Noisemaker => ItemBehavior::targeted(SelectionType::WithinRadius(PLAYER_THROW_DIST), use_noisemaker).consumable(),
+        Item::Torch => ItemBehavior::targeted(SelectionType::WithinReach(Reach::Horiz(1)), use_torch),
+
+        // daggers stab when moving, shields block while held, and
+        // stones, lanterns and traps are thrown or placed rather than used
+        Item::Dagger | Item::Shield | Item::Stone | Item::Goal |
+        Item::SpikeTrap | Item::SoundTrap | Item::Lantern => ItemBehavior::passive(),
+    }
+}
+
+pub fn use_item(entity_id: EntityId,
+                item_id: EntityId,
+                pos: Pos,
+                data: &mut GameData,
+                config: &Config,
+                msg_log: &mut MsgLog) {
+    let behavior = item_behavior(data.entities.item[&item_id]);
+
+    if let Some(effect) = behavior.effect {
+        effect(entity_id, item_id, pos, data, config, msg_log);
+
+        if behavior.consumed {
+            let count = data.entities.stack_count(item_id);
+            if count > 1 {
+                data.entities.count[&item_id] = count - 1;
+                msg_log.log(Msg::StackChanged(item_id, count - 1));
+            } else {
+                data.entities.remove_item(entity_id, item_id);
+                data.remove_entity(item_id);
+            }
+        }
+    }
+}
+
+fn use_hammer(entity_id: EntityId, _item_id: EntityId, pos: Pos, _data: &mut GameData, _config: &Config, msg_log: &mut MsgLog) {
+    msg_log.log(Msg::HammerSwing(entity_id, pos));
+}
+
+fn use_sword(entity_id: EntityId, _item_id: EntityId, pos: Pos, _data: &mut GameData, _config: &Config, msg_log: &mut MsgLog) {
+    msg_log.log(Msg::SwordSwing(entity_id, pos));
+}
+
+fn use_healing_draught(entity_id: EntityId, _item_id: EntityId, _pos: Pos, data: &mut GameData, config: &Config, msg_log: &mut MsgLog) {
+    if let Some(fighter) = data.entities.fighter.get_mut(&entity_id) {
+        let hp = std::cmp::min(fighter.max_hp, fighter.hp + config.healing_draught_amount);
+        let amount = hp - fighter.hp;
+        fighter.hp = hp;
+
+        msg_log.log(Msg::Healed(entity_id, amount));
+    }
+}
+
+fn use_smoke_bomb(_entity_id: EntityId, _item_id: EntityId, pos: Pos, data: &mut GameData, config: &Config, msg_log: &mut MsgLog) {
+    for smoke_pos in data.map.floodfill(pos, config.smoke_bomb_radius) {
+        make_smoke(&mut data.entities, config, smoke_pos, msg_log);
+    }
+}
+
+fn use_noisemaker(entity_id: EntityId, _item_id: EntityId, pos: Pos, _data: &mut GameData, config: &Config, msg_log: &mut MsgLog) {
+    msg_log.log(Msg::Sound(entity_id, pos, config.noisemaker_radius, true));
+}
+
+fn use_torch(entity_id: EntityId, _item_id: EntityId, pos: Pos, _data: &mut GameData, _config: &Config, msg_log: &mut MsgLog) {
+    msg_log.log(Msg::Ignite(entity_id, pos));
+}
+
+/// What happens to an entity struck by a thrown item.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ThrowHit {
+    Damage(Hp),
+    Stun(usize),
+    Nothing,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ThrowBehavior {
+    pub hit: ThrowHit,
+    pub sound_radius: usize,
+}
+
+pub fn throw_behavior(item: Item, config: &Config) -> ThrowBehavior {
+    match item {
+        Item::Stone => ThrowBehavior { hit: ThrowHit::Stun(config.stun_turns_stone), sound_radius: config.sound_radius_stone },
+        Item::Dagger => ThrowBehavior { hit: ThrowHit::Damage(config.throw_damage_dagger), sound_radius: config.sound_radius_thrown_item },
+        _ => ThrowBehavior { hit: ThrowHit::Nothing, sound_radius: config.sound_radius_thrown_item },
+    }
+}
+
+pub fn thrown_item_hit(thrower_id: EntityId,
+                       item_id: EntityId,
+                       hit_id: EntityId,
+                       data: &mut GameData,
+                       config: &Config,
+                       msg_log: &mut MsgLog) {
+    if !data.entities.alive[&hit_id] || data.entities.fighter.get(&hit_id).is_none() {
+        return;
+    }
+
+    match throw_behavior(data.entities.item[&item_id], config).hit {
+        ThrowHit::Damage(damage) => {
+            msg_log.log(Msg::Attack(thrower_id, hit_id, damage));
+            damage_entity(thrower_id, hit_id, damage, &mut data.entities, msg_log);
+
+            if let Some(messages) = data.entities.messages.get_mut(&hit_id) {
+                messages.push(Message::Attack(thrower_id));
+            }
+        }
+
+        ThrowHit::Stun(turns) => {
+            // only monsters lose their turns to being stunned
+            if data.entities.ai.get(&hit_id).is_some() {
+                data.entities.stunned.insert(hit_id, turns);
+                msg_log.log(Msg::Stunned(hit_id, turns));
+            }
+        }
+
+        ThrowHit::Nothing => {
+        }
+    }
+}
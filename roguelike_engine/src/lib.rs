@@ -3,3 +3,11 @@ pub mod make_map;
 pub mod actions;
 pub mod generation;
 pub mod resolve;
+pub mod items;
+pub mod solvability;
+pub mod bsp;
+pub mod caves;
+pub mod vaults;
+pub mod wfc;
+pub mod placement;
+pub mod text_map;
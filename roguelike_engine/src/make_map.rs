@@ -1,78 +1,162 @@
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{self, BufReader};
+#[cfg(test)]
+use std::env;
+#[cfg(test)]
+use std::fs;
+
+use rand::prelude::*;
 
 use rexpaint::*;
 
-use log::trace;
+use log::{trace, warn, error};
 
 use roguelike_core::constants::*;
 use roguelike_core::messaging::*;
 use roguelike_core::map::*;
 use roguelike_core::types::*;
 use roguelike_core::config::*;
+use roguelike_core::utils::{add_pos, move_x, move_y};
 
 use crate::generation::*;
 use crate::game::*;
+use crate::actions::pick_item_up;
+use crate::solvability::check_solvable;
+use crate::bsp::make_bsp;
+use crate::caves::make_caves;
+use crate::vaults::{Vault, load_vaults, place_vaults};
+use crate::wfc::{WfcModel, make_wfc};
+use crate::placement::populate_level;
+use crate::text_map::read_map_text_file;
+
+
+/// Map data read from disk, or built from it, kept between maps
+/// so it is only made once.
+#[derive(Default)]
+pub struct MapCache {
+    pub vaults: Option<Vec<Vault>>,
+    pub wfc_model: Option<WfcModel>,
+}
 
+pub fn make_map(map_config: &MapConfig, game: &mut Game) {
+    // a seed given for the map replaces the game's rng until the map is made
+    let game_rng =
+        map_config.seed.map(|seed| std::mem::replace(&mut game.rng, SeedableRng::seed_from_u64(seed)));
 
-pub fn make_map(map_load_config: &MapLoadConfig, game: &mut Game) {
     let player_position: Pos;
 
-    match map_load_config {
-        MapLoadConfig::TestMap => {
+    match &map_config.source {
+        MapSource::TestMap => {
             game.data.map = Map::from_dims(11, 12);
             make_test_map(game);
             player_position = Pos::new(0, 0);
         }
 
-        MapLoadConfig::Empty => {
+        MapSource::Empty => {
             let new_map = Map::from_dims(10, 10);
             game.data.map = new_map;
             player_position = Pos::new(0, 0);
         }
 
-        MapLoadConfig::FromFile => {
-            let mut position =
-                read_map_xp(&game.config, &mut game.data, &mut game.msg_log, "resources/map.xp");
-            if position == (0, 0) {
-                position = (game.data.map.width() / 2, game.data.map.height() / 2);
+        MapSource::FromFile(file_name) => {
+            let position;
+            if file_name.ends_with(".txt") {
+                match read_map_text_file(&game.config, &mut game.data, &mut game.msg_log, file_name) {
+                    Ok(pos) => position = pos,
+
+                    Err(err) => {
+                        // the current map is kept, with the player where they already are
+                        error!("could not load map {}: {}", file_name, err);
+                        position = game.data.find_player().map(|player_id| game.data.entities.pos[&player_id]);
+                    }
+                }
+            } else {
+                position = read_map_xp(&game.config, &mut game.data, &mut game.msg_log, file_name);
+            }
+            player_position =
+                position.unwrap_or(Pos::new(game.data.map.width() / 2, game.data.map.height() / 2));
+
+            // a hand made map can't be repaired, but at least report the problem
+            if let Err(error) = check_solvable(&game.data, player_position) {
+                error!("map file can't be solved: {:?}", error);
             }
-            player_position = Pos::from(position);
         }
 
-        MapLoadConfig::Random => {
-            game.data.map = Map::from_dims(MAP_WIDTH as usize, MAP_HEIGHT as usize);
+        MapSource::Island => {
+            game.data.map = Map::from_dims(map_config.width as usize, map_config.height as usize);
             let starting_position = make_island(&mut game.data, &game.config, &mut game.msg_log, &mut game.rng);
             player_position = Pos::from(starting_position);
         }
 
-        MapLoadConfig::TestWall => {
+        MapSource::Bsp => {
+            game.data.map = Map::from_dims(map_config.width as usize, map_config.height as usize);
+            player_position = make_bsp(&mut game.data, &game.config, &mut game.msg_log, &mut game.rng);
+        }
+
+        MapSource::Caves => {
+            game.data.map = Map::from_dims(map_config.width as usize, map_config.height as usize);
+            player_position = make_caves(&mut game.data, &game.config, &mut game.msg_log, &mut game.rng);
+        }
+
+        MapSource::Wfc => {
+            game.data.map = Map::from_dims(map_config.width as usize, map_config.height as usize);
+            if game.map_cache.wfc_model.is_none() {
+                game.map_cache.wfc_model = Some(WfcModel::from_config(&game.config));
+            }
+            let model = game.map_cache.wfc_model.as_ref().unwrap();
+            player_position = make_wfc(&mut game.data, &game.config, &mut game.msg_log, model, &mut game.rng);
+        }
+
+        MapSource::TestWall => {
             let (new_map, position) = make_wall_test_map(&mut game.data.entities, &game.config, &mut game.msg_log);
             game.data.map = new_map;
             player_position = Pos::from(position);
         }
 
-        MapLoadConfig::TestPlayer => {
+        MapSource::TestPlayer => {
             let (new_map, position) = make_player_test_map(&mut game.data.entities, &game.config, &mut game.msg_log);
             game.data.map = new_map;
             player_position = Pos::from(position);
         }
 
-        MapLoadConfig::TestCorner => {
+        MapSource::TestCorner => {
             let (new_map, position) = make_corner_test_map(&mut game.data.entities, &game.config, &mut game.msg_log);
             game.data.map = new_map;
             player_position = Pos::from(position);
         }
     }
 
+    if map_config.source.is_generated() {
+        fill_generated_map(game, player_position);
+    }
+
     let player_id = game.data.find_player().unwrap();
     game.data.entities.pos[&player_id] = player_position;
+
+    if let Some(rng) = game_rng {
+        game.rng = rng;
+    }
+}
+
+/// Splice hand made vaults into a generated map, and then
+/// fill it with monsters and items for the current depth.
+fn fill_generated_map(game: &mut Game, player_position: Pos) {
+    if game.map_cache.vaults.is_none() {
+        game.map_cache.vaults = Some(load_vaults(&game.config.vault_directory));
+    }
+    let vaults = game.map_cache.vaults.as_ref().unwrap();
+
+    let num_placed =
+        place_vaults(&mut game.data, &game.config, &mut game.msg_log, vaults, player_position, &mut game.rng);
+    trace!("placed {} vaults", num_placed);
+
+    populate_level(&mut game.data, &game.config, &mut game.msg_log, player_position, game.settings.depth, &mut game.rng);
 }
 
 pub fn read_map_xp(config: &Config,
                    data: &mut GameData,
                    msg_log: &mut MsgLog,
-                   file_name: &str) -> (i32, i32) {
+                   file_name: &str) -> Option<Pos> {
     trace!("opening map {}", file_name);
     let file = File::open(file_name).unwrap();
 
@@ -82,7 +166,25 @@ pub fn read_map_xp(config: &Config,
     let xp = XpFile::read(&mut buf_reader).unwrap();
 
     data.map = Map::from_dims(xp.layers[0].width, xp.layers[0].height);
-    let mut player_position = (0, 0);
+
+    return place_map_xp(config, data, msg_log, &xp, Pos::new(0, 0));
+}
+
+/// Fill in the map from .xp data, with the data's top left corner at the given offset.
+/// Cells left blank in the ground layer keep whatever tile was already there.
+/// Returns the player's position if one was given.
+pub fn place_map_xp(config: &Config,
+                    data: &mut GameData,
+                    msg_log: &mut MsgLog,
+                    xp: &XpFile,
+                    offset: Pos) -> Option<Pos> {
+    let mut player_position = None;
+
+    // triggers are linked to the traps drawn in the same color
+    let mut trigger_colors: Vec<(EntityId, (u8, u8, u8))> = Vec::new();
+    let mut trap_colors: Vec<(EntityId, (u8, u8, u8))> = Vec::new();
+    // tripwires waiting for the post at their other end
+    let mut tripwire_posts: Vec<(EntityId, (u8, u8, u8))> = Vec::new();
 
     for (layer_index, layer) in xp.layers.iter().enumerate() {
         let width = layer.width as i32;
@@ -93,7 +195,7 @@ pub fn read_map_xp(config: &Config,
                 let index = y + height * x;
                 let cell = layer.cells[index as usize];
 
-                let pos = Pos::new(x, y);
+                let pos = add_pos(offset, Pos::new(x, y));
 
                 let chr = std::char::from_u32(cell.ch).unwrap();
 
@@ -104,6 +206,7 @@ pub fn read_map_xp(config: &Config,
                             }
 
                             MAP_GROUND => {
+                                data.map[pos] = Tile::empty();
                             }
 
                             MAP_WATER => {
@@ -111,6 +214,11 @@ pub fn read_map_xp(config: &Config,
                                 data.map[pos].chr = chr as u8;
                             }
 
+                            MAP_SHALLOW_WATER => {
+                                data.map[pos] = Tile::shallow_water();
+                                data.map[pos].chr = chr as u8;
+                            }
+
                             MAP_RUBBLE => {
                                 data.map[pos].surface = Surface::Rubble;
                             }
@@ -133,7 +241,7 @@ pub fn read_map_xp(config: &Config,
 
                             MAP_THIN_WALL_TOP => {
                                 data.map[pos].chr = chr as u8;
-                                data.map[(x, y - 1)].bottom_wall = Wall::ShortWall;
+                                data.map[move_y(pos, -1)].bottom_wall = Wall::ShortWall;
                             }
 
                             MAP_THIN_WALL_BOTTOM => {
@@ -148,13 +256,13 @@ pub fn read_map_xp(config: &Config,
 
                             MAP_THIN_WALL_RIGHT => {
                                 data.map[pos].chr = chr as u8;
-                                data.map[(x + 1, y)].left_wall = Wall::ShortWall;
+                                data.map[move_x(pos, 1)].left_wall = Wall::ShortWall;
                             }
 
                             MAP_THIN_WALL_TOP_LEFT => {
                                 data.map[pos].chr = chr as u8;
                                 data.map[pos].left_wall = Wall::ShortWall;
-                                data.map[(x, y - 1)].bottom_wall = Wall::ShortWall;
+                                data.map[move_y(pos, -1)].bottom_wall = Wall::ShortWall;
                             }
 
                             MAP_THIN_WALL_BOTTOM_LEFT => {
@@ -165,19 +273,19 @@ pub fn read_map_xp(config: &Config,
 
                             MAP_THIN_WALL_TOP_RIGHT => {
                                 data.map[pos].chr = chr as u8;
-                                data.map[(x, y - 1)].bottom_wall = Wall::ShortWall;
-                                data.map[(x - 1, y)].left_wall = Wall::ShortWall;
+                                data.map[move_y(pos, -1)].bottom_wall = Wall::ShortWall;
+                                data.map[move_x(pos, 1)].left_wall = Wall::ShortWall;
                             }
 
                             MAP_THIN_WALL_BOTTOM_RIGHT => {
                                 data.map[pos].chr = chr as u8;
                                 data.map[pos].bottom_wall = Wall::ShortWall;
-                                data.map[(x + 1, y)].left_wall = Wall::ShortWall;
+                                data.map[move_x(pos, 1)].left_wall = Wall::ShortWall;
                             }
 
                             MAP_THICK_WALL_TOP => {
                                 data.map[pos].chr = chr as u8;
-                                data.map[(x, y - 1)].bottom_wall = Wall::ShortWall;
+                                data.map[move_y(pos, -1)].bottom_wall = Wall::ShortWall;
                             }
 
                             MAP_THICK_WALL_LEFT => {
@@ -187,7 +295,7 @@ pub fn read_map_xp(config: &Config,
 
                             MAP_THICK_WALL_RIGHT => {
                                 data.map[pos].chr = chr as u8;
-                                data.map[(x + 1, y)].left_wall = Wall::ShortWall;
+                                data.map[move_x(pos, 1)].left_wall = Wall::ShortWall;
                             }
 
                             MAP_THICK_WALL_BOTTOM => {
@@ -198,7 +306,7 @@ pub fn read_map_xp(config: &Config,
                             MAP_THICK_WALL_TOP_LEFT => {
                                 data.map[pos].chr = chr as u8;
                                 data.map[pos].left_wall = Wall::TallWall;
-                                data.map[(x, y - 1)].bottom_wall = Wall::TallWall;
+                                data.map[move_y(pos, -1)].bottom_wall = Wall::TallWall;
                             }
 
                             MAP_THICK_WALL_BOTTOM_LEFT => {
@@ -209,14 +317,14 @@ pub fn read_map_xp(config: &Config,
 
                             MAP_THICK_WALL_TOP_RIGHT => {
                                 data.map[pos].chr = chr as u8;
-                                data.map[(x, y - 1)].bottom_wall = Wall::TallWall;
-                                data.map[(x + 1, y)].left_wall = Wall::TallWall;
+                                data.map[move_y(pos, -1)].bottom_wall = Wall::TallWall;
+                                data.map[move_x(pos, 1)].left_wall = Wall::TallWall;
                             }
 
                             MAP_THICK_WALL_BOTTOM_RIGHT => {
                                 data.map[pos].chr = chr as u8;
                                 data.map[pos].bottom_wall = Wall::TallWall;
-                                data.map[(x + 1, y)].left_wall = Wall::TallWall;
+                                data.map[move_x(pos, 1)].left_wall = Wall::TallWall;
                             }
 
                             MAP_DOT_TOP_LEFT => {
@@ -284,7 +392,7 @@ pub fn read_map_xp(config: &Config,
                     MAP_LAYER_ENTITIES => {
                         match chr as u8 {
                             ENTITY_PLAYER => {
-                                player_position = (x as i32, y as i32);
+                                player_position = Some(pos);
                             }
 
                             ENTITY_GOL => {
@@ -316,19 +424,160 @@ pub fn read_map_xp(config: &Config,
                             }
 
                             ENTITY_SHIELD => {
-                                make_shield(&mut data.entities, config, Pos::new(x, y), msg_log);
+                                make_shield(&mut data.entities, config, pos, msg_log);
                             }
 
                             ENTITY_SPIKE_TRAP => {
                                 make_spikes(&mut data.entities, config, pos, msg_log);
                             }
 
+                            ENTITY_TORCH => {
+                                make_torch(&mut data.entities, config, pos, msg_log);
+                            }
+
+                            ENTITY_LANTERN => {
+                                make_lantern(&mut data.entities, config, pos, msg_log);
+                            }
+
+                            // spires are drawn as the castle tower
+                            ENTITY_CASTLE => {
+                                make_spire(&mut data.entities, config, pos, msg_log);
+                            }
+
+                            ENTITY_SWORD => {
+                                make_sword(&mut data.entities, config, pos, msg_log);
+                            }
+
+                            ENTITY_HAMMER => {
+                                make_hammer(&mut data.entities, config, pos, msg_log);
+                            }
+
+                            ENTITY_HEALING_DRAUGHT => {
+                                make_healing_draught(&mut data.entities, config, pos, msg_log);
+                            }
+
+                            ENTITY_SMOKE_BOMB => {
+                                make_smoke_bomb(&mut data.entities, config, pos, msg_log);
+                            }
+
+                            ENTITY_NOISEMAKER => {
+                                make_noisemaker(&mut data.entities, config, pos, msg_log);
+                            }
+
                             _ => {
                                 panic!(format!("Unexpected character {} in entities layer!", chr as u8));
                             }
                         }
                     }
 
+                    MAP_LAYER_TRIGGERS => {
+                        let color = (cell.fg.r, cell.fg.g, cell.fg.b);
+
+                        match chr as u8 {
+                            0 | MAP_EMPTY => {
+                            }
+
+                            ENTITY_PRESSURE_PLATE => {
+                                let plate = make_pressure_plate(&mut data.entities, config, pos, msg_log);
+                                trigger_colors.push((plate, color));
+                            }
+
+                            ENTITY_TRIPWIRE => {
+                                // tripwires run between two posts of the same color
+                                if let Some(index) = tripwire_posts.iter().position(|(_, post_color)| *post_color == color) {
+                                    let (wire, _) = tripwire_posts.remove(index);
+                                    data.entities.trigger[&wire] = Trigger::Tripwire(pos);
+                                } else {
+                                    let wire = make_tripwire(&mut data.entities, config, pos, pos, msg_log);
+                                    tripwire_posts.push((wire, color));
+                                    trigger_colors.push((wire, color));
+                                }
+                            }
+
+                            ENTITY_TIMER => {
+                                let timer = make_timer(&mut data.entities, config, pos, msg_log);
+                                trigger_colors.push((timer, color));
+                            }
+
+                            ENTITY_SPIKE_TRAP => {
+                                let spikes = make_spikes(&mut data.entities, config, pos, msg_log);
+                                trap_colors.push((spikes, color));
+                            }
+
+                            ENTITY_TRAP_SOUND => {
+                                let sound = make_trap_sound(&mut data.entities, config, pos, msg_log);
+                                trap_colors.push((sound, color));
+                            }
+
+                            ENTITY_DOOR => {
+                                let door = make_door(&mut data.entities, config, pos, msg_log);
+                                trap_colors.push((door, color));
+                            }
+
+                            ENTITY_CAGE => {
+                                let cage = make_cage(&mut data.entities, config, pos, msg_log);
+                                trap_colors.push((cage, color));
+                            }
+
+                            _ => {
+                                panic!(format!("Unexpected character {} in trigger layer!", chr as u8));
+                            }
+                        }
+                    }
+
+                    MAP_LAYER_LOOT => {
+                        // items in the loot layer are carried by the monster on the same tile
+                        let item_id;
+                        match chr as u8 {
+                            0 | MAP_EMPTY => {
+                                item_id = None;
+                            }
+
+                            ENTITY_DAGGER => {
+                                item_id = Some(make_dagger(&mut data.entities, config, pos, msg_log));
+                            }
+
+                            ENTITY_SWORD => {
+                                item_id = Some(make_sword(&mut data.entities, config, pos, msg_log));
+                            }
+
+                            ENTITY_HAMMER => {
+                                item_id = Some(make_hammer(&mut data.entities, config, pos, msg_log));
+                            }
+
+                            ENTITY_SHIELD => {
+                                item_id = Some(make_shield(&mut data.entities, config, pos, msg_log));
+                            }
+
+                            ENTITY_KEY => {
+                                item_id = Some(make_key(&mut data.entities, config, pos, msg_log));
+                            }
+
+                            ENTITY_STONE => {
+                                item_id = Some(make_stone(&mut data.entities, config, pos, msg_log));
+                            }
+
+                            _ => {
+                                panic!(format!("Unexpected character {} in loot layer!", chr as u8));
+                            }
+                        }
+
+                        if let Some(item_id) = item_id {
+                            let carrier = data.has_blocking_entity(pos)
+                                              .filter(|entity_id| data.entities.inventory.get(entity_id).is_some());
+                            if let Some(carrier) = carrier {
+                                pick_item_up(carrier, item_id, &mut data.entities);
+                            }
+                        }
+                    }
+
+                    MAP_LAYER_ELEVATION => {
+                        // raised ground is drawn with the digits 1-9
+                        if let Some(elevation) = chr.to_digit(10) {
+                            data.map[pos].elevation = elevation as u8;
+                        }
+                    }
+
                     _ => {
                         panic!(format!("Layer {} not expected in map file!", layer_index));
                     }
@@ -337,6 +586,14 @@ pub fn read_map_xp(config: &Config,
         }
     }
 
+    for (trigger, trigger_color) in trigger_colors.iter() {
+        for (trap, trap_color) in trap_colors.iter() {
+            if trigger_color == trap_color {
+                data.entities.link(*trigger, *trap);
+            }
+        }
+    }
+
     trace!("map read finished");
 
     data.map.update_map();
@@ -346,3 +603,587 @@ pub fn read_map_xp(config: &Config,
     return player_position;
 }
 
+
+/// The walls each wall glyph in the environment layer puts down when read by place_map_xp,
+/// given as the tile's left, bottom, top and right edges.
+const WALL_GLYPH_EDGES: &[(u8, [Wall; 4])] = &[
+    (MAP_THIN_WALL_LEFT, [Wall::ShortWall, Wall::Empty, Wall::Empty, Wall::Empty]),
+    (MAP_THIN_WALL_BOTTOM, [Wall::Empty, Wall::ShortWall, Wall::Empty, Wall::Empty]),
+    (MAP_THIN_WALL_TOP, [Wall::Empty, Wall::Empty, Wall::ShortWall, Wall::Empty]),
+    (MAP_THIN_WALL_RIGHT, [Wall::Empty, Wall::Empty, Wall::Empty, Wall::ShortWall]),
+    (MAP_THIN_WALL_TOP_LEFT, [Wall::ShortWall, Wall::Empty, Wall::ShortWall, Wall::Empty]),
+    (MAP_THIN_WALL_BOTTOM_LEFT, [Wall::ShortWall, Wall::ShortWall, Wall::Empty, Wall::Empty]),
+    (MAP_THIN_WALL_TOP_RIGHT, [Wall::Empty, Wall::Empty, Wall::ShortWall, Wall::ShortWall]),
+    (MAP_THIN_WALL_BOTTOM_RIGHT, [Wall::Empty, Wall::ShortWall, Wall::Empty, Wall::ShortWall]),
+    (MAP_THICK_WALL_LEFT, [Wall::TallWall, Wall::Empty, Wall::Empty, Wall::Empty]),
+    (MAP_THICK_WALL_BOTTOM, [Wall::Empty, Wall::TallWall, Wall::Empty, Wall::Empty]),
+    (MAP_THICK_WALL_TOP, [Wall::Empty, Wall::Empty, Wall::ShortWall, Wall::Empty]),
+    (MAP_THICK_WALL_RIGHT, [Wall::Empty, Wall::Empty, Wall::Empty, Wall::ShortWall]),
+    (MAP_THICK_WALL_TOP_LEFT, [Wall::TallWall, Wall::Empty, Wall::TallWall, Wall::Empty]),
+    (MAP_THICK_WALL_BOTTOM_LEFT, [Wall::TallWall, Wall::TallWall, Wall::Empty, Wall::Empty]),
+    (MAP_THICK_WALL_TOP_RIGHT, [Wall::Empty, Wall::Empty, Wall::TallWall, Wall::TallWall]),
+    (MAP_THICK_WALL_BOTTOM_RIGHT, [Wall::Empty, Wall::TallWall, Wall::Empty, Wall::TallWall]),
+];
+
+/// Write the map and the entities on it to a file that read_map_xp can load.
+pub fn write_map_xp(data: &GameData, file_name: &str) -> io::Result<()> {
+    trace!("writing map {}", file_name);
+    let xp = map_to_xp(data);
+
+    let mut file = File::create(file_name)?;
+    return xp.write(&mut file);
+}
+
+/// Lay the map out in the layers read by place_map_xp. Exit tiles become exit entities,
+/// and only one entity is kept on each tile. The loot layer only has room for one item
+/// on each tile, so monsters keep their first carried item and anything else they carry
+/// is left out with a warning. Triggers share a color with the traps they set off in the
+/// trigger layer. Entities the map format has no glyph for are left out.
+pub fn map_to_xp(data: &GameData) -> XpFile {
+    let width = data.map.width() as usize;
+    let height = data.map.height() as usize;
+    let index = |pos: Pos| pos.y as usize + height * pos.x as usize;
+
+    let mut xp = XpFile::new(width, height);
+    xp.layers = Vec::new();
+
+    let blank = XpCell { ch: MAP_EMPTY as u32, fg: XpColor { r: 255, g: 255, b: 255 }, bg: XpColor { r: 0, g: 0, b: 0 } };
+    for _ in 0..=MAP_LAYER_ELEVATION {
+        xp.layers.push(XpLayer { width, height, cells: vec![blank; width * height] });
+    }
+
+    let mut environment: Vec<Option<u8>> = vec![None; width * height];
+    let mut entities: Vec<Option<u8>> = vec![None; width * height];
+    let mut loot: Vec<Option<u8>> = vec![None; width * height];
+    let mut triggers: Vec<Option<(u8, XpColor)>> = vec![None; width * height];
+
+    for x in 0..data.map.width() {
+        for y in 0..data.map.height() {
+            let pos = Pos::new(x, y);
+            let tile = &data.map[pos];
+
+            xp.layers[MAP_LAYER_GROUND].cells[index(pos)].ch = ground_glyph(tile) as u32;
+            environment[index(pos)] = environment_glyph(&data.map, pos);
+
+            if tile.tile_type == TileType::Exit {
+                entities[index(pos)] = Some(ENTITY_EXIT);
+            }
+
+            if tile.elevation > 0 {
+                let elevation = std::cmp::min(tile.elevation, 9);
+                xp.layers[MAP_LAYER_ELEVATION].cells[index(pos)].ch = std::char::from_digit(elevation as u32, 10).unwrap() as u32;
+            }
+        }
+    }
+
+    let link_colors = link_colors(data);
+
+    place_wall_glyphs(&data.map, &mut environment);
+
+    // the player goes down first so they are never crowded out
+    let player = data.find_player();
+    let ids = player.iter().chain(data.entities.ids.iter().filter(|id| Some(**id) != player));
+    for id in ids {
+        let carried = data.entities.inventory.values().any(|inventory| inventory.contains(id));
+        let pos = match data.entities.pos.get(id) {
+            Some(pos) if !carried && data.map.is_within_bounds(*pos) => *pos,
+            _ => continue,
+        };
+
+        let name = data.entities.name[id];
+        if let Some((_, color)) = link_colors.iter().find(|(link_id, _)| link_id == id) {
+            // a tripwire is drawn as a post at either end
+            let mut posts = vec!(pos);
+            if let Some(Trigger::Tripwire(end)) = data.entities.trigger.get(id) {
+                if *end != pos && data.map.is_within_bounds(*end) {
+                    posts.push(*end);
+                }
+            }
+
+            for post in posts {
+                if triggers[index(post)].is_none() {
+                    triggers[index(post)] = Some((trigger_glyph(name).unwrap(), *color));
+                } else {
+                    trace!("no room for {:?} at {:?} in map file", name, post);
+                }
+            }
+        } else if let Some((layer_index, glyph)) = entity_glyph(name) {
+            let cells = if layer_index == MAP_LAYER_ENVIRONMENT { &mut environment } else { &mut entities };
+
+            if cells[index(pos)].is_none() {
+                cells[index(pos)] = Some(glyph);
+
+                // the player's inventory is not part of the map
+                if layer_index == MAP_LAYER_ENTITIES && Some(*id) != player {
+                    if let Some(inventory) = data.entities.inventory.get(id) {
+                        let carried_loot = inventory.iter().filter_map(|item_id| loot_glyph(data.entities.name[item_id])).next();
+                        loot[index(pos)] = carried_loot;
+
+                        let num_carried: usize = inventory.iter().map(|item_id| data.entities.stack_count(*item_id)).sum();
+                        let num_written = if carried_loot.is_some() { 1 } else { 0 };
+                        if num_carried > num_written {
+                            warn!("{} items carried by {:?} at {:?} are left out of the map file", num_carried - num_written, name, pos);
+                        }
+                    }
+                }
+            } else {
+                trace!("no room for {:?} at {:?} in map file", name, pos);
+            }
+        }
+    }
+
+    for cell_index in 0..(width * height) {
+        if let Some(glyph) = environment[cell_index] {
+            xp.layers[MAP_LAYER_ENVIRONMENT].cells[cell_index].ch = glyph as u32;
+        }
+
+        if let Some(glyph) = entities[cell_index] {
+            xp.layers[MAP_LAYER_ENTITIES].cells[cell_index].ch = glyph as u32;
+        }
+
+        if let Some(glyph) = loot[cell_index] {
+            xp.layers[MAP_LAYER_LOOT].cells[cell_index].ch = glyph as u32;
+        }
+
+        if let Some((glyph, color)) = triggers[cell_index] {
+            xp.layers[MAP_LAYER_TRIGGERS].cells[cell_index].ch = glyph as u32;
+            xp.layers[MAP_LAYER_TRIGGERS].cells[cell_index].fg = color;
+        }
+    }
+
+    return xp;
+}
+
+/// A color for each trigger, and each trap set off by one, so that place_map_xp links
+/// them back up. Triggers that set off the same trap share a color, and doors, cages
+/// and sound traps with nothing to set them off get a color of their own.
+/// Spikes are only given a color when a trigger sets them off. Tripwires that share a
+/// color are paired up by the order their posts are read in.
+fn link_colors(data: &GameData) -> Vec<(EntityId, XpColor)> {
+    let color = |group: usize| XpColor { r: 255, g: (group / 256) as u8, b: (group % 256) as u8 };
+
+    let mut groups: Vec<(EntityId, usize)> = Vec::new();
+    let mut num_groups = 0;
+    let is_trigger = |id: &EntityId| {
+        let name = data.entities.name[id];
+        return name == EntityName::PressurePlate || name == EntityName::Tripwire || name == EntityName::Timer;
+    };
+
+    for trigger in data.entities.ids.iter().filter(|id| is_trigger(*id)) {
+        let effects: Vec<EntityId> =
+            data.entities.links.get(trigger).map_or(Vec::new(), |links| links.clone())
+                               .into_iter()
+                               .filter(|effect| data.entities.name.get(effect).and_then(|name| trigger_glyph(*name)).is_some())
+                               .collect();
+
+        let existing = groups.iter().find(|(id, _)| effects.contains(id)).map(|(_, group)| *group);
+        let group = match existing {
+            Some(group) => group,
+            None => {
+                num_groups += 1;
+                num_groups - 1
+            }
+        };
+
+        groups.push((*trigger, group));
+        for effect in effects {
+            if !groups.iter().any(|(id, _)| *id == effect) {
+                groups.push((effect, group));
+            }
+        }
+    }
+
+    for id in data.entities.ids.iter() {
+        let name = data.entities.name[id];
+        let needs_color = trigger_glyph(name).is_some() && name != EntityName::Spike;
+        if needs_color && !groups.iter().any(|(other_id, _)| other_id == id) {
+            groups.push((*id, num_groups));
+            num_groups += 1;
+        }
+    }
+
+    return groups.into_iter().map(|(id, group)| (id, color(group))).collect();
+}
+
+/// The glyph a trigger, or a trap set off by one, is written with in the trigger layer.
+fn trigger_glyph(name: EntityName) -> Option<u8> {
+    match name {
+        EntityName::PressurePlate => Some(ENTITY_PRESSURE_PLATE),
+        EntityName::Tripwire => Some(ENTITY_TRIPWIRE),
+        EntityName::Timer => Some(ENTITY_TIMER),
+        EntityName::Spike => Some(ENTITY_SPIKE_TRAP),
+        EntityName::Sound => Some(ENTITY_TRAP_SOUND),
+        EntityName::Door => Some(ENTITY_DOOR),
+        EntityName::Cage => Some(ENTITY_CAGE),
+        _ => None,
+    }
+}
+
+/// The glyph an item carried by a monster is written with in the loot layer.
+fn loot_glyph(name: EntityName) -> Option<u8> {
+    match name {
+        EntityName::Dagger => Some(ENTITY_DAGGER),
+        EntityName::Sword => Some(ENTITY_SWORD),
+        EntityName::Hammer => Some(ENTITY_HAMMER),
+        EntityName::Shield => Some(ENTITY_SHIELD),
+        EntityName::Key => Some(ENTITY_KEY),
+        EntityName::Stone => Some(ENTITY_STONE),
+        _ => None,
+    }
+}
+
+fn ground_glyph(tile: &Tile) -> u8 {
+    match tile.tile_type {
+        TileType::Water => return MAP_WATER,
+        TileType::ShallowWater => return MAP_SHALLOW_WATER,
+        _ => {}
+    }
+
+    match tile.surface {
+        Surface::Grass => return MAP_GRASS,
+        Surface::Rubble => return MAP_RUBBLE,
+        _ => return MAP_GROUND,
+    }
+}
+
+/// The glyph a tile was read from, if it can be written back as it was.
+/// Wall glyphs are kept while their walls stand, and blocked tiles keep their character.
+fn environment_glyph(map: &Map, pos: Pos) -> Option<u8> {
+    let tile = &map[pos];
+    let chr = tile.chr;
+    let is_water = tile.tile_type.is_water();
+
+    // water blocks on its own, so only a wall glyph over it needs writing
+    if let Some((glyph, edges)) = WALL_GLYPH_EDGES.iter().find(|(glyph, _)| *glyph == chr) {
+        let standing =
+            glyph_walls(pos, edges).iter()
+                                   .all(|(wall_pos, edge, _)| map.is_within_bounds(*wall_pos) &&
+                                                              edge_wall(map, *wall_pos, *edge) != Wall::Empty);
+        if standing && (!tile.blocked || is_water) {
+            return Some(*glyph);
+        }
+    }
+
+    if !tile.blocked || is_water {
+        return None;
+    }
+
+    // these characters would be read back as something other than a wall
+    if [0, MAP_EMPTY, MAP_EMPTY_CHAR, MAP_COLUMN].contains(&chr) ||
+       WALL_GLYPH_EDGES.iter().any(|(glyph, _)| *glyph == chr) {
+        return Some(MAP_WALL);
+    }
+
+    return Some(chr);
+}
+
+/// Add glyphs for the walls not already drawn by a glyph kept from the original map,
+/// using the tile on either side of a wall if the tile it belongs to is taken.
+fn place_wall_glyphs(map: &Map, environment: &mut Vec<Option<u8>>) {
+    let height = map.height() as usize;
+    let index = |pos: Pos| pos.y as usize + height * pos.x as usize;
+
+    let mut drawn: Vec<(Pos, Edge)> = Vec::new();
+    for x in 0..map.width() {
+        for y in 0..map.height() {
+            let pos = Pos::new(x, y);
+            let glyph = environment[index(pos)];
+            if let Some((_, edges)) = WALL_GLYPH_EDGES.iter().find(|(wall_glyph, _)| Some(*wall_glyph) == glyph) {
+                drawn.extend(glyph_walls(pos, edges).iter().map(|(wall_pos, edge, _)| (*wall_pos, *edge)));
+            }
+        }
+    }
+
+    for x in 0..map.width() {
+        for y in 0..map.height() {
+            let pos = Pos::new(x, y);
+
+            let mut walls: Vec<(Pos, Edge, Wall)> =
+                vec!((pos, Edge::Left, map[pos].left_wall), (pos, Edge::Bottom, map[pos].bottom_wall))
+                    .into_iter()
+                    .filter(|(_, edge, wall)| *wall != Wall::Empty && !drawn.contains(&(pos, *edge)))
+                    .collect();
+            if walls.len() == 0 {
+                continue;
+            }
+
+            if environment[index(pos)].is_none() {
+                if let Some(glyph) = find_wall_glyph(&walls, pos) {
+                    environment[index(pos)] = Some(glyph);
+                    continue;
+                }
+            }
+
+            // tall walls can only be drawn from their own tile, so they are placed first
+            walls.sort_by_key(|(_, _, wall)| *wall != Wall::TallWall);
+            for wall in walls {
+                let neighbor = match wall.1 {
+                    Edge::Left => move_x(pos, -1),
+                    _ => move_y(pos, 1),
+                };
+
+                let cell =
+                    vec!(pos, neighbor).into_iter()
+                                       .filter(|cell| map.is_within_bounds(*cell) && environment[index(*cell)].is_none())
+                                       .find_map(|cell| find_wall_glyph(&[wall], cell).map(|glyph| (cell, glyph)));
+
+                if let Some((cell, glyph)) = cell {
+                    environment[index(cell)] = Some(glyph);
+                } else {
+                    trace!("no room for the wall at {:?} in map file", pos);
+                }
+            }
+        }
+    }
+}
+
+/// The walls a wall glyph at the given position puts down.
+fn glyph_walls(pos: Pos, edges: &[Wall; 4]) -> Vec<(Pos, Edge, Wall)> {
+    let locations =
+        [(pos, Edge::Left), (pos, Edge::Bottom), (move_y(pos, -1), Edge::Bottom), (move_x(pos, 1), Edge::Left)];
+
+    return edges.iter()
+                .zip(locations.iter())
+                .filter(|(wall, _)| **wall != Wall::Empty)
+                .map(|(wall, (wall_pos, edge))| (*wall_pos, *edge, *wall))
+                .collect();
+}
+
+/// Find a glyph that, placed at the given position, puts down exactly the given walls.
+fn find_wall_glyph(walls: &[(Pos, Edge, Wall)], pos: Pos) -> Option<u8> {
+    return WALL_GLYPH_EDGES.iter()
+                           .find(|(_, edges)| {
+                               let glyph_walls = glyph_walls(pos, edges);
+                               glyph_walls.len() == walls.len() &&
+                               walls.iter().all(|wall| glyph_walls.contains(wall))
+                           })
+                           .map(|(glyph, _)| *glyph);
+}
+
+fn edge_wall(map: &Map, pos: Pos, edge: Edge) -> Wall {
+    match edge {
+        Edge::Left => map[pos].left_wall,
+        Edge::Bottom => map[pos].bottom_wall,
+        Edge::Full => Wall::Empty,
+    }
+}
+
+/// The layer and glyph an entity is written with, if place_map_xp can read it back.
+fn entity_glyph(name: EntityName) -> Option<(usize, u8)> {
+    let glyph = match name {
+        EntityName::Column => return Some((MAP_LAYER_ENVIRONMENT, MAP_COLUMN)),
+        EntityName::Player => ENTITY_PLAYER,
+        EntityName::Gol => ENTITY_GOL,
+        EntityName::Pawn => ENTITY_ELF,
+        EntityName::Spire => ENTITY_CASTLE,
+        EntityName::Exit => ENTITY_EXIT,
+        EntityName::Key => ENTITY_KEY,
+        EntityName::Stone => ENTITY_STONE,
+        EntityName::Dagger => ENTITY_DAGGER,
+        EntityName::Sword => ENTITY_SWORD,
+        EntityName::Hammer => ENTITY_HAMMER,
+        EntityName::Shield => ENTITY_SHIELD,
+        EntityName::Spike => ENTITY_SPIKE_TRAP,
+        EntityName::Torch => ENTITY_TORCH,
+        EntityName::Lantern => ENTITY_LANTERN,
+        EntityName::HealingDraught => ENTITY_HEALING_DRAUGHT,
+        EntityName::SmokeBomb => ENTITY_SMOKE_BOMB,
+        EntityName::Noisemaker => ENTITY_NOISEMAKER,
+        _ => return None,
+    };
+
+    return Some((MAP_LAYER_ENTITIES, glyph));
+}
+
+#[cfg(test)]
+fn entities_on_map(data: &GameData) -> Vec<(EntityName, Pos)> {
+    let mut entities: Vec<(EntityName, Pos)> =
+        data.entities.ids.iter()
+                         .filter_map(|id| data.entities.pos.get(id).map(|pos| (data.entities.name[id], *pos)))
+                         .filter(|(_, pos)| data.map.is_within_bounds(*pos))
+                         .collect();
+    entities.sort_by_key(|(name, pos)| (pos.x, pos.y, format!("{:?}", name)));
+
+    return entities;
+}
+
+#[test]
+pub fn test_place_thin_wall_top_right() {
+    let config = Config::from_file("../config.yaml");
+    let mut game = Game::new(0, config.clone()).unwrap();
+    game.data.map = Map::from_dims(3, 3);
+
+    let (width, height) = (3, 3);
+    let mut xp = XpFile::new(width, height);
+    xp.layers = Vec::new();
+    let blank = XpCell { ch: MAP_EMPTY as u32, fg: XpColor { r: 255, g: 255, b: 255 }, bg: XpColor { r: 0, g: 0, b: 0 } };
+    for _ in 0..3 {
+        xp.layers.push(XpLayer { width, height, cells: vec![blank; width * height] });
+    }
+    for cell in xp.layers[MAP_LAYER_GROUND].cells.iter_mut() {
+        cell.ch = MAP_GROUND as u32;
+    }
+    xp.layers[MAP_LAYER_ENVIRONMENT].cells[1 + height * 1].ch = MAP_THIN_WALL_TOP_RIGHT as u32;
+
+    place_map_xp(&config, &mut game.data, &mut game.msg_log, &xp, Pos::new(0, 0));
+
+    // the right side of a tile is the left wall of the tile to its right
+    assert_eq!(Wall::ShortWall, game.data.map[(1, 0)].bottom_wall);
+    assert_eq!(Wall::ShortWall, game.data.map[(2, 1)].left_wall);
+    assert_eq!(Wall::Empty, game.data.map[(1, 1)].left_wall);
+    assert_eq!(Wall::Empty, game.data.map[(0, 1)].left_wall);
+}
+
+#[test]
+pub fn test_write_map_xp() {
+    let config = Config::from_file("../config.yaml");
+    let file_name = env::temp_dir().join("test_write_map_xp.xp");
+    let file_name = file_name.to_str().unwrap();
+
+    let mut original = Game::new(0, config.clone()).unwrap();
+    let player_pos = read_map_xp(&config, &mut original.data, &mut original.msg_log, "../resources/map.xp");
+    let player_id = original.data.find_player().unwrap();
+    original.data.entities.pos[&player_id] = player_pos.unwrap();
+
+    write_map_xp(&original.data, file_name).unwrap();
+
+    let mut loaded = Game::new(0, config.clone()).unwrap();
+    let loaded_player_pos = read_map_xp(&config, &mut loaded.data, &mut loaded.msg_log, file_name);
+    let player_id = loaded.data.find_player().unwrap();
+    loaded.data.entities.pos[&player_id] = loaded_player_pos.unwrap();
+
+    assert_eq!(player_pos, loaded_player_pos);
+    assert_eq!(original.data.map.width(), loaded.data.map.width());
+    assert_eq!(original.data.map.height(), loaded.data.map.height());
+
+    // everything read from a map file comes back exactly as it was
+    for (tile, loaded_tile) in original.data.map.tiles.iter().flatten().zip(loaded.data.map.tiles.iter().flatten()) {
+        assert_eq!(tile.blocked, loaded_tile.blocked);
+        assert_eq!(tile.block_sight, loaded_tile.block_sight);
+        assert_eq!(tile.tile_type, loaded_tile.tile_type);
+        assert_eq!(tile.left_wall, loaded_tile.left_wall);
+        assert_eq!(tile.bottom_wall, loaded_tile.bottom_wall);
+        assert_eq!(tile.surface, loaded_tile.surface);
+        assert_eq!(tile.chr, loaded_tile.chr);
+    }
+
+    assert!(entities_on_map(&original.data).len() > 0);
+    assert_eq!(entities_on_map(&original.data), entities_on_map(&loaded.data));
+}
+
+#[test]
+pub fn test_write_generated_map_xp() {
+    let config = Config::from_file("../config.yaml");
+    let file_name = env::temp_dir().join("test_write_generated_map_xp.xp");
+    let file_name = file_name.to_str().unwrap();
+
+    let mut game = Game::new(0, config.clone()).unwrap();
+    game.data.map = Map::from_dims(11, 12);
+    make_test_map(&mut game);
+
+    // walls of both heights on one tile need two glyphs
+    game.data.map[(5, 5)].left_wall = Wall::ShortWall;
+    game.data.map[(5, 5)].bottom_wall = Wall::TallWall;
+    game.data.map[(6, 2)].surface = Surface::Grass;
+    game.data.map[(6, 3)] = Tile::water();
+    game.data.map[(9, 1)] = Tile::exit();
+
+    write_map_xp(&game.data, file_name).unwrap();
+
+    let mut loaded = Game::new(0, config.clone()).unwrap();
+    read_map_xp(&config, &mut loaded.data, &mut loaded.msg_log, file_name);
+
+    for (tile, loaded_tile) in game.data.map.tiles.iter().flatten().zip(loaded.data.map.tiles.iter().flatten()) {
+        assert_eq!(tile.blocked, loaded_tile.blocked);
+        assert_eq!(tile.left_wall, loaded_tile.left_wall);
+        assert_eq!(tile.bottom_wall, loaded_tile.bottom_wall);
+        assert_eq!(tile.surface, loaded_tile.surface);
+        assert_eq!(tile.tile_type.is_water(), loaded_tile.tile_type.is_water());
+    }
+
+    // the exit tile comes back as an exit entity
+    let mut entities = entities_on_map(&game.data);
+    entities.push((EntityName::Exit, Pos::new(9, 1)));
+    entities.sort_by_key(|(name, pos)| (pos.x, pos.y, format!("{:?}", name)));
+    assert_eq!(entities, entities_on_map(&loaded.data));
+}
+
+#[test]
+pub fn test_write_map_xp_layers() {
+    let config = Config::from_file("../config.yaml");
+    let file_name = env::temp_dir().join("test_write_map_xp_layers.xp");
+    let file_name = file_name.to_str().unwrap();
+
+    let mut game = Game::new(0, config.clone()).unwrap();
+    game.data.map = Map::from_dims(8, 8);
+    game.data.map[(2, 2)].elevation = 2;
+    game.data.map[(3, 3)].elevation = 9;
+
+    let gol = make_gol(&mut game.data.entities, &config, Pos::new(2, 1), &mut game.msg_log);
+    let dagger = make_dagger(&mut game.data.entities, &config, Pos::new(2, 1), &mut game.msg_log);
+    pick_item_up(gol, dagger, &mut game.data.entities);
+    let stone = make_stone(&mut game.data.entities, &config, Pos::new(2, 1), &mut game.msg_log);
+    pick_item_up(gol, stone, &mut game.data.entities);
+
+    let plate = make_pressure_plate(&mut game.data.entities, &config, Pos::new(4, 1), &mut game.msg_log);
+    let door = make_door(&mut game.data.entities, &config, Pos::new(5, 1), &mut game.msg_log);
+    game.data.entities.link(plate, door);
+
+    let wire = make_tripwire(&mut game.data.entities, &config, Pos::new(1, 5), Pos::new(4, 5), &mut game.msg_log);
+    let cage = make_cage(&mut game.data.entities, &config, Pos::new(6, 5), &mut game.msg_log);
+    game.data.entities.link(wire, cage);
+
+    make_door(&mut game.data.entities, &config, Pos::new(6, 6), &mut game.msg_log);
+
+    write_map_xp(&game.data, file_name).unwrap();
+
+    let mut loaded = Game::new(0, config.clone()).unwrap();
+    read_map_xp(&config, &mut loaded.data, &mut loaded.msg_log, file_name);
+
+    for (tile, loaded_tile) in game.data.map.tiles.iter().flatten().zip(loaded.data.map.tiles.iter().flatten()) {
+        assert_eq!(tile.elevation, loaded_tile.elevation);
+    }
+
+    // the tripwire's second post does not become an entity of its own
+    assert_eq!(entities_on_map(&game.data), entities_on_map(&loaded.data));
+
+    let find = |name: EntityName, pos: Pos| {
+        return *loaded.data.entities.ids.iter()
+                                        .find(|id| loaded.data.entities.name[*id] == name && loaded.data.entities.pos[*id] == pos)
+                                        .unwrap();
+    };
+
+    // only the first item a monster carries fits in the loot layer
+    let gol = find(EntityName::Gol, Pos::new(2, 1));
+    let loot: Vec<EntityName> = loaded.data.entities.inventory[&gol].iter().map(|id| loaded.data.entities.name[id]).collect();
+    assert_eq!(vec!(EntityName::Dagger), loot);
+
+    let plate = find(EntityName::PressurePlate, Pos::new(4, 1));
+    assert_eq!(vec!(find(EntityName::Door, Pos::new(5, 1))), loaded.data.entities.trigger_effects(plate));
+
+    let wire = find(EntityName::Tripwire, Pos::new(1, 5));
+    assert_eq!(Trigger::Tripwire(Pos::new(4, 5)), loaded.data.entities.trigger[&wire]);
+    assert_eq!(vec!(find(EntityName::Cage, Pos::new(6, 5))), loaded.data.entities.trigger_effects(wire));
+
+    // a door with no trigger is not linked to anything
+    let lone_door = find(EntityName::Door, Pos::new(6, 6));
+    assert!(loaded.data.entities.links.values().all(|links| !links.contains(&lone_door)));
+}
+
+#[test]
+pub fn test_map_file_player_at_origin() {
+    let config = Config::from_file("../config.yaml");
+    let file_name = env::temp_dir().join("test_map_file_player_at_origin.txt");
+    let file_name = file_name.to_str().unwrap();
+    fs::write(file_name, " @  .  .\n .  .  .\n .  .  .\n").unwrap();
+
+    let mut game = Game::new(0, config.clone()).unwrap();
+    make_map(&MapConfig::from_file(file_name), &mut game);
+
+    // a player in the corner is still where the map put them
+    let player_id = game.data.find_player().unwrap();
+    assert_eq!(Pos::new(0, 0), game.data.entities.pos[&player_id]);
+
+    let num_players = game.data.entities.ids.iter().filter(|id| game.data.entities.name[*id] == EntityName::Player).count();
+    assert_eq!(1, num_players);
+}
@@ -0,0 +1,257 @@
+use rand::prelude::*;
+
+use roguelike_core::types::*;
+use roguelike_core::config::*;
+use roguelike_core::constants::*;
+use roguelike_core::messaging::MsgLog;
+use roguelike_core::utils::distance;
+
+use crate::generation::*;
+use crate::solvability::reachable_positions;
+#[cfg(test)]
+use roguelike_core::map::*;
+#[cfg(test)]
+use crate::game::Game;
+
+
+/// The monsters a level's threat budget can be spent on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MonsterKind {
+    Gol,
+    Elf,
+    Spire,
+}
+
+impl MonsterKind {
+    pub fn all() -> Vec<MonsterKind> {
+        return vec!(MonsterKind::Gol, MonsterKind::Elf, MonsterKind::Spire);
+    }
+
+    pub fn cost(&self, config: &Config) -> i32 {
+        match self {
+            MonsterKind::Gol => config.threat_cost_gol,
+            MonsterKind::Elf => config.threat_cost_elf,
+            MonsterKind::Spire => config.threat_cost_spire,
+        }
+    }
+
+    pub fn from_name(name: EntityName) -> Option<MonsterKind> {
+        match name {
+            EntityName::Gol => Some(MonsterKind::Gol),
+            EntityName::Pawn => Some(MonsterKind::Elf),
+            EntityName::Spire => Some(MonsterKind::Spire),
+            _ => None,
+        }
+    }
+
+    pub fn make(&self, entities: &mut Entities, config: &Config, pos: Pos, msg_log: &mut MsgLog) -> EntityId {
+        match self {
+            MonsterKind::Gol => make_gol(entities, config, pos, msg_log),
+            MonsterKind::Elf => make_elf(entities, config, pos, msg_log),
+            MonsterKind::Spire => make_spire(entities, config, pos, msg_log),
+        }
+    }
+}
+
+pub fn threat_budget(config: &Config, depth: usize) -> i32 {
+    return config.threat_budget + config.threat_budget_per_depth * depth as i32;
+}
+
+/// Fill a generated level with monsters, items and stones for the given depth.
+pub fn populate_level(data: &mut GameData,
+                      config: &Config,
+                      msg_log: &mut MsgLog,
+                      start: Pos,
+                      depth: usize,
+                      rng: &mut SmallRng) {
+    place_monsters(data, config, msg_log, start, depth, rng);
+    place_items(data, config, msg_log, start, depth, rng);
+}
+
+/// Spend the level's threat budget on monsters, keeping them away from the start.
+/// Part of the budget goes to guards around the key before the rest are spread out.
+/// Monsters already on the level, such as those placed in vaults, come out of the budget.
+/// Returns the threat spent.
+pub fn place_monsters(data: &mut GameData,
+                      config: &Config,
+                      msg_log: &mut MsgLog,
+                      start: Pos,
+                      depth: usize,
+                      rng: &mut SmallRng) -> i32 {
+    let existing: i32 =
+        data.entities.typ.iter()
+                         .filter(|(_, typ)| **typ == EntityType::Enemy)
+                         .filter_map(|(id, _)| MonsterKind::from_name(data.entities.name[id]))
+                         .map(|kind| kind.cost(config))
+                         .sum();
+    let budget = threat_budget(config, depth) - existing;
+
+    let positions: Vec<Pos> =
+        open_positions(data, start).into_iter()
+                                   .filter(|pos| data.has_entity(*pos).is_none())
+                                   .filter(|pos| distance(start, *pos) >= config.monster_min_start_distance)
+                                   .collect();
+
+    let key_positions: Vec<Pos> =
+        data.entities.item.iter()
+                          .filter(|(_, item)| **item == Item::Goal)
+                          .map(|(key, _)| data.entities.pos[key])
+                          .collect();
+    let guard_positions: Vec<Pos> =
+        positions.iter()
+                 .filter(|pos| key_positions.iter().any(|key_pos| distance(*key_pos, **pos) <= config.key_guard_radius))
+                 .map(|pos| *pos)
+                 .collect();
+
+    let guard_budget = (budget as f32 * config.key_guard_fraction) as i32;
+    let mut spent = spend_threat(data, config, msg_log, &guard_positions, guard_budget, rng);
+    spent += spend_threat(data, config, msg_log, &positions, budget - spent, rng);
+
+    return spent;
+}
+
+fn spend_threat(data: &mut GameData,
+                config: &Config,
+                msg_log: &mut MsgLog,
+                positions: &[Pos],
+                budget: i32,
+                rng: &mut SmallRng) -> i32 {
+    let mut spent = 0;
+
+    loop {
+        let num_monsters = data.entities.typ.values().filter(|typ| **typ == EntityType::Enemy).count();
+        if num_monsters >= MAX_MONSTERS as usize {
+            break;
+        }
+
+        let affordable: Vec<MonsterKind> =
+            MonsterKind::all().into_iter().filter(|kind| kind.cost(config) <= budget - spent).collect();
+
+        let free: Vec<Pos> =
+            positions.iter().filter(|pos| data.has_blocking_entity(**pos).is_none()).map(|pos| *pos).collect();
+
+        if let (Some(kind), Some(pos)) = (affordable.choose(rng), free.choose(rng)) {
+            kind.make(&mut data.entities, config, *pos, msg_log);
+            spent += kind.cost(config);
+        } else {
+            break;
+        }
+    }
+
+    return spent;
+}
+
+/// Scatter stones and consumable items around the level, with their
+/// number of each given by a density per open tile that changes with depth.
+/// Returns the number of stones and items placed.
+pub fn place_items(data: &mut GameData,
+                   config: &Config,
+                   msg_log: &mut MsgLog,
+                   start: Pos,
+                   depth: usize,
+                   rng: &mut SmallRng) -> usize {
+    let mut positions: Vec<Pos> =
+        open_positions(data, start).into_iter()
+                                   .filter(|pos| *pos != start && data.has_entity(*pos).is_none())
+                                   .collect();
+    positions.shuffle(rng);
+
+    let count = |density: f32, density_per_depth: f32| {
+        let density = (density + density_per_depth * depth as f32).max(0.0);
+        return (positions.len() as f32 * density).round() as usize;
+    };
+    let num_stones = count(config.stone_density, config.stone_density_per_depth);
+    let num_items = count(config.item_density, config.item_density_per_depth);
+
+    let mut placed = 0;
+    for pos in positions.iter().take(num_stones + num_items) {
+        if placed < num_stones {
+            make_stone(&mut data.entities, config, *pos, msg_log);
+        } else {
+            match rng.gen_range(0, 3) {
+                0 => make_healing_draught(&mut data.entities, config, *pos, msg_log),
+                1 => make_smoke_bomb(&mut data.entities, config, *pos, msg_log),
+                _ => make_noisemaker(&mut data.entities, config, *pos, msg_log),
+            };
+        }
+        placed += 1;
+    }
+
+    return placed;
+}
+
+/// Open floor the player can get to from the start, in a fixed order.
+fn open_positions(data: &GameData, start: Pos) -> Vec<Pos> {
+    let swims = data.find_player().map_or(false, |player_id| data.entities.can_swim(player_id));
+
+    let mut positions: Vec<Pos> =
+        reachable_positions(&data.map, start, swims).into_iter()
+                                                    .filter(|pos| !data.map[*pos].blocked && data.map.is_empty(*pos))
+                                                    .collect();
+    positions.sort_by_key(|pos| (pos.x, pos.y));
+
+    return positions;
+}
+
+#[test]
+pub fn test_threat_budget() {
+    let mut config = Config::from_file("../config.yaml");
+    config.threat_budget = 6;
+    config.threat_budget_per_depth = 2;
+    config.threat_cost_gol = 2;
+    config.threat_cost_elf = 2;
+    config.threat_cost_spire = 2;
+    config.key_guard_fraction = 0.5;
+    config.key_guard_radius = 3;
+    config.monster_min_start_distance = 5;
+
+    let mut game = Game::new(0, config.clone()).unwrap();
+    game.data.map = Map::from_dims(20, 20);
+    let start = Pos::new(1, 1);
+    let key_pos = Pos::new(15, 15);
+    make_key(&mut game.data.entities, &config, key_pos, &mut game.msg_log);
+
+    let spent = place_monsters(&mut game.data, &config, &mut game.msg_log, start, 1, &mut game.rng);
+    assert_eq!(8, spent);
+
+    let monsters: Vec<Pos> =
+        game.data.entities.typ.iter()
+                              .filter(|(_, typ)| **typ == EntityType::Enemy)
+                              .map(|(id, _)| game.data.entities.pos[id])
+                              .collect();
+    assert_eq!(4, monsters.len());
+
+    // no monster starts near the player, and half the budget guards the key
+    assert!(monsters.iter().all(|pos| distance(start, *pos) >= 5));
+    assert!(monsters.iter().filter(|pos| distance(key_pos, **pos) <= 3).count() >= 2);
+
+    // the budget grows with depth
+    assert!(threat_budget(&config, 3) > threat_budget(&config, 1));
+
+    // monsters that are already placed are paid for out of the budget
+    let mut game = Game::new(0, config.clone()).unwrap();
+    game.data.map = Map::from_dims(20, 20);
+    make_key(&mut game.data.entities, &config, key_pos, &mut game.msg_log);
+    make_gol(&mut game.data.entities, &config, Pos::new(10, 10), &mut game.msg_log);
+
+    let spent = place_monsters(&mut game.data, &config, &mut game.msg_log, start, 1, &mut game.rng);
+    assert_eq!(6, spent);
+}
+
+#[test]
+pub fn test_item_density() {
+    let mut config = Config::from_file("../config.yaml");
+    config.stone_density = 0.01;
+    config.stone_density_per_depth = 0.01;
+    config.item_density = 0.0;
+    config.item_density_per_depth = 0.01;
+
+    let num_placed = |depth: usize| {
+        let mut game = Game::new(0, config.clone()).unwrap();
+        game.data.map = Map::from_dims(20, 20);
+        return place_items(&mut game.data, &config, &mut game.msg_log, Pos::new(1, 1), depth, &mut game.rng);
+    };
+
+    assert_eq!(4, num_placed(0));
+    assert!(num_placed(2) > num_placed(0));
+}
@@ -5,13 +5,15 @@ use roguelike_core::ai::{Behavior};
 use roguelike_core::map::{Surface, AoeEffect};
 use roguelike_core::messaging::{MsgLog, Msg};
 use roguelike_core::constants::*;
-use roguelike_core::movement::{MoveMode, MoveType, Action, Attack, Movement, Direction, Reach};
+use roguelike_core::movement::{MoveMode, MoveType, Action, Attack, Movement, Reach};
 use roguelike_core::config::*;
 use roguelike_core::utils::*;
 use roguelike_core::map::*;
 
 use crate::game::*;
 use crate::actions::{throw_item, pick_item_up, place_trap};
+use crate::items::{use_item, throw_behavior, thrown_item_hit};
+use crate::generation::make_fire;
 
 
 pub fn resolve_messages(data: &mut GameData, msg_log: &mut MsgLog, settings: &mut GameSettings, config: &Config) {
@@ -25,6 +27,11 @@ pub fn resolve_messages(data: &mut GameData, msg_log: &mut MsgLog, settings: &mu
 
         match msg {
             Msg::Crushed(entity_id, pos) => {
+                // anything crushed into a wall damages it
+                if data.map[pos].blocked {
+                    msg_log.log(Msg::DamageTerrain(entity_id, pos, Edge::Full, config.crush_terrain_damage));
+                }
+
                 data.map[pos].surface = Surface::Rubble;
 
                 if let Some(crushed_id) = data.has_entity(pos) {
@@ -56,32 +63,56 @@ pub fn resolve_messages(data: &mut GameData, msg_log: &mut MsgLog, settings: &mu
             }
 
             Msg::ItemThrow(entity_id, item_id, start, end) => {
-                throw_item(entity_id, item_id, start, end, data);
+                data.entities.remove_item(entity_id, item_id);
+                let hit_entity = throw_item(entity_id, item_id, start, end, data, config);
+                let land_pos = data.entities.pos[&item_id];
+
+                if let Some(hit_id) = hit_entity {
+                    thrown_item_hit(entity_id, item_id, hit_id, data, config, msg_log);
+                }
+
+                let sound_radius = throw_behavior(data.entities.item[&item_id], config).sound_radius;
+                msg_log.log_front(Msg::ItemLanded(item_id, start, land_pos, sound_radius));
+                msg_log.log_front(Msg::Sound(entity_id, land_pos, sound_radius, false));
 
-                // NOTE the radius here is the stone radius, regardless of item type
-                msg_log.log_front(Msg::Sound(entity_id, end, SOUND_RADIUS_STONE, false));
+                // lanterns break where they land, setting the ground alight
+                if data.entities.item[&item_id] == Item::Lantern {
+                    data.entities.needs_removal[&item_id] = true;
+                    msg_log.log(Msg::Ignite(entity_id, land_pos));
+                }
+            }
+
+            Msg::Ignite(entity_id, pos) => {
+                // only grass burns, and it is burnt away as it catches
+                if data.map.is_within_bounds(pos) && data.map[pos].surface == Surface::Grass {
+                    data.map[pos].surface = Surface::Burnt;
+                    make_fire(&mut data.entities, config, pos, msg_log);
+
+                    msg_log.log_front(Msg::Sound(entity_id, pos, config.sound_radius_fire, true));
+                }
+            }
+
+            Msg::Burned(fire, entity_id, damage) => {
+                damage_entity(fire, entity_id, damage, &mut data.entities, msg_log);
             }
 
             Msg::JumpWall(entity_id, _start, end) => {
                     msg_log.log_front(Msg::Sound(entity_id, end, config.sound_radius_run, true));
             }
 
+            Msg::WallKick(entity_id, pos) => {
+                msg_log.log_front(Msg::Sound(entity_id, pos, config.sound_radius_wall_kick, true));
+            }
+
             Msg::Pushed(pusher, pushed, delta_pos) => {
                 let pushed_pos = data.entities.pos[&pushed];
                 let pusher_pos = data.entities.pos[&pusher];
 
                 if data.entities.typ[&pushed] == EntityType::Column {
-
-                    let entity_diff = sub_pos(pushed_pos, pusher_pos);
-                    let next_pos = next_pos(pusher_pos, entity_diff);
-                    let diff = sub_pos(pushed_pos, next_pos);
-                    let blocked = data.map.is_blocked_by_wall(pushed_pos, diff.x, diff.y); 
-
-                    if blocked == None {
-                        data.remove_entity(pushed);
-
-                        msg_log.log(Msg::Crushed(pusher, next_pos));
-                    }
+                    // the column falls away from the pusher before they step into its place
+                    let diff = sub_pos(pushed_pos, pusher_pos);
+                    let direction = Pos::new(signedness(diff.x), signedness(diff.y));
+                    msg_log.log_front(Msg::Toppled(pusher, pushed, direction));
                 } else if data.entities.alive[&pushed] {
                     push_attack(pusher, pushed, delta_pos, true, data, msg_log);
                 } else {
@@ -90,14 +121,46 @@ pub fn resolve_messages(data: &mut GameData, msg_log: &mut MsgLog, settings: &mu
                 }
             }
 
+            Msg::Toppled(_cause, column, direction) => {
+                topple_column(column, direction, data, msg_log, config);
+            }
+
+            Msg::Slammed(pushed, hit) => {
+                for (entity_id, other_id) in vec!((pushed, hit), (hit, pushed)) {
+                    damage_entity(other_id, entity_id, config.push_collision_damage, &mut data.entities, msg_log);
+                }
+
+                let pos = data.entities.pos[&hit];
+                msg_log.log_front(Msg::Sound(pushed, pos, config.sound_radius_attack, true));
+            }
+
+            Msg::Drowned(pusher, drowned, pos) => {
+                data.entities.move_to(drowned, pos);
+
+                let damage = data.entities.fighter.get(&drowned).map_or(0, |fighter| fighter.hp);
+                data.entities.alive[&drowned] = false;
+                data.entities.blocks[&drowned] = false;
+
+                msg_log.log_front(Msg::Killed(pusher, drowned, damage));
+            }
+
             Msg::Moved(entity_id, movement, pos) => {
                 // only perform move if tile does not contain a wall or entity
                 if data.has_blocking_entity(movement.pos).is_none() &&
-                   !data.map[movement.pos].blocked {
+                   (!data.map[movement.pos].blocked || data.can_swim_to(entity_id, movement.pos)) {
                        process_moved_message(entity_id, movement, pos, data, msg_log, config);
                 }
             }
 
+            Msg::Fell(entity_id, pos, height) => {
+                msg_log.log_front(Msg::Sound(entity_id, pos, config.sound_radius_fall, true));
+
+                if height > config.safe_fall_height {
+                    let damage = (height - config.safe_fall_height) as i32 * config.fall_damage;
+                    damage_entity(entity_id, entity_id, damage, &mut data.entities, msg_log);
+                }
+            }
+
             Msg::Yell(entity_id, pos) => {
                 msg_log.log_front(Msg::Sound(entity_id, pos, config.yell_radius, true));
             }
@@ -106,7 +169,18 @@ pub fn resolve_messages(data: &mut GameData, msg_log: &mut MsgLog, settings: &mu
                 if data.entities.typ[&attacked] != EntityType::Player {
                     let pos = data.entities.pos[&attacked];
 
-                    data.map[pos].surface = Surface::Rubble;
+                    if !data.map[pos].tile_type.is_water() {
+                        data.map[pos].surface = Surface::Rubble;
+                    }
+
+                    // monsters drop everything they were carrying
+                    let carried = data.entities.inventory.get(&attacked).map_or(Vec::new(), |inventory| {
+                        inventory.iter().map(|item_id| *item_id).collect::<Vec<EntityId>>()
+                    });
+                    for item_id in carried {
+                        data.entities.drop_item(attacked, item_id);
+                        msg_log.log(Msg::ItemDropped(attacked, item_id));
+                    }
                 }
 
                 if let Some(fighter) = data.entities.fighter.get_mut(&attacked) {
@@ -137,7 +211,7 @@ pub fn resolve_messages(data: &mut GameData, msg_log: &mut MsgLog, settings: &mu
                 }
 
                 if any_hit_entity {
-                    data.used_up_item(entity_id);
+                    wear_held_item(entity_id, config.wear_hit_entity, data, msg_log);
                 }
             }
 
@@ -147,11 +221,11 @@ pub fn resolve_messages(data: &mut GameData, msg_log: &mut MsgLog, settings: &mu
 
                 if let Some(blocked) = data.map.is_blocked_by_wall(entity_pos, pos_diff.x, pos_diff.y) {
                     msg_log.log_front(Msg::HammerHitWall(entity, blocked));
-                    data.used_up_item(entity);
+                    wear_held_item(entity, config.wear_hammer_wall, data, msg_log);
                 } else if let Some(hit_entity) = data.has_blocking_entity(pos) {
                     // we hit another entity!
                     msg_log.log_front(Msg::HammerHitEntity(entity, hit_entity));
-                    data.used_up_item(entity);
+                    wear_held_item(entity, config.wear_hit_entity, data, msg_log);
                 }
             }
 
@@ -170,52 +244,38 @@ pub fn resolve_messages(data: &mut GameData, msg_log: &mut MsgLog, settings: &mu
             }
 
             Msg::HammerHitWall(entity, blocked) => {
-                let entity_pos = data.entities.pos[&entity];
-                let hit_pos = blocked.end_pos;
-                if data.map[hit_pos].blocked {
-                    if data.map[hit_pos].surface == Surface::Floor {
-                        data.map[hit_pos].surface = Surface::Rubble;
-                    }
-
-                    data.map[hit_pos].blocked = false;
-                    data.map[hit_pos].chr = ' ' as u8;
+                if let Some((hit_pos, edge)) = blocked.terrain() {
+                    msg_log.log_front(Msg::DamageTerrain(entity, hit_pos, edge, config.hammer_terrain_damage));
+                }
+            }
 
-                    let next_pos = next_from_to(entity_pos, hit_pos);
-                    msg_log.log_front(Msg::Crushed(entity, next_pos)); 
-                    msg_log.log_front(Msg::Sound(entity, blocked.end_pos, config.sound_radius_attack, true)); 
-                } else {
-                    let wall_loc: Pos;
-                    let left_wall: bool;
-                    if blocked.direction == Direction::Left {
-                         wall_loc = blocked.start_pos;
-                         left_wall = true;
-                    } else if blocked.direction == Direction::Right {
-                         wall_loc = blocked.end_pos;
-                         left_wall = true;
-                     } else if blocked.direction == Direction::Down {
-                         wall_loc = blocked.start_pos;
-                         left_wall = false;
-                     } else if blocked.direction == Direction::Up {
-                         wall_loc = blocked.end_pos;
-                         left_wall = false;
-                     } else {
-                        panic!(format!("Hammer direction was not up/down/left/right ({:?})!", blocked.direction));
-                     }
-
-                    if left_wall {
-                        data.map[wall_loc].left_wall = Wall::Empty;
-                    } else {
-                        data.map[wall_loc].bottom_wall = Wall::Empty;
-                    }
+            Msg::DamageTerrain(entity, pos, edge, amount) => {
+                if data.map.damage_terrain(pos, edge, amount) {
+                    msg_log.log_front(Msg::TerrainDestroyed(entity, pos, edge));
+                }
+            }
 
-                    msg_log.log(Msg::Crushed(entity, blocked.end_pos));
+            Msg::TerrainDestroyed(entity, pos, edge) => {
+                // the debris falls away from whatever destroyed the terrain, crushing what is there
+                let entity_pos = data.entities.pos[&entity];
+                let fall_pos = debris_pos(entity_pos, pos, edge);
+                if data.map.is_within_bounds(fall_pos) {
+                    msg_log.log_front(Msg::Crushed(entity, fall_pos));
                 }
+                msg_log.log_front(Msg::Sound(entity, pos, config.sound_radius_attack, true));
             }
 
             Msg::Action(entity_id, action) => {
                 let entity_pos = data.entities.pos[&entity_id];
 
                 if let Action::Move(movement) = action {
+                    if let MoveType::WallKick(dir_x, dir_y) = movement.typ {
+                        // kicking off of a wall redirects momentum rather than losing it
+                        if let Some(momentum) = data.entities.momentum.get_mut(&entity_id) {
+                            momentum.set_momentum(momentum.mx.abs() * dir_x, momentum.my.abs() * dir_y);
+                        }
+                    }
+
                     if let Some(attack_field) = movement.attack {
                         match attack_field {
                             Attack::Attack(target_id) => {
@@ -226,7 +286,7 @@ pub fn resolve_messages(data: &mut GameData, msg_log: &mut MsgLog, settings: &mu
                                 stab(entity_id, target_id, &mut data.entities, msg_log);
 
                                 if data.using(entity_id, Item::Dagger) {
-                                    data.used_up_item(entity_id);
+                                    wear_held_item(entity_id, config.wear_hit_entity, data, msg_log);
                                 }
 
                                 if entity_pos != movement.pos {
@@ -239,6 +299,10 @@ pub fn resolve_messages(data: &mut GameData, msg_log: &mut MsgLog, settings: &mu
                                 msg_log.log(Msg::Moved(entity_id, movement, movement.pos));
                             }
                         }
+
+                        if let MoveType::WallKick(_dir_x, _dir_y) = movement.typ {
+                            msg_log.log(Msg::WallKick(entity_id, movement.pos));
+                        }
                     } else if movement.attack.is_none() {
                         match movement.typ {
                             MoveType::Collide => {
@@ -252,17 +316,18 @@ pub fn resolve_messages(data: &mut GameData, msg_log: &mut MsgLog, settings: &mu
                             }
 
                             MoveType::WallKick(_dir_x, _dir_y) => {
-                                data.entities.move_to(entity_id, movement.pos);
-
-                                // TODO could check for enemy and attack
-                                msg_log.log(Msg::WallKick(entity_id, movement.pos));
+                                if data.clear_path(entity_pos, movement.pos) {
+                                    msg_log.log(Msg::Moved(entity_id, movement, movement.pos));
+                                    msg_log.log(Msg::WallKick(entity_id, movement.pos));
+                                }
                             }
 
                             MoveType::Move | MoveType::JumpWall => {
                                 // TODO what about if the entity is moved (say, pushed)?
                                 // should check for this, and no do the move at all, likely
                                 if entity_pos != movement.pos {
-                                    if data.clear_path(entity_pos, movement.pos) {
+                                    let swimming = movement.typ == MoveType::Move && data.can_swim_to(entity_id, movement.pos);
+                                    if data.clear_path(entity_pos, movement.pos) || swimming {
                                         if movement.typ == MoveType::Move {
                                             msg_log.log(Msg::Moved(entity_id, movement, movement.pos));
                                         } else {
@@ -270,6 +335,7 @@ pub fn resolve_messages(data: &mut GameData, msg_log: &mut MsgLog, settings: &mu
                                         }
                                     } else if movement.typ == MoveType::JumpWall {
                                         // no clear path to moved position
+                                        update_momentum(entity_id, movement.pos, data);
                                         data.entities.move_to(entity_id, movement.pos);
                                     } else {
                                         // TODO move towards position, perhaps emitting a Collide
@@ -291,26 +357,67 @@ pub fn resolve_messages(data: &mut GameData, msg_log: &mut MsgLog, settings: &mu
                 } else if let Action::Yell = action {
                     msg_log.log(Msg::Yell(entity_id, entity_pos));
                 } else if let Action::Pass = action {
+                    // standing still loses any built up momentum
+                    if let Some(momentum) = data.entities.momentum.get_mut(&entity_id) {
+                        momentum.clear();
+                    }
+
                     msg_log.log(Msg::Pass());
                 } else if let Action::ThrowItem(throw_pos, item_id) = action {
-                    msg_log.log(Msg::ItemThrow(entity_id, item_id, entity_pos, throw_pos));
+                    // there is no way to throw while swimming in deep water
+                    if data.map[entity_pos].tile_type == TileType::Water {
+                        msg_log.log(Msg::TriedThrowSwimming(entity_id));
+                    } else {
+                        let item_id = take_from_stack(item_id, data, msg_log);
+                        msg_log.log(Msg::ItemThrow(entity_id, item_id, entity_pos, throw_pos));
+                    }
                 } else if let Action::Pickup(item_id) = action {
-                    msg_log.log(Msg::PickedUp(entity_id, item_id));
-                } else if let Action::UseItem(pos) = action {
-                    if data.using(entity_id, Item::Hammer) {
-                        msg_log.log(Msg::HammerSwing(entity_id, pos));
-                    } else if data.using(entity_id, Item::Sword) {
-                        msg_log.log(Msg::SwordSwing(entity_id, pos));
+                    if data.entities.can_carry(entity_id, item_id) {
+                        msg_log.log(Msg::PickedUp(entity_id, item_id));
+                    } else {
+                        msg_log.log(Msg::InventoryFull(entity_id, item_id));
                     }
+                } else if let Action::UseItem(item_id, pos) = action {
+                    use_item(entity_id, item_id, pos, data, config, msg_log);
                 } else if let Action::ArmDisarmTrap(trap_id) = action {
                     data.entities.armed[&trap_id] = !data.entities.armed[&trap_id];
                 } else if let Action::PlaceTrap(place_pos, trap_id) = action {
+                    let trap_id = take_from_stack(trap_id, data, msg_log);
+                    data.entities.remove_item(entity_id, trap_id);
                     place_trap(trap_id, place_pos, data);
+                } else if let Action::Climb(climb_pos) = action {
+                    if data.map.can_climb(entity_pos, climb_pos) &&
+                       data.has_blocking_entity(climb_pos).is_none() {
+                        msg_log.log(Msg::Climbed(entity_id, climb_pos));
+
+                        let movement = Movement::new(climb_pos, MoveType::Move, None);
+                        msg_log.log(Msg::Moved(entity_id, movement, climb_pos));
+                    }
                 }
             }
 
+            Msg::ItemBroke(entity_id, item_id) => {
+                // broken items are left behind as debris
+                let pos = data.entities.pos[&entity_id];
+                data.entities.remove_item(entity_id, item_id);
+                data.remove_entity(item_id);
+
+                data.map[pos].surface = Surface::Rubble;
+            }
+
             Msg::PickedUp(entity_id, item_id) => {
-                pick_item_up(entity_id, item_id, &mut data.entities);
+                let stack_id = pick_item_up(entity_id, item_id, &mut data.entities);
+                if stack_id != item_id {
+                    msg_log.log(Msg::StackChanged(stack_id, data.entities.stack_count(stack_id)));
+                }
+
+                // picking up too much slows a running entity down
+                if data.entities.encumbered(entity_id) &&
+                   data.entities.move_mode.get(&entity_id) == Some(&MoveMode::Run) {
+                    data.entities.move_mode[&entity_id] = MoveMode::Walk;
+                    data.entities.movement[&entity_id] = reach_by_mode(MoveMode::Walk);
+                    msg_log.log(Msg::MoveMode(MoveMode::Walk));
+                }
             }
 
             Msg::StateChange(entity_id, behavior) => {
@@ -328,11 +435,41 @@ pub fn resolve_messages(data: &mut GameData, msg_log: &mut MsgLog, settings: &mu
                 }
             }
 
+            Msg::Triggered(trigger, entity_id) => {
+                set_off_trigger(trigger, entity_id, data, msg_log);
+            }
+
             Msg::SoundTrapTriggered(trap, entity) => {
                 let source_pos = data.entities.pos[&trap];
 
                 // the triggering entity is considered the source of the sound
-                Msg::Sound(entity, source_pos, config.sound_radius_trap, true);
+                msg_log.log_front(Msg::Sound(entity, source_pos, config.sound_radius_trap, true));
+            }
+
+            Msg::DoorClosed(door) => {
+                let door_pos = data.entities.pos[&door];
+
+                // a door can't close on something standing in its way
+                if data.has_blocking_entity(door_pos).is_none() {
+                    data.map[door_pos] = Tile::wall();
+                    data.map.update_map();
+
+                    data.entities.armed[&door] = false;
+                    data.entities.needs_removal[&door] = true;
+                }
+            }
+
+            Msg::CageOpened(cage, target_pos) => {
+                let cage_pos = data.entities.pos[&cage];
+
+                for (key, pos) in data.entities.pos.iter() {
+                    if *pos == cage_pos && data.entities.ai.get(key).is_some() {
+                        data.entities.behavior[key] = Behavior::Investigating(target_pos);
+                    }
+                }
+
+                data.entities.armed[&cage] = false;
+                data.entities.needs_removal[&cage] = true;
             }
 
             _ => {
@@ -359,7 +496,67 @@ pub fn resolve_messages(data: &mut GameData, msg_log: &mut MsgLog, settings: &mu
     data.entities.messages[&player_id].clear();
 }
 
+fn wear_held_item(entity_id: EntityId, amount: i32, data: &mut GameData, msg_log: &mut MsgLog) {
+    if let Some(item_id) = data.wear_item(entity_id, amount) {
+        msg_log.log(Msg::ItemBroke(entity_id, item_id));
+    }
+}
+
+// throwing or placing an item only takes one item off of a stack
+fn take_from_stack(item_id: EntityId, data: &mut GameData, msg_log: &mut MsgLog) -> EntityId {
+    let split_id = data.entities.split_stack(item_id);
+
+    if split_id != item_id {
+        msg_log.log(Msg::SpawnedObject(split_id,
+                                       data.entities.typ[&split_id],
+                                       data.entities.pos[&split_id],
+                                       data.entities.name[&split_id]));
+        msg_log.log(Msg::StackChanged(item_id, data.entities.stack_count(item_id)));
+    }
+
+    return split_id;
+}
+
+// running builds momentum, while any other movement (or not moving) loses it
+fn update_momentum(entity_id: EntityId, pos: Pos, data: &mut GameData) {
+    let delta = sub_pos(pos, data.entities.pos[&entity_id]);
+    let running = data.entities.move_mode.get(&entity_id) == Some(&MoveMode::Run);
+
+    if let Some(momentum) = data.entities.momentum.get_mut(&entity_id) {
+        if running && delta != Pos::new(0, 0) {
+            momentum.ran(delta.x, delta.y);
+        } else {
+            momentum.clear();
+        }
+    }
+}
+
 fn process_moved_message(entity_id: EntityId, movement: Movement, pos: Pos, data: &mut GameData, msg_log: &mut MsgLog, config: &Config) {
+    update_momentum(entity_id, pos, data);
+
+    // heavy items can't be carried while swimming, so they are left behind on the shore
+    if data.map[pos].tile_type == TileType::Water {
+        let heavy_items: Vec<EntityId> =
+            data.entities.inventory.get(&entity_id).map_or(Vec::new(), |inventory| {
+                inventory.iter()
+                         .filter(|item_id| data.entities.item[*item_id].heavy())
+                         .map(|item_id| *item_id)
+                         .collect()
+            });
+
+        for item_id in heavy_items {
+            data.entities.drop_item(entity_id, item_id);
+            msg_log.log(Msg::ItemDropped(entity_id, item_id));
+        }
+    }
+
+    // dropping off a ledge makes a noise, and can hurt
+    let start_pos = data.entities.pos[&entity_id];
+    let drop_height = data.map.drop_height(start_pos, pos);
+    if drop_height > 0 {
+        msg_log.log(Msg::Fell(entity_id, pos, drop_height));
+    }
+
     data.entities.move_to(entity_id, pos);
 
     // if running, but didn't move any squares, then decrease speed
@@ -385,35 +582,103 @@ fn process_moved_message(entity_id: EntityId, movement: Movement, pos: Pos, data
             sound_radius -= config.sound_grass_radius;
         }
 
+        // wading through shallow water splashes
+        if data.map[pos].tile_type == TileType::ShallowWater {
+            sound_radius += config.sound_radius_shallow_water;
+        }
+
+        if data.entities.encumbered(entity_id) {
+            sound_radius += config.sound_radius_encumbered;
+        }
+
         msg_log.log_front(Msg::Sound(entity_id, pos, sound_radius, true));
     } else {
         msg_log.log_front(Msg::Sound(entity_id, pos, SOUND_RADIUS_MONSTER_MOVE, true));
     }
 
-    // get a list of triggered traps
-    let mut traps: Vec<EntityId> = Vec::new();
+    // get a list of triggers the entity set off
+    let mut triggers: Vec<EntityId> = Vec::new();
     for key in data.entities.ids.iter() {
-        if data.entities.trap.get(key).is_some()           && // key is a trap
-           data.entities.armed.get(key) == Some(&true)     && // trap is armed
+        if data.entities.trigger.get(key).is_some()        && // key is a trigger
+           data.entities.armed.get(key) == Some(&true)     && // trigger is armed
            data.entities.alive[&entity_id]                 && // entity is alive
            data.entities.fighter.get(&entity_id).is_some() && // entity is a fighter
-           data.entities.pos[key] == data.entities.pos[&entity_id] {
-            traps.push(*key);
+           data.entities.triggered_at(*key, data.entities.pos[&entity_id]) {
+            triggers.push(*key);
+        }
+    }
+
+    for trigger in triggers.iter() {
+        msg_log.log(Msg::Triggered(*trigger, entity_id));
+    }
+}
+
+/// A toppled column falls along its length, crushing each tile it lands on. It stops
+/// early when it hits a wall, or another column, which then topples in turn.
+fn topple_column(column: EntityId, direction: Pos, data: &mut GameData, msg_log: &mut MsgLog, config: &Config) {
+    let mut pos = data.entities.pos[&column];
+
+    // the column is removed at the end of the turn, so the messages below can still refer to it
+    data.entities.blocks[&column] = false;
+    data.entities.needs_removal[&column] = true;
+
+    for _ in 0..config.column_fall_length {
+        if let Some(blocked) = data.map.is_blocked_by_wall(pos, direction.x, direction.y) {
+            if let Some((wall_pos, edge)) = blocked.terrain() {
+                msg_log.log(Msg::DamageTerrain(column, wall_pos, edge, config.crush_terrain_damage));
+            }
+            break;
         }
+
+        pos = add_pos(pos, direction);
+
+        if let Some(other_id) = data.has_blocking_entity(pos) {
+            if data.entities.typ[&other_id] == EntityType::Column {
+                msg_log.log(Msg::Toppled(column, other_id, direction));
+                break;
+            }
+        }
+
+        msg_log.log(Msg::Crushed(column, pos));
     }
+}
+
+fn set_off_trigger(trigger_id: EntityId, entity_id: EntityId, data: &mut GameData, msg_log: &mut MsgLog) {
+    let trigger_pos = data.entities.pos[&trigger_id];
+
+    for effect_id in data.entities.trigger_effects(trigger_id) {
+        let trap = data.entities.trap.get(&effect_id).map(|trap| *trap);
+        if trap.is_none() || data.entities.armed.get(&effect_id) != Some(&true) {
+            continue;
+        }
 
-    // Check if the entity hit a trap
-    for trap in traps.iter() {
-        match data.entities.trap[trap] {
+        let effect_pos = data.entities.pos[&effect_id];
+        match trap.unwrap() {
             Trap::Spikes => {
-                msg_log.log(Msg::SpikeTrapTriggered(*trap, entity_id));
-                data.entities.needs_removal[trap] = true;
+                // spikes hit whoever is standing on them
+                let hit_entity = data.has_blocking_entity(effect_pos)
+                                     .filter(|hit_id| data.entities.fighter.get(hit_id).is_some());
+                if let Some(hit_id) = hit_entity {
+                    msg_log.log(Msg::SpikeTrapTriggered(effect_id, hit_id));
+                }
             }
 
             Trap::Sound => {
-                msg_log.log(Msg::SoundTrapTriggered(*trap, entity_id));
-                data.entities.needs_removal[trap] = true;
+                msg_log.log(Msg::SoundTrapTriggered(effect_id, entity_id));
+            }
+
+            Trap::Door => {
+                msg_log.log(Msg::DoorClosed(effect_id));
+            }
+
+            Trap::Cage => {
+                msg_log.log(Msg::CageOpened(effect_id, trigger_pos));
             }
         }
     }
+
+    // trap items are used up when they go off
+    if data.entities.item.get(&trigger_id).is_some() {
+        data.entities.needs_removal[&trigger_id] = true;
+    }
 }
@@ -0,0 +1,243 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use roguelike_core::types::*;
+use roguelike_core::map::*;
+use roguelike_core::movement::Direction;
+use roguelike_core::messaging::MsgLog;
+use roguelike_core::config::Config;
+use roguelike_core::utils::add_pos;
+
+use crate::generation::make_player;
+use crate::make_map::read_map_xp;
+#[cfg(test)]
+use roguelike_core::constants::*;
+#[cfg(test)]
+use crate::game::Game;
+#[cfg(test)]
+use crate::generation::{make_key, make_island};
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolvabilityError {
+    MissingKey,
+    MissingExit,
+    Unreachable(Pos, Pos), // start, position that can't be reached from it
+}
+
+/// Check that the player, a key and an exit are all reachable from each other,
+/// so the key can be collected and taken to the exit.
+pub fn check_solvable(data: &GameData, player_pos: Pos) -> Result<(), SolvabilityError> {
+    let swims = data.find_player().map_or(false, |player_id| data.entities.can_swim(player_id));
+
+    let keys = key_positions(data);
+    if keys.len() == 0 {
+        return Err(SolvabilityError::MissingKey);
+    }
+
+    let exits = exit_positions(data);
+    if exits.len() == 0 {
+        return Err(SolvabilityError::MissingExit);
+    }
+
+    let mut error = SolvabilityError::Unreachable(player_pos, keys[0]);
+    for key_pos in keys.iter() {
+        for exit_pos in exits.iter() {
+            match mutually_reachable(&data.map, &[player_pos, *key_pos, *exit_pos], swims) {
+                Ok(()) => return Ok(()),
+                Err(err) => error = err,
+            }
+        }
+    }
+
+    return Err(error);
+}
+
+/// Load an .xp map file and check that it can be solved.
+pub fn check_map_file(config: &Config, file_name: &str) -> Result<(), SolvabilityError> {
+    let mut msg_log = MsgLog::new();
+    let mut data = GameData::new(Map::empty(), Entities::new());
+    make_player(&mut data.entities, config, &mut msg_log);
+
+    let player_pos = read_map_xp(config, &mut data, &mut msg_log, file_name)
+        .unwrap_or(Pos::new(data.map.width() / 2, data.map.height() / 2));
+
+    return check_solvable(&data, player_pos);
+}
+
+pub fn mutually_reachable(map: &Map, positions: &[Pos], swims: bool) -> Result<(), SolvabilityError> {
+    for start in positions.iter() {
+        let reachable = reachable_positions(map, *start, swims);
+
+        for end in positions.iter() {
+            if !reachable.contains(end) {
+                return Err(SolvabilityError::Unreachable(*start, *end));
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+/// Positions the player can get to from the start position. Short walls can be vaulted,
+/// ledges one level high climbed, and deep water crossed by swimmers.
+/// Entities are ignored, as they can be pushed aside or killed.
+pub fn reachable_positions(map: &Map, start: Pos, swims: bool) -> HashSet<Pos> {
+    return path_distances(map, start, swims).keys().map(|pos| *pos).collect();
+}
+
+/// The number of steps on the shortest path between two positions, using the same
+/// moves as reachable_positions, or None if there is no path.
+pub fn path_length(map: &Map, start: Pos, end: Pos, swims: bool) -> Option<usize> {
+    return path_distances(map, start, swims).get(&end).map(|steps| *steps);
+}
+
+/// The number of steps to each position that can be reached from the start.
+fn path_distances(map: &Map, start: Pos, swims: bool) -> HashMap<Pos, usize> {
+    let mut reached = HashMap::new();
+    let mut frontier = VecDeque::new();
+    reached.insert(start, 0);
+    frontier.push_back(start);
+
+    while let Some(pos) = frontier.pop_front() {
+        let steps = reached[&pos];
+
+        for direction in Direction::move_actions() {
+            let next_pos = add_pos(pos, Pos::from(direction.into_move()));
+
+            if map.is_within_bounds(next_pos) &&
+               !reached.contains_key(&next_pos) &&
+               can_step(map, pos, next_pos, swims) {
+                reached.insert(next_pos, steps + 1);
+                frontier.push_back(next_pos);
+            }
+        }
+    }
+
+    return reached;
+}
+
+fn can_step(map: &Map, pos: Pos, next_pos: Pos, swims: bool) -> bool {
+    let (dx, dy) = (next_pos.x - pos.x, next_pos.y - pos.y);
+
+    if let Some(blocked) = map.is_blocked_by_wall(pos, dx, dy) {
+        let can_vault = !blocked.blocked_tile && !blocked.ledge && blocked.wall_type == Wall::ShortWall;
+
+        let can_swim =
+            swims &&
+            blocked.wall_type == Wall::Empty &&
+            map[next_pos].tile_type == TileType::Water &&
+            map[next_pos].elevation <= map[pos].elevation;
+
+        return can_vault || can_swim || map.can_climb(pos, next_pos);
+    }
+
+    return true;
+}
+
+/// Keys are found where they lie, or with whoever is carrying them.
+pub fn key_positions(data: &GameData) -> Vec<Pos> {
+    let mut positions = Vec::new();
+
+    for (key, item) in data.entities.item.iter() {
+        if *item == Item::Goal {
+            let carrier =
+                data.entities.inventory.iter()
+                                       .find(|(_, inventory)| inventory.contains(key))
+                                       .map(|(carrier_id, _)| *carrier_id);
+
+            positions.push(data.entities.pos[&carrier.unwrap_or(*key)]);
+        }
+    }
+
+    return positions;
+}
+
+pub fn exit_positions(data: &GameData) -> Vec<Pos> {
+    let mut positions = Vec::new();
+
+    for x in 0..data.map.width() {
+        for y in 0..data.map.height() {
+            let pos = Pos::new(x, y);
+            if data.map[pos].tile_type == TileType::Exit {
+                positions.push(pos);
+            }
+        }
+    }
+
+    for (key, name) in data.entities.name.iter() {
+        if *name == EntityName::Exit {
+            positions.push(data.entities.pos[key]);
+        }
+    }
+
+    return positions;
+}
+
+#[test]
+pub fn test_solvability() {
+    let config = Config::from_file("../config.yaml");
+    let mut game = Game::new(0, config.clone()).unwrap();
+
+    let player_pos = Pos::new(1, 1);
+    game.data.map = Map::from_dims(10, 5);
+    game.data.map[Pos::new(8, 1)] = Tile::exit();
+    assert_eq!(Err(SolvabilityError::MissingKey), check_solvable(&game.data, player_pos));
+
+    let key_pos = Pos::new(5, 1);
+    make_key(&mut game.data.entities, &config, key_pos, &mut game.msg_log);
+    assert_eq!(Ok(()), check_solvable(&game.data, player_pos));
+    assert_eq!(Some(4), path_length(&game.data.map, player_pos, key_pos, false));
+
+    // a wall across the map cuts the player off from the key
+    for y in 0..5 {
+        game.data.map[Pos::new(3, y)] = Tile::wall();
+    }
+    assert!(matches!(check_solvable(&game.data, player_pos), Err(SolvabilityError::Unreachable(_, _))));
+
+    // unless there is a short wall to vault
+    game.data.map[Pos::new(3, 2)] = Tile::empty();
+    game.data.map[Pos::new(3, 2)].left_wall = Wall::ShortWall;
+    assert_eq!(Ok(()), check_solvable(&game.data, player_pos));
+
+    // a ledge that is too high can be dropped from, but not climbed back up
+    for x in 6..10 {
+        for y in 0..5 {
+            game.data.map[Pos::new(x, y)].elevation = 2;
+        }
+    }
+    assert!(matches!(check_solvable(&game.data, player_pos), Err(SolvabilityError::Unreachable(_, _))));
+
+    for x in 6..10 {
+        for y in 0..5 {
+            game.data.map[Pos::new(x, y)].elevation = 1;
+        }
+    }
+    assert_eq!(Ok(()), check_solvable(&game.data, player_pos));
+}
+
+#[test]
+pub fn test_island_solvable() {
+    let config = Config::from_file("../config.yaml");
+
+    for seed in 0..10 {
+        let mut game = Game::new(seed, config.clone()).unwrap();
+        game.data.map = Map::from_dims(MAP_WIDTH as usize, MAP_HEIGHT as usize);
+
+        let player_pos = make_island(&mut game.data, &game.config, &mut game.msg_log, &mut game.rng);
+        assert_eq!(Ok(()), check_solvable(&game.data, player_pos));
+
+        let num_keys = game.data.entities.item.values().filter(|item| **item == Item::Goal).count();
+        assert_eq!(1, num_keys);
+    }
+
+    // small and narrow maps leave little room, but are still solvable
+    for (width, height) in vec!((MAP_MIN_SIZE, MAP_MIN_SIZE), (8, 40), (40, 9)) {
+        for seed in 0..10 {
+            let mut game = Game::new(seed, config.clone()).unwrap();
+            game.data.map = Map::from_dims(width as usize, height as usize);
+
+            let player_pos = make_island(&mut game.data, &game.config, &mut game.msg_log, &mut game.rng);
+            assert_eq!(Ok(()), check_solvable(&game.data, player_pos));
+        }
+    }
+}
@@ -0,0 +1,500 @@
+use std::fs;
+
+use roguelike_core::types::*;
+use roguelike_core::map::*;
+use roguelike_core::config::*;
+use roguelike_core::messaging::MsgLog;
+
+use crate::generation::*;
+#[cfg(test)]
+use crate::game::Game;
+
+
+/*
+ * Text maps give each cell three characters: its left edge, its glyph, and its bottom edge.
+ * Edges are ' ' for no wall, '|' or '_' for a short wall, and '!' or '=' for a tall wall.
+ *
+ * Glyphs are either a tile:
+ *   '.' floor    '"' grass        ':' rubble    ';' burnt floor
+ *   '#' wall     '%' short wall   '~' water     '-' shallow water    '>' exit
+ *
+ * or an entity standing on plain floor, given in a legend after the grid:
+ *   legend:
+ *   @ Player
+ *   g Gol
+ *
+ * Entities without a legend entry use the glyphs in ENTITY_GLYPHS.
+ *
+ * Entities standing on any other tile are drawn in a second grid under an
+ * 'entities:' header, and raised ground is given as a digit per tile in a grid
+ * under an 'elevation:' header. These grids are laid out like the tile grid,
+ * with nothing along the edges, and rows left out are treated as empty.
+ */
+
+const ENTITIES_HEADER: &str = "entities:";
+const ELEVATION_HEADER: &str = "elevation:";
+const LEGEND_HEADER: &str = "legend:";
+
+const HEADERS: &[&str] = &[ENTITIES_HEADER, ELEVATION_HEADER, LEGEND_HEADER];
+
+const ENTITY_GLYPHS: &[(char, EntityName)] = &[
+    ('@', EntityName::Player),
+    ('g', EntityName::Gol),
+    ('e', EntityName::Pawn),
+    ('s', EntityName::Spire),
+    ('O', EntityName::Column),
+    ('k', EntityName::Key),
+    ('X', EntityName::Exit),
+    ('d', EntityName::Dagger),
+    ('h', EntityName::Hammer),
+    ('/', EntityName::Sword),
+    (']', EntityName::Shield),
+    ('^', EntityName::Spike),
+    ('o', EntityName::Stone),
+    ('p', EntityName::HealingDraught),
+    ('*', EntityName::SmokeBomb),
+    ('?', EntityName::Noisemaker),
+    ('t', EntityName::Torch),
+    ('l', EntityName::Lantern),
+];
+
+fn tile_from_glyph(glyph: char) -> Option<Tile> {
+    let mut tile = Tile::empty();
+
+    match glyph {
+        '.' | ' ' => {}
+        '"' => tile.surface = Surface::Grass,
+        ':' => tile.surface = Surface::Rubble,
+        ';' => tile.surface = Surface::Burnt,
+        '#' => tile = Tile::wall(),
+        '%' => tile = Tile::short_wall(),
+        '~' => tile = Tile::water(),
+        '-' => tile = Tile::shallow_water(),
+        '>' => tile = Tile::exit(),
+        _ => return None,
+    }
+
+    return Some(tile);
+}
+
+fn tile_glyph(tile: &Tile) -> char {
+    match tile.tile_type {
+        TileType::Wall => return '#',
+        TileType::ShortWall => return '%',
+        TileType::Water => return '~',
+        TileType::ShallowWater => return '-',
+        TileType::Exit => return '>',
+        TileType::Empty => {}
+    }
+
+    // blocking decorations have no tile of their own, so they are kept as walls
+    if tile.blocked {
+        return '#';
+    }
+
+    match tile.surface {
+        Surface::Grass => return '"',
+        Surface::Rubble => return ':',
+        Surface::Burnt => return ';',
+        Surface::Floor => return '.',
+    }
+}
+
+fn wall_from_edge(edge: char, short: char, tall: char) -> Option<Wall> {
+    if edge == ' ' {
+        return Some(Wall::Empty);
+    } else if edge == short {
+        return Some(Wall::ShortWall);
+    } else if edge == tall {
+        return Some(Wall::TallWall);
+    }
+
+    return None;
+}
+
+fn edge_glyph(wall: Wall, short: char, tall: char) -> char {
+    match wall {
+        Wall::Empty => ' ',
+        Wall::ShortWall => short,
+        Wall::TallWall => tall,
+    }
+}
+
+fn make_entity(name: EntityName, data: &mut GameData, config: &Config, pos: Pos, msg_log: &mut MsgLog) {
+    let entities = &mut data.entities;
+
+    match name {
+        EntityName::Gol => { make_gol(entities, config, pos, msg_log); }
+        EntityName::Pawn => { make_elf(entities, config, pos, msg_log); }
+        EntityName::Spire => { make_spire(entities, config, pos, msg_log); }
+        EntityName::Column => { make_column(entities, config, pos, msg_log); }
+        EntityName::Key => { make_key(entities, config, pos, msg_log); }
+        EntityName::Exit => { make_exit(entities, config, pos, msg_log); }
+        EntityName::Dagger => { make_dagger(entities, config, pos, msg_log); }
+        EntityName::Hammer => { make_hammer(entities, config, pos, msg_log); }
+        EntityName::Sword => { make_sword(entities, config, pos, msg_log); }
+        EntityName::Shield => { make_shield(entities, config, pos, msg_log); }
+        EntityName::Spike => { make_spikes(entities, config, pos, msg_log); }
+        EntityName::Stone => { make_stone(entities, config, pos, msg_log); }
+        EntityName::HealingDraught => { make_healing_draught(entities, config, pos, msg_log); }
+        EntityName::SmokeBomb => { make_smoke_bomb(entities, config, pos, msg_log); }
+        EntityName::Noisemaker => { make_noisemaker(entities, config, pos, msg_log); }
+        EntityName::Torch => { make_torch(entities, config, pos, msg_log); }
+        EntityName::Lantern => { make_lantern(entities, config, pos, msg_log); }
+        _ => {}
+    }
+}
+
+fn parse_legend(lines: &[&str]) -> Result<Vec<(char, EntityName)>, String> {
+    let mut legend = ENTITY_GLYPHS.to_vec();
+
+    for line in lines.iter().map(|line| line.trim()).filter(|line| line.len() > 0) {
+        let mut chars = line.chars();
+        let glyph = chars.next().unwrap();
+        let name_str = chars.as_str().trim();
+
+        let name =
+            ENTITY_GLYPHS.iter()
+                         .map(|(_, name)| *name)
+                         .find(|name| format!("{:?}", name) == name_str)
+                         .ok_or(format!("Unknown entity '{}' in legend", name_str))?;
+
+        if tile_from_glyph(glyph).is_some() {
+            return Err(format!("Legend glyph '{}' is already used for a tile", glyph));
+        }
+
+        legend.retain(|(other_glyph, other_name)| *other_glyph != glyph && *other_name != name);
+        legend.push((glyph, name));
+    }
+
+    return Ok(legend);
+}
+
+/// The lines under a header, up to the next header, without trailing blank lines.
+/// With no header, this is the tile grid at the start of the text.
+fn section<'a>(lines: &[&'a str], header: Option<&str>) -> Vec<&'a str> {
+    let start = match header {
+        Some(header) => match lines.iter().position(|line| line.trim() == header) {
+            Some(index) => index + 1,
+            None => return Vec::new(),
+        },
+        None => 0,
+    };
+
+    let mut section: Vec<&str> =
+        lines[start..].iter()
+                      .take_while(|line| !HEADERS.contains(&line.trim()))
+                      .map(|line| *line)
+                      .collect();
+    while section.last().map_or(false, |line| line.trim().len() == 0) {
+        section.pop();
+    }
+
+    return section;
+}
+
+/// The glyphs given for tiles in an entity or elevation grid.
+fn overlay_glyphs(rows: &[&str], width: usize, height: usize) -> Result<Vec<(Pos, char)>, String> {
+    let mut glyphs = Vec::new();
+
+    for (y, row) in rows.iter().enumerate() {
+        for (index, glyph) in row.chars().enumerate() {
+            if glyph == ' ' {
+                continue;
+            }
+
+            let x = index / 3;
+            if index % 3 != 1 {
+                return Err(format!("Unexpected edge '{}' at ({}, {})", glyph, x, y));
+            }
+
+            if x >= width || y >= height {
+                return Err(format!("Glyph '{}' at ({}, {}) is outside of the map", glyph, x, y));
+            }
+
+            glyphs.push((Pos::new(x as i32, y as i32), glyph));
+        }
+    }
+
+    return Ok(glyphs);
+}
+
+fn overlay_text(overlay: &[Vec<char>]) -> String {
+    let mut text = String::new();
+
+    for row in overlay {
+        let line: String = row.iter().map(|glyph| format!(" {} ", glyph)).collect();
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+
+    return text.trim_end().to_string() + "\n";
+}
+
+/// Replace the map with the one given as text, creating its entities.
+/// Returns the player's position, if the map has one.
+/// The game is left untouched if the text can't be read.
+pub fn read_map_text(config: &Config,
+                     data: &mut GameData,
+                     msg_log: &mut MsgLog,
+                     text: &str) -> Result<Option<Pos>, String> {
+    let lines: Vec<&str> = text.lines().collect();
+
+    let grid = section(&lines, None);
+    let legend = parse_legend(&section(&lines, Some(LEGEND_HEADER)))?;
+
+    // editors may strip trailing spaces, so short rows are padded out
+    let rows: Vec<Vec<char>> = grid.iter().map(|line| line.chars().collect()).collect();
+    let width = rows.iter().map(|row| (row.len() + 2) / 3).max().unwrap_or(0);
+    let height = rows.len();
+
+    let mut map = Map::from_dims(width, height);
+    let mut entities: Vec<(EntityName, Pos)> = Vec::new();
+
+    let mut player_pos = None;
+    for (y, row) in rows.iter().enumerate() {
+        for x in 0..width {
+            let pos = Pos::new(x as i32, y as i32);
+            let cell = |offset: usize| *row.get(x * 3 + offset).unwrap_or(&' ');
+            let (left, glyph, bottom) = (cell(0), cell(1), cell(2));
+
+            if let Some(tile) = tile_from_glyph(glyph) {
+                map[pos] = tile;
+            } else if let Some((_, name)) = legend.iter().find(|(entity_glyph, _)| *entity_glyph == glyph) {
+                if *name == EntityName::Player {
+                    player_pos = Some(pos);
+                } else {
+                    entities.push((*name, pos));
+                }
+            } else {
+                return Err(format!("Unexpected glyph '{}' at ({}, {})", glyph, x, y));
+            }
+
+            map[pos].left_wall =
+                wall_from_edge(left, '|', '!').ok_or(format!("Unexpected left edge '{}' at ({}, {})", left, x, y))?;
+            map[pos].bottom_wall =
+                wall_from_edge(bottom, '_', '=').ok_or(format!("Unexpected bottom edge '{}' at ({}, {})", bottom, x, y))?;
+        }
+    }
+
+    for (pos, glyph) in overlay_glyphs(&section(&lines, Some(ENTITIES_HEADER)), width, height)? {
+        match legend.iter().find(|(entity_glyph, _)| *entity_glyph == glyph) {
+            Some((_, EntityName::Player)) => player_pos = Some(pos),
+            Some((_, name)) => entities.push((*name, pos)),
+            None => return Err(format!("Unexpected entity '{}' at ({}, {})", glyph, pos.x, pos.y)),
+        }
+    }
+
+    for (pos, glyph) in overlay_glyphs(&section(&lines, Some(ELEVATION_HEADER)), width, height)? {
+        let elevation =
+            glyph.to_digit(10).ok_or(format!("Unexpected elevation '{}' at ({}, {})", glyph, pos.x, pos.y))?;
+        map[pos].elevation = elevation as u8;
+    }
+
+    map.update_map();
+    data.map = map;
+
+    for (name, pos) in entities {
+        make_entity(name, data, config, pos, msg_log);
+    }
+
+    return Ok(player_pos);
+}
+
+pub fn read_map_text_file(config: &Config,
+                          data: &mut GameData,
+                          msg_log: &mut MsgLog,
+                          file_name: &str) -> Result<Option<Pos>, String> {
+    let text = fs::read_to_string(file_name).map_err(|err| format!("Could not read {}: {}", file_name, err))?;
+    return read_map_text(config, data, msg_log, &text);
+}
+
+/// Write the map and the entities on it as text. Only one entity is kept per tile,
+/// preferring the player. Entities on plain floor are written into the tile grid,
+/// and the rest into the entity grid so the tile under them is kept.
+pub fn write_map_text(data: &GameData) -> String {
+    let width = data.map.width();
+    let height = data.map.height();
+
+    let mut glyphs: Vec<Vec<char>> =
+        (0..height).map(|y| (0..width).map(|x| tile_glyph(&data.map[(x, y)])).collect())
+                   .collect();
+    let mut overlay: Vec<Vec<char>> = vec![vec![' '; width as usize]; height as usize];
+
+    let mut placed: Vec<Pos> = Vec::new();
+    let mut used: Vec<(char, EntityName)> = Vec::new();
+    let player = data.find_player();
+    let ids = player.iter().chain(data.entities.ids.iter().filter(|id| Some(**id) != player));
+
+    for id in ids {
+        let carried = data.entities.inventory.values().any(|inventory| inventory.contains(id));
+        let pos = match data.entities.pos.get(id) {
+            Some(pos) if !carried && data.map.is_within_bounds(*pos) && !placed.contains(pos) => *pos,
+            _ => continue,
+        };
+
+        let name = data.entities.name[id];
+        if let Some(entry) = ENTITY_GLYPHS.iter().find(|(_, entity_name)| *entity_name == name) {
+            if glyphs[pos.y as usize][pos.x as usize] == '.' {
+                glyphs[pos.y as usize][pos.x as usize] = entry.0;
+            } else {
+                overlay[pos.y as usize][pos.x as usize] = entry.0;
+            }
+            placed.push(pos);
+            if !used.contains(entry) {
+                used.push(*entry);
+            }
+        }
+    }
+
+    let mut text = String::new();
+    for y in 0..height {
+        let mut line = String::new();
+        for x in 0..width {
+            let tile = &data.map[(x, y)];
+            line.push(edge_glyph(tile.left_wall, '|', '!'));
+            line.push(glyphs[y as usize][x as usize]);
+            line.push(edge_glyph(tile.bottom_wall, '_', '='));
+        }
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+
+    if overlay.iter().flatten().any(|glyph| *glyph != ' ') {
+        text.push('\n');
+        text.push_str(ENTITIES_HEADER);
+        text.push('\n');
+        text.push_str(&overlay_text(&overlay));
+    }
+
+    let mut elevation: Vec<Vec<char>> = vec![vec![' '; width as usize]; height as usize];
+    for x in 0..width {
+        for y in 0..height {
+            let tile_elevation = std::cmp::min(data.map[(x, y)].elevation, 9);
+            if tile_elevation > 0 {
+                elevation[y as usize][x as usize] = std::char::from_digit(tile_elevation as u32, 10).unwrap();
+            }
+        }
+    }
+    if elevation.iter().flatten().any(|glyph| *glyph != ' ') {
+        text.push('\n');
+        text.push_str(ELEVATION_HEADER);
+        text.push('\n');
+        text.push_str(&overlay_text(&elevation));
+    }
+
+    if used.len() > 0 {
+        text.push('\n');
+        text.push_str(LEGEND_HEADER);
+        text.push('\n');
+        for (glyph, name) in used {
+            text.push_str(&format!("{} {:?}\n", glyph, name));
+        }
+    }
+
+    return text;
+}
+
+#[test]
+pub fn test_read_map_text() {
+    let config = Config::from_file("../config.yaml");
+    let mut game = Game::new(0, config.clone()).unwrap();
+
+    let text = concat!(
+        " #  #  #  #  #\n",
+        " #  @ |.  G  #\n",
+        " #  \"_ :  ~  #\n",
+        " #  #  >  -  #\n",
+        "\n",
+        "legend:\n",
+        "G Gol\n");
+
+    let player_pos = read_map_text(&config, &mut game.data, &mut game.msg_log, text).unwrap();
+
+    assert_eq!(Some(Pos::new(1, 1)), player_pos);
+    assert_eq!(5, game.data.map.width());
+    assert_eq!(4, game.data.map.height());
+
+    assert_eq!(TileType::Wall, game.data.map[(0, 0)].tile_type);
+    assert_eq!(Wall::ShortWall, game.data.map[(2, 1)].left_wall);
+    assert_eq!(Surface::Grass, game.data.map[(1, 2)].surface);
+    assert_eq!(Wall::ShortWall, game.data.map[(1, 2)].bottom_wall);
+    assert_eq!(Surface::Rubble, game.data.map[(2, 2)].surface);
+    assert_eq!(TileType::Water, game.data.map[(3, 2)].tile_type);
+    assert_eq!(TileType::Exit, game.data.map[(2, 3)].tile_type);
+    assert_eq!(TileType::ShallowWater, game.data.map[(3, 3)].tile_type);
+
+    let gol = game.data.has_blocking_entity(Pos::new(3, 1)).unwrap();
+    assert_eq!(EntityName::Gol, game.data.entities.name[&gol]);
+
+    // entities can stand on any tile when drawn in their own grid,
+    // and raised ground is given as a digit per tile
+    let text = concat!(
+        " .  \"  ~\n",
+        " .  .  .\n",
+        "\n",
+        "entities:\n",
+        "    g\n",
+        "\n",
+        "elevation:\n",
+        "\n",
+        "       2\n");
+    read_map_text(&config, &mut game.data, &mut game.msg_log, text).unwrap();
+
+    let gol = game.data.has_blocking_entity(Pos::new(1, 0)).unwrap();
+    assert_eq!(EntityName::Gol, game.data.entities.name[&gol]);
+    assert_eq!(Surface::Grass, game.data.map[(1, 0)].surface);
+    assert_eq!(2, game.data.map[(2, 1)].elevation);
+    assert_eq!(0, game.data.map[(2, 0)].elevation);
+
+    // unknown glyphs are reported rather than guessed at, and the map is kept
+    let num_entities = game.data.entities.ids.len();
+    assert!(read_map_text(&config, &mut game.data, &mut game.msg_log, " g  Q \n").is_err());
+    assert_eq!(3, game.data.map.width());
+    assert_eq!(num_entities, game.data.entities.ids.len());
+}
+
+#[test]
+pub fn test_write_map_text() {
+    let config = Config::from_file("../config.yaml");
+    let mut game = Game::new(0, config.clone()).unwrap();
+    game.data.map = Map::from_dims(11, 12);
+    make_test_map(&mut game);
+
+    // tiles under entities and raised ground are kept too
+    game.data.map[(8, 10)].surface = Surface::Grass;
+    game.data.map[(7, 5)].surface = Surface::Rubble;
+    for y in 0..3 {
+        game.data.map[(10, y)].elevation = 2;
+    }
+
+    let text = write_map_text(&game.data);
+
+    let mut loaded = Game::new(0, config.clone()).unwrap();
+    read_map_text(&config, &mut loaded.data, &mut loaded.msg_log, &text).unwrap();
+
+    for (tile, loaded_tile) in game.data.map.tiles.iter().flatten().zip(loaded.data.map.tiles.iter().flatten()) {
+        assert_eq!(tile.blocked, loaded_tile.blocked);
+        assert_eq!(tile.tile_type, loaded_tile.tile_type);
+        assert_eq!(tile.left_wall, loaded_tile.left_wall);
+        assert_eq!(tile.bottom_wall, loaded_tile.bottom_wall);
+        assert_eq!(tile.surface, loaded_tile.surface);
+        assert_eq!(tile.elevation, loaded_tile.elevation);
+    }
+
+    // the player and their starting stone are off the map in both games
+    let on_map = |data: &GameData| {
+        let mut entities: Vec<(EntityName, Pos)> =
+            data.entities.ids.iter()
+                             .map(|id| (data.entities.name[id], data.entities.pos[id]))
+                             .filter(|(_, pos)| data.map.is_within_bounds(*pos))
+                             .collect();
+        entities.sort_by_key(|(_, pos)| (pos.x, pos.y));
+        return entities;
+    };
+    assert_eq!(7, on_map(&game.data).len());
+    assert_eq!(on_map(&game.data), on_map(&loaded.data));
+
+    // writing the loaded map gives back the same text
+    assert_eq!(text, write_map_text(&loaded.data));
+}
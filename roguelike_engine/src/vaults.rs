@@ -0,0 +1,435 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::collections::HashSet;
+
+use rand::prelude::*;
+
+use rexpaint::*;
+
+use log::{trace, error};
+
+use roguelike_core::constants::*;
+use roguelike_core::types::*;
+use roguelike_core::map::*;
+use roguelike_core::config::*;
+use roguelike_core::messaging::MsgLog;
+
+use crate::make_map::place_map_xp;
+use crate::solvability::reachable_positions;
+#[cfg(test)]
+use crate::game::Game;
+#[cfg(test)]
+use crate::solvability::check_solvable;
+#[cfg(test)]
+use crate::generation::{make_key, make_exit};
+#[cfg(test)]
+use std::env;
+
+
+/// Number of random positions to try for each vault before giving up on it.
+const VAULT_PLACEMENT_ATTEMPTS: usize = 20;
+
+/// A hand made room or set piece, drawn in REXPaint with the same layers as a map file.
+/// The ground layer should be drawn with MAP_GROUND wherever the vault is open, as blank
+/// ground cells keep whatever tile the generator put there.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vault {
+    pub name: String,
+    pub xp: XpFile,
+}
+
+impl Vault {
+    pub fn new(name: &str, xp: XpFile) -> Vault {
+        return Vault { name: name.to_string(), xp };
+    }
+
+    pub fn from_file(file_name: &str) -> Result<Vault, String> {
+        trace!("opening vault {}", file_name);
+        let file = File::open(file_name).map_err(|err| format!("Could not open {}: {}", file_name, err))?;
+
+        let mut buf_reader = BufReader::new(file);
+        let xp = XpFile::read(&mut buf_reader).map_err(|err| format!("Could not read {}: {}", file_name, err))?;
+        if xp.layers.is_empty() {
+            return Err(format!("Vault {} has no layers", file_name));
+        }
+
+        return Ok(Vault::new(file_name, xp));
+    }
+
+    pub fn width(&self) -> i32 {
+        return self.xp.layers[0].width as i32;
+    }
+
+    pub fn height(&self) -> i32 {
+        return self.xp.layers[0].height as i32;
+    }
+
+    /// Rotate the vault a quarter turn clockwise.
+    pub fn rotated(&self) -> Vault {
+        let height = self.height();
+        return self.transformed(self.height(), self.width(), |x, y| (height - 1 - y, x), rotate_glyph);
+    }
+
+    /// Mirror the vault from left to right.
+    pub fn mirrored(&self) -> Vault {
+        let width = self.width();
+        return self.transformed(self.width(), self.height(), |x, y| (width - 1 - x, y), mirror_glyph);
+    }
+
+    fn transformed<F>(&self, width: i32, height: i32, new_pos: F, glyph: fn(u8) -> u8) -> Vault
+        where F: Fn(i32, i32) -> (i32, i32) {
+        let mut xp = self.xp.clone();
+
+        for (layer_index, layer) in xp.layers.iter_mut().enumerate() {
+            let old_layer = layer.clone();
+            layer.width = width as usize;
+            layer.height = height as usize;
+
+            for x in 0..old_layer.width as i32 {
+                for y in 0..old_layer.height as i32 {
+                    let mut cell = old_layer.cells[(y + old_layer.height as i32 * x) as usize];
+
+                    // only the environment layer has glyphs that face a direction
+                    if layer_index == MAP_LAYER_ENVIRONMENT && cell.ch < 256 {
+                        cell.ch = glyph(cell.ch as u8) as u32;
+                    }
+
+                    let (new_x, new_y) = new_pos(x, y);
+                    layer.cells[(new_y + height * new_x) as usize] = cell;
+                }
+            }
+        }
+
+        return Vault::new(&self.name, xp);
+    }
+}
+
+fn rotate_glyph(chr: u8) -> u8 {
+    match chr {
+        MAP_THIN_WALL_TOP => MAP_THIN_WALL_RIGHT,
+        MAP_THIN_WALL_RIGHT => MAP_THIN_WALL_BOTTOM,
+        MAP_THIN_WALL_BOTTOM => MAP_THIN_WALL_LEFT,
+        MAP_THIN_WALL_LEFT => MAP_THIN_WALL_TOP,
+
+        MAP_THIN_WALL_TOP_LEFT => MAP_THIN_WALL_TOP_RIGHT,
+        MAP_THIN_WALL_TOP_RIGHT => MAP_THIN_WALL_BOTTOM_RIGHT,
+        MAP_THIN_WALL_BOTTOM_RIGHT => MAP_THIN_WALL_BOTTOM_LEFT,
+        MAP_THIN_WALL_BOTTOM_LEFT => MAP_THIN_WALL_TOP_LEFT,
+
+        MAP_THICK_WALL_TOP => MAP_THICK_WALL_RIGHT,
+        MAP_THICK_WALL_RIGHT => MAP_THICK_WALL_BOTTOM,
+        MAP_THICK_WALL_BOTTOM => MAP_THICK_WALL_LEFT,
+        MAP_THICK_WALL_LEFT => MAP_THICK_WALL_TOP,
+
+        MAP_THICK_WALL_TOP_LEFT => MAP_THICK_WALL_TOP_RIGHT,
+        MAP_THICK_WALL_TOP_RIGHT => MAP_THICK_WALL_BOTTOM_RIGHT,
+        MAP_THICK_WALL_BOTTOM_RIGHT => MAP_THICK_WALL_BOTTOM_LEFT,
+        MAP_THICK_WALL_BOTTOM_LEFT => MAP_THICK_WALL_TOP_LEFT,
+
+        MAP_DOT_TOP_LEFT => MAP_DOT_TOP_RIGHT,
+        MAP_DOT_TOP_RIGHT => MAP_DOT_BOTTOM_RIGHT,
+        MAP_DOT_BOTTOM_RIGHT => MAP_DOT_BOTTOM_LEFT,
+        MAP_DOT_BOTTOM_LEFT => MAP_DOT_TOP_LEFT,
+
+        _ => chr,
+    }
+}
+
+fn mirror_glyph(chr: u8) -> u8 {
+    match chr {
+        MAP_THIN_WALL_LEFT => MAP_THIN_WALL_RIGHT,
+        MAP_THIN_WALL_RIGHT => MAP_THIN_WALL_LEFT,
+        MAP_THIN_WALL_TOP_LEFT => MAP_THIN_WALL_TOP_RIGHT,
+        MAP_THIN_WALL_TOP_RIGHT => MAP_THIN_WALL_TOP_LEFT,
+        MAP_THIN_WALL_BOTTOM_LEFT => MAP_THIN_WALL_BOTTOM_RIGHT,
+        MAP_THIN_WALL_BOTTOM_RIGHT => MAP_THIN_WALL_BOTTOM_LEFT,
+
+        MAP_THICK_WALL_LEFT => MAP_THICK_WALL_RIGHT,
+        MAP_THICK_WALL_RIGHT => MAP_THICK_WALL_LEFT,
+        MAP_THICK_WALL_TOP_LEFT => MAP_THICK_WALL_TOP_RIGHT,
+        MAP_THICK_WALL_TOP_RIGHT => MAP_THICK_WALL_TOP_LEFT,
+        MAP_THICK_WALL_BOTTOM_LEFT => MAP_THICK_WALL_BOTTOM_RIGHT,
+        MAP_THICK_WALL_BOTTOM_RIGHT => MAP_THICK_WALL_BOTTOM_LEFT,
+
+        MAP_DOT_TOP_LEFT => MAP_DOT_TOP_RIGHT,
+        MAP_DOT_TOP_RIGHT => MAP_DOT_TOP_LEFT,
+        MAP_DOT_BOTTOM_LEFT => MAP_DOT_BOTTOM_RIGHT,
+        MAP_DOT_BOTTOM_RIGHT => MAP_DOT_BOTTOM_LEFT,
+
+        _ => chr,
+    }
+}
+
+/// Load every .xp file in a directory as a vault, in file name order.
+/// Files that can't be read are logged and left out.
+pub fn load_vaults(directory: &str) -> Vec<Vault> {
+    let mut vaults = Vec::new();
+
+    match fs::read_dir(directory) {
+        Ok(entries) => {
+            let mut paths: Vec<PathBuf> =
+                entries.filter_map(|entry| entry.ok())
+                       .map(|entry| entry.path())
+                       .filter(|path| path.extension().map_or(false, |ext| ext == "xp"))
+                       .collect();
+            paths.sort();
+
+            for path in paths {
+                match Vault::from_file(&path.to_string_lossy()) {
+                    Ok(vault) => vaults.push(vault),
+                    Err(err) => error!("skipping vault: {}", err),
+                }
+            }
+        }
+
+        Err(err) => {
+            error!("could not read vault directory {}: {}", directory, err);
+        }
+    }
+
+    return vaults;
+}
+
+/// Place up to config.vault_count vaults in random orientations. A vault is only placed
+/// where it doesn't overlap another vault, the start, the exit or any entity, and where
+/// everything that could be reached from the start still can be.
+/// Returns the number of vaults placed.
+pub fn place_vaults(data: &mut GameData,
+                    config: &Config,
+                    msg_log: &mut MsgLog,
+                    vaults: &[Vault],
+                    start: Pos,
+                    rng: &mut SmallRng) -> usize {
+    let swims = data.find_player().map_or(false, |player_id| data.entities.can_swim(player_id));
+
+    // the top left corner and dimensions of each vault placed
+    let mut placed: Vec<(Pos, i32, i32)> = Vec::new();
+
+    for _ in 0..config.vault_count {
+        let mut vault = match vaults.choose(rng) {
+            Some(vault) => vault.clone(),
+            None => break,
+        };
+
+        for _ in 0..rng.gen_range(0, 4) {
+            vault = vault.rotated();
+        }
+        if rng.gen::<bool>() {
+            vault = vault.mirrored();
+        }
+
+        // leave a tile around the vault, as walls along its edges can be on the tiles beside it
+        let max_x = data.map.width() - vault.width();
+        let max_y = data.map.height() - vault.height();
+        if max_x <= 1 || max_y <= 1 {
+            continue;
+        }
+
+        // the map only changes once a vault is placed
+        let reachable_before = reachable_positions(&data.map, start, swims);
+        for _ in 0..VAULT_PLACEMENT_ATTEMPTS {
+            let offset = Pos::new(rng.gen_range(1, max_x), rng.gen_range(1, max_y));
+
+            if can_place_vault(data, config, &vault, offset, start, &placed, &reachable_before) {
+                place_map_xp(config, data, msg_log, &vault.xp, offset);
+                placed.push((offset, vault.width(), vault.height()));
+                break;
+            }
+        }
+    }
+
+    return placed.len();
+}
+
+fn can_place_vault(data: &GameData,
+                   config: &Config,
+                   vault: &Vault,
+                   offset: Pos,
+                   start: Pos,
+                   placed: &[(Pos, i32, i32)],
+                   reachable_before: &HashSet<Pos>) -> bool {
+    let within_vault = |pos: Pos| {
+        pos.x >= offset.x && pos.x < offset.x + vault.width() &&
+        pos.y >= offset.y && pos.y < offset.y + vault.height()
+    };
+
+    let overlaps_vault = placed.iter().any(|(other, width, height)| {
+        offset.x < other.x + width && other.x < offset.x + vault.width() &&
+        offset.y < other.y + height && other.y < offset.y + vault.height()
+    });
+    if overlaps_vault || within_vault(start) {
+        return false;
+    }
+
+    let player_id = data.find_player();
+    let covers_entity =
+        data.entities.pos.iter()
+                         .any(|(entity_id, pos)| Some(*entity_id) != player_id && within_vault(*pos));
+    if covers_entity {
+        return false;
+    }
+
+    for x in offset.x..(offset.x + vault.width()) {
+        for y in offset.y..(offset.y + vault.height()) {
+            if data.map[(x, y)].tile_type == TileType::Exit {
+                return false;
+            }
+        }
+    }
+
+    // try the vault out on a copy of the map to check that it doesn't cut anything off
+    let swims = player_id.map_or(false, |player_id| data.entities.can_swim(player_id));
+    let mut trial = GameData::new(data.map.clone(), data.entities.clone());
+    place_map_xp(config, &mut trial, &mut MsgLog::new(), &vault.xp, offset);
+
+    let reachable_after = reachable_positions(&trial.map, start, swims);
+
+    let cuts_off_map =
+        reachable_before.iter().any(|pos| !within_vault(*pos) && !reachable_after.contains(pos));
+
+    let mut unreachable_in_vault = false;
+    for x in offset.x..(offset.x + vault.width()) {
+        for y in offset.y..(offset.y + vault.height()) {
+            let pos = Pos::new(x, y);
+            if !trial.map[pos].blocked && !reachable_after.contains(&pos) {
+                unreachable_in_vault = true;
+            }
+        }
+    }
+
+    return !cuts_off_map && !unreachable_in_vault;
+}
+
+#[cfg(test)]
+fn test_vault(environment: &[&str]) -> Vault {
+    let width = environment[0].len();
+    let height = environment.len();
+
+    let mut xp = XpFile::new(width, height);
+    xp.layers = Vec::new();
+
+    let blank = XpCell { ch: 0, fg: XpColor { r: 0, g: 0, b: 0 }, bg: XpColor { r: 0, g: 0, b: 0 } };
+    for layer_index in 0..3 {
+        let mut layer = XpLayer { width, height, cells: vec![blank; width * height] };
+
+        for (y, row) in environment.iter().enumerate() {
+            for (x, chr) in row.chars().enumerate() {
+                let ch;
+                match (layer_index, chr) {
+                    (MAP_LAYER_GROUND, _) => ch = MAP_GROUND,
+                    (MAP_LAYER_ENVIRONMENT, '#') => ch = MAP_WALL,
+                    (MAP_LAYER_ENVIRONMENT, '|') => ch = MAP_THIN_WALL_LEFT,
+                    (MAP_LAYER_ENVIRONMENT, '_') => ch = MAP_THIN_WALL_BOTTOM,
+                    (MAP_LAYER_ENVIRONMENT, 'L') => ch = MAP_THIN_WALL_BOTTOM_LEFT,
+                    (MAP_LAYER_ENTITIES, 'g') => ch = ENTITY_GOL,
+                    _ => ch = MAP_EMPTY,
+                }
+                layer.cells[y + height * x].ch = ch as u32;
+            }
+        }
+
+        xp.layers.push(layer);
+    }
+
+    return Vault::new("test", xp);
+}
+
+#[test]
+pub fn test_vault_transforms() {
+    let vault = test_vault(&["#|.",
+                             "L_.",]);
+    let env = |vault: &Vault, x: i32, y: i32| {
+        let layer = &vault.xp.layers[MAP_LAYER_ENVIRONMENT];
+        return layer.cells[(y + layer.height as i32 * x) as usize].ch as u8;
+    };
+
+    let rotated = vault.rotated();
+    assert_eq!(2, rotated.width());
+    assert_eq!(3, rotated.height());
+    assert_eq!(MAP_WALL, env(&rotated, 1, 0));
+    assert_eq!(MAP_THIN_WALL_TOP, env(&rotated, 1, 1));
+    assert_eq!(MAP_THIN_WALL_TOP_LEFT, env(&rotated, 0, 0));
+    assert_eq!(MAP_THIN_WALL_LEFT, env(&rotated, 0, 1));
+
+    // four turns brings the vault back around
+    assert_eq!(vault, vault.rotated().rotated().rotated().rotated());
+
+    let mirrored = vault.mirrored();
+    assert_eq!(MAP_WALL, env(&mirrored, 2, 0));
+    assert_eq!(MAP_THIN_WALL_RIGHT, env(&mirrored, 1, 0));
+    assert_eq!(MAP_THIN_WALL_BOTTOM_RIGHT, env(&mirrored, 2, 1));
+    assert_eq!(vault, vault.mirrored().mirrored());
+
+    // the walls end up on the same edges once the vault is placed
+    let config = Config::from_file("../config.yaml");
+    let mut data = GameData::new(Map::from_dims(5, 5), Entities::new());
+    place_map_xp(&config, &mut data, &mut MsgLog::new(), &vault.mirrored().xp, Pos::new(1, 1));
+    assert!(data.map[(3, 1)].blocked);
+    assert_eq!(Wall::ShortWall, data.map[(3, 1)].left_wall);
+    assert_eq!(Wall::ShortWall, data.map[(2, 2)].bottom_wall);
+    assert_eq!(Wall::ShortWall, data.map[(3, 2)].bottom_wall);
+    assert_eq!(Wall::ShortWall, data.map[(4, 2)].left_wall);
+}
+
+#[test]
+pub fn test_load_vaults() {
+    let vaults = load_vaults("../resources/vaults");
+    assert!(vaults.len() > 0);
+
+    for vault in vaults.iter() {
+        assert!(vault.width() > 0 && vault.height() > 0);
+        assert_eq!(vault.width(), vault.rotated().height());
+    }
+}
+
+#[test]
+pub fn test_load_vaults_skips_bad_files() {
+    let directory = env::temp_dir().join("test_load_vaults_skips_bad_files");
+    fs::create_dir_all(&directory).unwrap();
+    fs::copy("../resources/vaults/walled_cell.xp", directory.join("a_good.xp")).unwrap();
+    fs::write(directory.join("b_bad.xp"), "not a vault").unwrap();
+
+    let vaults = load_vaults(&directory.to_string_lossy());
+    assert_eq!(1, vaults.len());
+    assert!(vaults[0].name.ends_with("a_good.xp"));
+}
+
+#[test]
+pub fn test_place_vaults() {
+    let mut config = Config::from_file("../config.yaml");
+    config.vault_count = 3;
+    let mut game = Game::new(0, config.clone()).unwrap();
+
+    let start = Pos::new(1, 1);
+    game.data.map = Map::from_dims(20, 20);
+    make_key(&mut game.data.entities, &config, Pos::new(18, 18), &mut game.msg_log);
+    make_exit(&mut game.data.entities, &config, Pos::new(18, 1), &mut game.msg_log);
+
+    // a walled in room with a single doorway, and a gol inside
+    let vault = test_vault(&["#####",
+                             "#...#",
+                             "#.g.#",
+                             "#...#",
+                             "##.##",]);
+
+    let num_placed = place_vaults(&mut game.data, &config, &mut game.msg_log, &[vault], start, &mut game.rng);
+    assert!(num_placed > 0);
+    assert_eq!(Ok(()), check_solvable(&game.data, start));
+
+    let num_gols = game.data.entities.name.values().filter(|name| **name == EntityName::Gol).count();
+    assert_eq!(num_placed, num_gols);
+
+    // every open tile is still reachable
+    let reachable = reachable_positions(&game.data.map, start, false);
+    let num_open = game.data.map.tiles.iter().flatten().filter(|tile| !tile.blocked).count();
+    assert_eq!(num_open, reachable.len());
+
+    // a vault that can't be entered is never placed
+    let closed = test_vault(&["###",
+                              "#.#",
+                              "###",]);
+    let mut game = Game::new(0, config.clone()).unwrap();
+    game.data.map = Map::from_dims(20, 20);
+    assert_eq!(0, place_vaults(&mut game.data, &config, &mut game.msg_log, &[closed], start, &mut game.rng));
+}
@@ -0,0 +1,498 @@
+use std::collections::{HashMap, HashSet};
+
+use rand::prelude::*;
+
+use log::{trace, warn};
+
+use roguelike_core::types::*;
+use roguelike_core::map::*;
+use roguelike_core::config::*;
+use roguelike_core::messaging::MsgLog;
+use roguelike_core::utils::distance;
+
+use crate::generation::{make_key, make_island};
+use crate::make_map::read_map_xp;
+use crate::solvability::{reachable_positions, mutually_reachable};
+#[cfg(test)]
+use roguelike_core::constants::*;
+#[cfg(test)]
+use crate::game::Game;
+#[cfg(test)]
+use crate::solvability::check_solvable;
+
+
+/// The fraction of the map the player must be able to get around in for a generated map to be used.
+const WFC_MIN_OPEN_FRACTION: f32 = 0.1;
+
+/// Offsets to the neighbors of a position, where a direction's opposite is two further along.
+const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+fn opposite(direction: usize) -> usize {
+    return (direction + 2) % 4;
+}
+
+/// An overlapping wave function collapse model- every square of tiles in the
+/// example maps is a pattern, and patterns can be placed next to each other
+/// when the tiles they share agree. As tiles include their edge walls, walls
+/// are only placed where they were seen in the examples.
+pub struct WfcModel {
+    pattern_size: usize,
+    tiles: Vec<Tile>,
+    patterns: Vec<Vec<usize>>, // tile indices, by x * pattern_size + y
+    weights: Vec<usize>, // how often each pattern appeared
+    propagator: Vec<Vec<Vec<usize>>>, // direction, pattern, patterns allowed in that direction
+}
+
+impl WfcModel {
+    /// The model for the example maps in config.wfc_sample_maps.
+    pub fn from_config(config: &Config) -> WfcModel {
+        let samples = load_samples(config, &config.wfc_sample_maps);
+        return WfcModel::from_maps(&samples, config.wfc_pattern_size);
+    }
+
+    pub fn from_maps(maps: &[Map], pattern_size: usize) -> WfcModel {
+        let size = pattern_size as i32;
+
+        let mut tiles: Vec<Tile> = Vec::new();
+        let mut patterns: Vec<Vec<usize>> = Vec::new();
+        let mut weights = Vec::new();
+        let mut pattern_indices: HashMap<Vec<usize>, usize> = HashMap::new();
+
+        for map in maps.iter() {
+            for x in 0..=(map.width() - size) {
+                for y in 0..=(map.height() - size) {
+                    let mut pattern = Vec::new();
+                    for dx in 0..size {
+                        for dy in 0..size {
+                            let tile = map[(x + dx, y + dy)];
+
+                            let tile_index;
+                            if let Some(index) = tiles.iter().position(|other| *other == tile) {
+                                tile_index = index;
+                            } else {
+                                tiles.push(tile);
+                                tile_index = tiles.len() - 1;
+                            }
+                            pattern.push(tile_index);
+                        }
+                    }
+
+                    if let Some(index) = pattern_indices.get(&pattern) {
+                        weights[*index] += 1;
+                    } else {
+                        pattern_indices.insert(pattern.clone(), patterns.len());
+                        patterns.push(pattern);
+                        weights.push(1);
+                    }
+                }
+            }
+        }
+
+        let mut propagator = Vec::new();
+        for (dx, dy) in DIRECTIONS.iter() {
+            let mut allowed = Vec::new();
+            for first in patterns.iter() {
+                allowed.push((0..patterns.len()).filter(|second| {
+                    patterns_agree(first, &patterns[*second], *dx, *dy, size)
+                }).collect::<Vec<usize>>());
+            }
+            propagator.push(allowed);
+        }
+
+        return WfcModel { pattern_size, tiles, patterns, weights, propagator };
+    }
+
+    pub fn num_patterns(&self) -> usize {
+        return self.patterns.len();
+    }
+
+    /// Generate a map of the given size, backtracking when a choice leads to a
+    /// position with no patterns left. Returns None if the backtracking limit is
+    /// reached or there is no way to fill the map.
+    pub fn generate(&self, width: i32, height: i32, max_backtracks: usize, rng: &mut SmallRng) -> Option<Map> {
+        let size = self.pattern_size as i32;
+        if self.patterns.len() == 0 || width < size || height < size {
+            return None;
+        }
+
+        let mut wave = Wave::new(self, width - size + 1, height - size + 1);
+
+        // patterns with nothing that can go beside them are only allowed along the edges
+        for cell in 0..wave.possible.len() {
+            for pattern in 0..self.patterns.len() {
+                let unsupported = (0..DIRECTIONS.len()).any(|direction| {
+                    wave.neighbor(cell, opposite(direction)).is_some() &&
+                    wave.supports[cell][pattern][direction] == 0
+                });
+
+                if unsupported {
+                    wave.ban(cell, pattern);
+                }
+            }
+        }
+        wave.propagate();
+
+        // each choice made, with the length of the trail before it was made
+        let mut decisions: Vec<(usize, usize, usize)> = Vec::new();
+        let mut backtracks = 0;
+        loop {
+            if wave.contradiction {
+                if backtracks >= max_backtracks {
+                    return None;
+                }
+                backtracks += 1;
+
+                // undo the last choice and rule it out
+                let (trail_len, cell, pattern) = decisions.pop()?;
+                wave.undo(trail_len);
+                wave.ban(cell, pattern);
+                wave.propagate();
+                continue;
+            }
+
+            match wave.least_certain(rng) {
+                Some(cell) => {
+                    let pattern = self.choose_pattern(&wave.possible[cell], rng);
+                    decisions.push((wave.trail.len(), cell, pattern));
+
+                    for other in 0..self.patterns.len() {
+                        if other != pattern {
+                            wave.ban(cell, other);
+                        }
+                    }
+                    wave.propagate();
+                }
+
+                None => break,
+            }
+        }
+
+        trace!("wave function collapse finished with {} backtracks", backtracks);
+
+        let mut map = Map::from_dims(width as usize, height as usize);
+        for x in 0..width {
+            for y in 0..height {
+                // the patterns along the far edges fill in the tiles past them
+                let cell_x = std::cmp::min(x, wave.width - 1);
+                let cell_y = std::cmp::min(y, wave.height - 1);
+                let cell = (cell_x + cell_y * wave.width) as usize;
+
+                let pattern = wave.possible[cell].iter().position(|possible| *possible).unwrap();
+                let tile_index = self.patterns[pattern][((x - cell_x) * size + (y - cell_y)) as usize];
+                map[(x, y)] = self.tiles[tile_index];
+            }
+        }
+
+        return Some(map);
+    }
+
+    fn choose_pattern(&self, possible: &[bool], rng: &mut SmallRng) -> usize {
+        let total: usize = (0..possible.len()).filter(|pattern| possible[*pattern])
+                                              .map(|pattern| self.weights[pattern])
+                                              .sum();
+
+        let mut choice = rng.gen_range(0, total);
+        for pattern in 0..possible.len() {
+            if possible[pattern] {
+                if choice < self.weights[pattern] {
+                    return pattern;
+                }
+                choice -= self.weights[pattern];
+            }
+        }
+
+        panic!("No pattern chosen!");
+    }
+}
+
+/// Whether two patterns agree on the tiles they share when the second
+/// is offset from the first.
+fn patterns_agree(first: &[usize], second: &[usize], dx: i32, dy: i32, size: i32) -> bool {
+    for x in std::cmp::max(0, dx)..std::cmp::min(size, size + dx) {
+        for y in std::cmp::max(0, dy)..std::cmp::min(size, size + dy) {
+            if first[(x * size + y) as usize] != second[((x - dx) * size + (y - dy)) as usize] {
+                return false;
+            }
+        }
+    }
+
+    return true;
+}
+
+/// The patterns still possible at each position. Every pattern ruled out is
+/// recorded in a trail, so choices can be undone when they lead to a contradiction.
+struct Wave<'a> {
+    model: &'a WfcModel,
+    width: i32,
+    height: i32,
+    possible: Vec<Vec<bool>>,
+    num_possible: Vec<usize>,
+    // for each position, pattern and direction, the number of patterns still
+    // possible at the neighbor in the opposite direction that allow the pattern here
+    supports: Vec<Vec<[usize; 4]>>,
+    trail: Vec<(usize, usize)>,
+    pending: Vec<(usize, usize)>,
+    contradiction: bool,
+}
+
+impl<'a> Wave<'a> {
+    fn new(model: &'a WfcModel, width: i32, height: i32) -> Wave<'a> {
+        let num_cells = (width * height) as usize;
+        let num_patterns = model.patterns.len();
+
+        let mut pattern_supports = Vec::new();
+        for pattern in 0..num_patterns {
+            let mut support = [0; 4];
+            for direction in 0..DIRECTIONS.len() {
+                support[direction] = model.propagator[opposite(direction)][pattern].len();
+            }
+            pattern_supports.push(support);
+        }
+
+        return Wave {
+            model,
+            width,
+            height,
+            possible: vec!(vec!(true; num_patterns); num_cells),
+            num_possible: vec!(num_patterns; num_cells),
+            supports: vec!(pattern_supports; num_cells),
+            trail: Vec::new(),
+            pending: Vec::new(),
+            contradiction: false,
+        };
+    }
+
+    fn neighbor(&self, cell: usize, direction: usize) -> Option<usize> {
+        let (dx, dy) = DIRECTIONS[direction];
+        let x = cell as i32 % self.width + dx;
+        let y = cell as i32 / self.width + dy;
+
+        if x >= 0 && y >= 0 && x < self.width && y < self.height {
+            return Some((x + y * self.width) as usize);
+        }
+
+        return None;
+    }
+
+    fn ban(&mut self, cell: usize, pattern: usize) {
+        if !self.possible[cell][pattern] {
+            return;
+        }
+
+        self.possible[cell][pattern] = false;
+        self.num_possible[cell] -= 1;
+        if self.num_possible[cell] == 0 {
+            self.contradiction = true;
+        }
+        self.trail.push((cell, pattern));
+
+        let model = self.model;
+        for direction in 0..DIRECTIONS.len() {
+            if let Some(neighbor) = self.neighbor(cell, direction) {
+                for other in model.propagator[direction][pattern].iter() {
+                    self.supports[neighbor][*other][direction] -= 1;
+
+                    if self.supports[neighbor][*other][direction] == 0 && self.possible[neighbor][*other] {
+                        self.pending.push((neighbor, *other));
+                    }
+                }
+            }
+        }
+    }
+
+    fn propagate(&mut self) {
+        while !self.contradiction {
+            match self.pending.pop() {
+                Some((cell, pattern)) => self.ban(cell, pattern),
+                None => break,
+            }
+        }
+    }
+
+    /// Put back every pattern ruled out since the trail was the given length.
+    fn undo(&mut self, trail_len: usize) {
+        let model = self.model;
+
+        while self.trail.len() > trail_len {
+            let (cell, pattern) = self.trail.pop().unwrap();
+            self.possible[cell][pattern] = true;
+            self.num_possible[cell] += 1;
+
+            for direction in 0..DIRECTIONS.len() {
+                if let Some(neighbor) = self.neighbor(cell, direction) {
+                    for other in model.propagator[direction][pattern].iter() {
+                        self.supports[neighbor][*other][direction] += 1;
+                    }
+                }
+            }
+        }
+
+        self.pending.clear();
+        self.contradiction = false;
+    }
+
+    /// A random position out of those with the fewest patterns left,
+    /// or None if every position is decided.
+    fn least_certain(&self, rng: &mut SmallRng) -> Option<usize> {
+        let fewest = self.num_possible.iter().filter(|num| **num > 1).min()?;
+
+        let cells: Vec<usize> =
+            (0..self.num_possible.len()).filter(|cell| self.num_possible[*cell] == *fewest).collect();
+
+        return cells.choose(rng).map(|cell| *cell);
+    }
+}
+
+/// Load the maps that wave function collapse learns from.
+pub fn load_samples(config: &Config, file_names: &[String]) -> Vec<Map> {
+    let mut maps = Vec::new();
+
+    for file_name in file_names.iter() {
+        let mut data = GameData::new(Map::empty(), Entities::new());
+        read_map_xp(config, &mut data, &mut MsgLog::new(), file_name);
+        maps.push(data.map);
+    }
+
+    return maps;
+}
+
+/// Generate a map that looks like the example maps the model was built from.
+/// If the map can't be generated, or doesn't have enough room for the player,
+/// an island is made instead.
+/// Returns the player's starting position.
+pub fn make_wfc(data: &mut GameData,
+                config: &Config,
+                msg_log: &mut MsgLog,
+                model: &WfcModel,
+                rng: &mut SmallRng) -> Pos {
+    let width = data.map.width();
+    let height = data.map.height();
+    let swims = data.find_player().map_or(false, |player_id| data.entities.can_swim(player_id));
+
+    if let Some(map) = model.generate(width, height, config.wfc_max_backtracks, rng) {
+        data.map = map;
+
+        // the largest area the player can get around is used for the level
+        let mut region: HashSet<Pos> = HashSet::new();
+        for x in 0..width {
+            for y in 0..height {
+                let pos = Pos::new(x, y);
+                if !data.map[pos].blocked && !region.contains(&pos) {
+                    let reachable = reachable_positions(&data.map, pos, swims);
+                    if reachable.len() > region.len() {
+                        region = reachable;
+                    }
+                }
+            }
+        }
+
+        // sort so the layout only depends on the rng, not on hashing order
+        let mut open: Vec<Pos> = region.into_iter().filter(|pos| !data.map[*pos].blocked).collect();
+        open.sort_by_key(|pos| (pos.x, pos.y));
+
+        if open.len() > 2 && open.len() as f32 >= (width * height) as f32 * WFC_MIN_OPEN_FRACTION {
+            let start = *open.choose(rng).unwrap();
+            let key_pos = *open.iter().max_by_key(|pos| distance(start, **pos)).unwrap();
+
+            let mut exit_pos = *open.choose(rng).unwrap();
+            while exit_pos == start || exit_pos == key_pos {
+                exit_pos = *open.choose(rng).unwrap();
+            }
+
+            if mutually_reachable(&data.map, &[start, key_pos, exit_pos], swims).is_ok() {
+                // the exit keeps the walls and height of the tile it replaces
+                let mut exit = Tile::exit();
+                exit.left_wall = data.map[exit_pos].left_wall;
+                exit.bottom_wall = data.map[exit_pos].bottom_wall;
+                exit.elevation = data.map[exit_pos].elevation;
+                data.map[exit_pos] = exit;
+
+                make_key(&mut data.entities, config, key_pos, msg_log);
+
+                data.map.update_map();
+
+                return start;
+            }
+        }
+    }
+
+    warn!("wave function collapse failed, making an island instead");
+    data.map = Map::from_dims(width as usize, height as usize);
+    return make_island(data, config, msg_log, rng);
+}
+
+#[test]
+pub fn test_wfc_model() {
+    // columns of walls, with a short wall on the left of every tile beside them
+    let mut sample = Map::from_dims(9, 9);
+    for x in 0..9 {
+        for y in 0..9 {
+            if x % 3 == 0 {
+                sample[(x, y)] = Tile::wall();
+            } else if x % 3 == 1 {
+                sample[(x, y)].left_wall = Wall::ShortWall;
+            }
+        }
+    }
+
+    let model = WfcModel::from_maps(&[sample.clone()], 2);
+    assert!(model.num_patterns() > 0);
+
+    let mut rng: SmallRng = SeedableRng::seed_from_u64(0);
+    let map = model.generate(15, 12, 100, &mut rng).unwrap();
+    assert_eq!(15, map.width());
+    assert_eq!(12, map.height());
+
+    // every pair of tiles beside each other was seen in the sample
+    let seen = |first: Tile, second: Tile, dx: i32, dy: i32| {
+        for x in 0..(sample.width() - dx) {
+            for y in 0..(sample.height() - dy) {
+                if sample[(x, y)] == first && sample[(x + dx, y + dy)] == second {
+                    return true;
+                }
+            }
+        }
+        return false;
+    };
+
+    for x in 0..map.width() {
+        for y in 0..map.height() {
+            if x + 1 < map.width() {
+                assert!(seen(map[(x, y)], map[(x + 1, y)], 1, 0));
+            }
+            if y + 1 < map.height() {
+                assert!(seen(map[(x, y)], map[(x, y + 1)], 0, 1));
+            }
+        }
+    }
+
+    // the same seed gives the same map
+    let mut rng: SmallRng = SeedableRng::seed_from_u64(0);
+    let other_map = model.generate(15, 12, 100, &mut rng).unwrap();
+    assert!(map.tiles.iter().flatten().zip(other_map.tiles.iter().flatten()).all(|(first, second)| first == second));
+}
+
+#[test]
+pub fn test_wfc() {
+    let mut config = Config::from_file("../config.yaml");
+    config.wfc_sample_maps = vec!("../resources/map.xp".to_string());
+
+    let model = WfcModel::from_config(&config);
+    for seed in 0..3 {
+        let mut game = Game::new(seed, config.clone()).unwrap();
+        game.data.map = Map::from_dims(MAP_WIDTH as usize, MAP_HEIGHT as usize);
+
+        let player_pos = make_wfc(&mut game.data, &game.config, &mut game.msg_log, &model, &mut game.rng);
+        assert_eq!(Ok(()), check_solvable(&game.data, player_pos));
+    }
+
+    // without any examples, an island is made instead
+    config.wfc_sample_maps = Vec::new();
+    let model = WfcModel::from_config(&config);
+    let mut game = Game::new(0, config.clone()).unwrap();
+    game.data.map = Map::from_dims(MAP_WIDTH as usize, MAP_HEIGHT as usize);
+
+    let player_pos = make_wfc(&mut game.data, &game.config, &mut game.msg_log, &model, &mut game.rng);
+    assert_eq!(Ok(()), check_solvable(&game.data, player_pos));
+    assert_eq!(MAP_WIDTH, game.data.map.width());
+}
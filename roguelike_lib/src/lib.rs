@@ -14,13 +14,43 @@ use roguelike_core::config::Config;
 use roguelike_core::map::*;
 
 use roguelike_engine::game::*;
-use roguelike_engine::make_map::read_map_xp;
+use roguelike_engine::make_map::make_map;
 use roguelike_engine::actions::*;
 
 
 // TODO pass map back and forth
 
 
+/// The tile layout handed out by read_map. This is kept separate from Tile
+/// so that adding fields to Tile does not change what callers read.
+#[derive(Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct MapTile {
+    pub blocked: bool,
+    pub block_sight: bool,
+    pub explored: bool,
+    pub tile_type: TileType,
+    pub bottom_wall: Wall,
+    pub left_wall: Wall,
+    pub chr: u8,
+    pub surface: Surface,
+}
+
+impl From<Tile> for MapTile {
+    fn from(tile: Tile) -> MapTile {
+        return MapTile { blocked: tile.blocked,
+                         block_sight: tile.block_sight,
+                         explored: tile.explored,
+                         tile_type: tile.tile_type,
+                         bottom_wall: tile.bottom_wall,
+                         left_wall: tile.left_wall,
+                         chr: tile.chr,
+                         surface: tile.surface,
+        };
+    }
+}
+
+
 #[no_mangle]
 pub extern "C" fn create_game(seed: u64, config_name: *mut i8, map_name: *mut i8) -> *mut Game {
     simple_logging::log_to_file("game.log", LevelFilter::Trace).unwrap();
@@ -45,10 +75,7 @@ pub extern "C" fn create_game(seed: u64, config_name: *mut i8, map_name: *mut i8
         let map_str = map_cstr.to_str().unwrap();
         trace!("loading map {}", map_str);
 
-        let pos = read_map_xp(&game.config, &mut game.data, &mut game.msg_log, map_str);
-
-        let player_id = game.data.find_player().unwrap();
-        game.data.entities.pos[&player_id] = Pos::from(pos);
+        make_map(&MapConfig::from_file(map_str), &mut game);
     }
 
     let raw_ptr = Box::into_raw(game);
@@ -136,7 +163,7 @@ pub extern "C" fn read_message(game_ptr: *mut Game, msg_ptr: *mut u8, msg_len: *
 }
 
 #[no_mangle]
-pub extern "C" fn read_map(game_ptr: *mut Game, width: *mut i32, height: *mut i32) -> *mut Tile {
+pub extern "C" fn read_map(game_ptr: *mut Game, width: *mut i32, height: *mut i32) -> *mut MapTile {
     trace!("reading map");
 
     let game: Box<Game>;
@@ -153,14 +180,14 @@ pub extern "C" fn read_map(game_ptr: *mut Game, width: *mut i32, height: *mut i3
             trace!("getting tiles");
 
             let count = *height * *width;
-            let temp_buf = alloc_buffer(count * std::mem::size_of::<Tile>() as i32);
-            tile_buf = temp_buf.cast::<Tile>();
+            let temp_buf = alloc_buffer(count * std::mem::size_of::<MapTile>() as i32);
+            tile_buf = temp_buf.cast::<MapTile>();
 
             for x in 0..game.data.map.width() {
                 for y in 0..game.data.map.height() {
                     let offset = x + y * game.data.map.width();
                     let tile = game.data.map[(x, y)];
-                    *tile_buf.offset(offset as isize) = tile;
+                    *tile_buf.offset(offset as isize) = MapTile::from(tile);
                 }
             }
 
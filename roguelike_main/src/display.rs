@@ -271,8 +271,8 @@ impl DisplayState {
                 }
             }
 
-            Msg::ItemThrow(_thrower, item_id, start, end) => {
-                let sound_aoe = data.map.aoe_fill(AoeEffect::Sound, end, SOUND_RADIUS_STONE);
+            Msg::ItemLanded(item_id, start, end, sound_radius) => {
+                let sound_aoe = data.map.aoe_fill(AoeEffect::Sound, end, sound_radius);
 
                 let chr = data.entities.chr[&item_id];
                 let item_sprite =
@@ -361,6 +361,26 @@ impl DisplayState {
                 }
             }
 
+            Msg::WallKick(kicker, _pos) => {
+                if data.entities.typ[&kicker] == EntityType::Player {
+                    let kick_sprite =
+                        self.new_sprite("player_wall_kick".to_string(), config.player_wall_kick_speed)
+                                          .unwrap();
+                    let kick_anim = Animation::Once(kick_sprite);
+                    let kick_key = self.play_animation(kick_anim);
+
+                    let idle_sprite =
+                        self.new_sprite("player_idle".to_string(), config.idle_speed)
+                                          .unwrap();
+                    let idle_anim = Animation::Loop(idle_sprite);
+                    let idle_key = self.play_animation(idle_anim);
+
+                    data.entities.animation[&kicker].clear();
+                    data.entities.animation[&kicker].push_back(kick_key);
+                    data.entities.animation[&kicker].push_back(idle_key);
+                }
+            }
+
             Msg::SpawnedObject(entity_id, _typ, _pos, _name) => {
                 if data.entities.typ[&entity_id] == EntityType::Player {
                     let sprite = self.new_sprite("player_idle".to_string(), config.idle_speed)
@@ -23,12 +23,13 @@ use rand::prelude::*;
 
 use walkdir::WalkDir;
 
-use log::LevelFilter;
+use log::{LevelFilter, error};
 use simple_logging;
 use logging_timer::timer;
 
 use roguelike_core::types::*;
 use roguelike_core::config::Config;
+use roguelike_core::map::MapSource;
 use roguelike_core::constants::*;
 use roguelike_core::movement::Direction;
 use roguelike_core::utils::{add_pos};
@@ -37,6 +38,7 @@ use roguelike_engine::game::*;
 use roguelike_engine::generation::*;
 use roguelike_engine::actions::*;
 use roguelike_engine::make_map::{make_map, read_map_xp};
+use roguelike_engine::text_map::read_map_text_file;
 
 use crate::throttler::*;
 use crate::render::*;
@@ -234,13 +236,36 @@ pub fn run(seed: u64) -> Result<(), String> {
 
         /* Reload map if configured to do so */
         let config_timer = timer!("CONFIG");
-        if game.config.load_map_file_every_frame && Path::new("resources/map.xp").exists() {
-            let player = game.data.find_player().unwrap();
+        if let MapSource::FromFile(map_file) = game.config.map_load.source.clone() {
+            if game.config.load_map_file_every_frame && Path::new(&map_file).exists() {
+                let player = game.data.find_player().unwrap();
+                let level_entities = game.data.level_entities(player);
+
+                if map_file.ends_with(".txt") {
+                    // a map that is being edited may not load, so keep the current map until it does
+                    match read_map_text_file(&game.config, &mut game.data, &mut game.msg_log, &map_file) {
+                        Ok(player_pos) => {
+                            for id in level_entities {
+                                game.data.remove_entity(id);
+                            }
+                            if let Some(player_pos) = player_pos {
+                                game.data.entities.set_pos(player, player_pos);
+                            }
+                        }
 
-            let map_file = format!("resources/{}", game.config.map_file);
-            game.data.entities.clear();
-            let player_pos = read_map_xp(&game.config, &mut game.data, &mut game.msg_log, &map_file);
-            game.data.entities.set_pos(player, Pos::from(player_pos));
+                        Err(err) => {
+                            error!("could not reload map {}: {}", map_file, err);
+                        }
+                    }
+                } else {
+                    for id in level_entities {
+                        game.data.remove_entity(id);
+                    }
+                    if let Some(player_pos) = read_map_xp(&game.config, &mut game.data, &mut game.msg_log, &map_file) {
+                        game.data.entities.set_pos(player, player_pos);
+                    }
+                }
+            }
         }
 
         /* Reload Configuration */
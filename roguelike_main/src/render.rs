@@ -11,7 +11,7 @@ use roguelike_core::constants::*;
 use roguelike_core::movement::*;
 use roguelike_core::config::*;
 use roguelike_core::animation::{Effect, Animation, AnimKey};
-use roguelike_core::utils::{line, item_primary_at, distance, move_towards, lerp_color};
+use roguelike_core::utils::{line, distance, move_towards, lerp_color};
 
 use roguelike_engine::game::*;
 
@@ -340,7 +340,7 @@ fn render_inventory(display_state: &mut DisplayState, game: &mut Game, area: &Ar
     let mut item_index = 0;
     let item_ids = game.data.entities.inventory[&player_id].clone();
 
-    for (index, obj_id) in item_ids.iter().enumerate() {
+    for obj_id in item_ids.iter() {
         let color;
         match &game.data.entities.item.get(obj_id) {
             Some(Item::Stone) => {
@@ -364,13 +364,16 @@ fn render_inventory(display_state: &mut DisplayState, game: &mut Game, area: &Ar
 
         // place object name
         let text_pos = Pos::new(2, y_pos);
-        let item_marker =
-            if index == 0 && item_primary_at(player_id, &mut game.data.entities, 0) {
-                "<"
-            } else {
-                ""
-            };
-        let item_text = format!(" {:?} {}", game.data.entities.name[obj_id], item_marker);
+        let item_slot =
+            game.data.entities.slot.get(obj_id).map_or("".to_string(), |slot| format!("({})", slot));
+        let item_durability =
+            game.data.entities.durability.get(obj_id).map_or("".to_string(), |durability| {
+                format!(" {}/{}", durability.current, durability.max)
+            });
+        let item_count = game.data.entities.stack_count(*obj_id);
+        let item_stack =
+            if item_count > 1 { format!(" x{}", item_count) } else { "".to_string() };
+        let item_text = format!(" {:?}{} {}{}", game.data.entities.name[obj_id], item_stack, item_slot, item_durability);
         display_state.draw_text(&item_text,
                                 text_pos,
                                 color,
@@ -387,6 +390,27 @@ fn render_inventory(display_state: &mut DisplayState, game: &mut Game, area: &Ar
                                 text_pos,
                                 game.config.color_ice_blue,
                                 area);
+        y_pos += 1;
+    }
+
+    // capacity readout
+    if let Some(capacity) = game.data.entities.capacity.get(&player_id) {
+        let weight = game.data.entities.carried_weight(player_id);
+        let color = if game.data.entities.encumbered(player_id) {
+            game.config.color_red
+        } else {
+            game.config.color_ice_blue
+        };
+
+        let capacity_text = format!("weight {}/{}  items {}/{}",
+                                    weight,
+                                    capacity.max_weight,
+                                    item_ids.len(),
+                                    capacity.max_items);
+        display_state.draw_text(&capacity_text,
+                                Pos::new(1, y_pos + 1),
+                                color,
+                                area);
     }
 }
 
@@ -445,7 +469,7 @@ fn render_background(display_state: &mut DisplayState, game: &mut Game, area: &A
                                   area);
 
                         let tile = &map.tiles[x as usize][y as usize];
-                        if tile.tile_type == TileType::Water {
+                        if tile.tile_type.is_water() {
                             let color = tile_color(&config, x, y, tile, visible);
                             let chr = tile.chr;
                             draw_char(canvas, font_image, chr as char, map_pos, color, area);
@@ -496,7 +520,7 @@ fn render_map(display_state: &mut DisplayState, game: &mut Game, area: &Area) {
 
             // if the tile is not empty or water, draw it
             let color = tile_color(&game.config, x, y, tile, visible);
-            if chr != MAP_EMPTY_CHAR && tile.tile_type != TileType::Water {
+            if chr != MAP_EMPTY_CHAR && !tile.tile_type.is_water() {
                 display_state.draw_char(chr as char, pos, color, area);
             }
 
@@ -509,6 +533,10 @@ fn render_map(display_state: &mut DisplayState, game: &mut Game, area: &Area) {
                     display_state.draw_char(MAP_RUBBLE as char, pos, game.config.color_light_green, area);
                 }
 
+                Surface::Burnt => {
+                    display_state.draw_char(MAP_RUBBLE as char, pos, game.config.color_warm_grey, area);
+                }
+
                 Surface::Floor => {
                 }
             }
@@ -544,10 +572,26 @@ fn render_map(display_state: &mut DisplayState, game: &mut Game, area: &Area) {
                 }
             }
 
+            // mark the edges of raised ground on the sides that drop away
+            let elevation = tile.elevation;
+            if elevation > 0 {
+                let ledges = vec!((Pos::new(0, 1), MAP_THIN_WALL_BOTTOM),
+                                  (Pos::new(-1, 0), MAP_THIN_WALL_LEFT),
+                                  (Pos::new(1, 0), MAP_THIN_WALL_RIGHT),
+                                  (Pos::new(0, -1), MAP_THIN_WALL_TOP));
+                for (offset, chr) in ledges {
+                    let next_pos = Pos::new(x + offset.x, y + offset.y);
+                    if game.data.map.is_within_bounds(next_pos) &&
+                       game.data.map[next_pos].elevation < elevation {
+                        display_state.draw_char(chr as char, pos, game.config.color_medium_grey, area);
+                    }
+                }
+            }
+
             // Draw a square around this tile to help distinguish it visually in the grid
             let outline_color = Color::white();
             let alpha;
-            if visible && game.data.map[pos].tile_type != TileType::Water {
+            if visible && !game.data.map[pos].tile_type.is_water() {
                 if game.settings.overlay {
                     alpha = game.config.grid_alpha_overlay;
                 } else {
@@ -1005,6 +1049,11 @@ fn tile_color(config: &Config, _x: i32, _y: i32, tile: &Tile, visible: bool) ->
         (TileType::Water, false) =>
             config.color_blueish_grey,
 
+        (TileType::ShallowWater, true) =>
+            config.color_tile_blue_light,
+        (TileType::ShallowWater, false) =>
+            config.color_tile_blue_dark,
+
         (TileType::ShortWall, true) =>
             config.color_light_brown,
         (TileType::ShortWall, false) =>